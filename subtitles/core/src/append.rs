@@ -0,0 +1,462 @@
+// Incremental documents of live and segmented streams (DASH stpp): every
+// segment is a complete TTML document whose cues are merged into the loaded
+// one. Cues repeated by consecutive segments are kept once and the cues that
+// ended before the back-buffer are evicted, so the document does not grow
+// with the stream.
+use super::convert::format_clock_time;
+use super::units::{Length, Unit};
+use super::{
+    get_cell_resolution, time_to_ms, CellResolution, Head, Metadata, Style, Subtitles, P, TT,
+};
+use quick_xml::de::from_str;
+use std::collections::HashSet;
+
+/// Document time kept behind the current time, in milliseconds.
+pub const DEFAULT_BACK_BUFFER_MS: i64 = 30000;
+
+/// End of the cues of decoded broadcast streams, which stay on screen until
+/// the next update replaces them (`replace_stream_cues`). Without an update
+/// they end after an hour.
+pub const UNTIL_NEXT_UPDATE_MS: i64 = 3_600_000;
+
+/// Identity of a cue: id in its segment, begin and end. A cue repeated by the
+/// next segment has the same key, even if the first one was renamed.
+type CueKey = (String, i64, i64);
+
+fn get_time(time: Option<&String>) -> i64 {
+    time.and_then(|time| time_to_ms(time).ok()).unwrap_or(-1)
+}
+
+fn get_paragraph_key(p: &P) -> CueKey {
+    (
+        p.source_id.clone().unwrap_or_else(|| p.id.clone()),
+        get_time(Some(&p.begin)),
+        get_time(Some(&p.end)),
+    )
+}
+
+/// `id`, or `id-n` with the first `n` from `suffix` not taken by another cue.
+fn get_unique_id(id: &str, suffix: usize, ids: &HashSet<String>) -> String {
+    if !ids.contains(id) {
+        return id.to_string();
+    }
+    (suffix..)
+        .map(|n| format!("{}-{}", id, n))
+        .find(|candidate| !ids.contains(candidate))
+        .unwrap()
+}
+
+/// Converts the cell lengths of an attribute to another cell size. `scale`
+/// takes the position and the number of lengths in each comma separated
+/// part (e.g. a shadow).
+fn convert_cells(value: &str, scale: impl Fn(usize, usize) -> f32) -> String {
+    value
+        .split(',')
+        .map(|part| {
+            let count = part
+                .split(' ')
+                .filter(|token| Length::parse(token).is_ok())
+                .count();
+            let mut index = 0;
+            part.split(' ')
+                .map(|token| match Length::parse(token) {
+                    Ok(length) => {
+                        index += 1;
+                        match length.unit {
+                            Unit::Cell => format!("{}c", length.value * scale(index - 1, count)),
+                            _ => token.to_string(),
+                        }
+                    }
+                    Err(_) => token.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Keeps the sizes of a segment with another cell resolution than the
+/// document (e.g. Teletext, 40 by 25) by converting its cell lengths to the
+/// cells of the document. Its regions get the initial font size of the
+/// segment, one of its cells.
+fn convert_head_cells(head: &mut Head, from: CellResolution, to: CellResolution) {
+    let x = to.columns as f32 / from.columns as f32;
+    let y = to.rows as f32 / from.rows as f32;
+    let horizontal_first = |index: usize, _: usize| if index == 0 { x } else { y };
+    // A single font size is the height of a square glyph.
+    let font_size_scale = |index: usize, count: usize| if count == 2 && index == 0 { x } else { y };
+    for style in head.styling.styles.iter_mut() {
+        style.font_size = style
+            .font_size
+            .as_ref()
+            .map(|value| convert_cells(value, font_size_scale));
+        style.line_height = style
+            .line_height
+            .as_ref()
+            .map(|value| convert_cells(value, |_, _| y));
+        style.line_padding = style
+            .line_padding
+            .as_ref()
+            .map(|value| convert_cells(value, |_, _| x));
+        style.text_outline = style
+            .text_outline
+            .as_ref()
+            .map(|value| convert_cells(value, |_, _| y));
+        style.text_shadow = style
+            .text_shadow
+            .as_ref()
+            .map(|value| convert_cells(value, horizontal_first));
+    }
+    // Padding is before, end, after and start: vertical lengths first.
+    let padding_scale =
+        |index: usize, count: usize| if count > 1 && index % 2 == 1 { x } else { y };
+    let font_size_id = format!("cells-{}-{}", from.columns, from.rows);
+    for region in head.layout.regions.iter_mut() {
+        region.origin = region
+            .origin
+            .as_ref()
+            .map(|value| convert_cells(value, horizontal_first));
+        region.extent = region
+            .extent
+            .as_ref()
+            .map(|value| convert_cells(value, horizontal_first));
+        region.padding = region
+            .padding
+            .as_ref()
+            .map(|value| convert_cells(value, padding_scale));
+        region.style = Some(match region.style.take() {
+            Some(style) => format!("{} {}", font_size_id, style),
+            None => font_size_id.clone(),
+        });
+    }
+    head.styling.styles.push(Style {
+        id: font_size_id,
+        font_size: Some(format!("{}c", y)),
+        ..Default::default()
+    });
+}
+
+/// Adds the styles, regions and embedded images of a segment that the
+/// document does not have. Segments of a stream repeat the same head, so on
+/// conflicting ids the first definition is kept.
+fn merge_head(head: &mut Head, segment_head: Head) {
+    let style_ids: HashSet<String> = head.styling.styles.iter().map(|s| s.id.clone()).collect();
+    head.styling.styles.extend(
+        segment_head
+            .styling
+            .styles
+            .into_iter()
+            .filter(|style| !style_ids.contains(&style.id)),
+    );
+    let region_ids: HashSet<String> = head.layout.regions.iter().map(|r| r.id.clone()).collect();
+    head.layout.regions.extend(
+        segment_head
+            .layout
+            .regions
+            .into_iter()
+            .filter(|region| !region_ids.contains(&region.id)),
+    );
+    let segment_images = match segment_head.metadata {
+        Some(metadata) if !metadata.images.is_empty() => metadata.images,
+        _ => return,
+    };
+    match head.metadata.as_mut() {
+        Some(metadata) => {
+            let image_ids: HashSet<Option<String>> = metadata
+                .images
+                .iter()
+                .map(|image| image.id.clone())
+                .collect();
+            metadata.images.extend(
+                segment_images
+                    .into_iter()
+                    .filter(|image| image.id.is_none() || !image_ids.contains(&image.id)),
+            );
+        }
+        None => {
+            head.metadata = Some(Metadata {
+                ppd: None,
+                document_metadata: None,
+                images: segment_images,
+            })
+        }
+    }
+}
+
+impl Subtitles {
+    /// Document time kept behind the current time when segments are
+    /// appended, older cues are evicted.
+    pub fn set_back_buffer(&mut self, ms: i64) -> Result<(), String> {
+        if ms < 0 {
+            return Err(format!("Invalid back-buffer: {}", ms));
+        }
+        self.back_buffer_ms = ms;
+        Ok(())
+    }
+
+    /// Merges the cues of a segment into the loaded document, or loads it if
+    /// there is none. The root parameters (cell resolution, extent) and the
+    /// source checked by `validate` stay the ones of the loaded document, the
+    /// cell lengths of a segment with another cell resolution are converted.
+    pub fn append_document(&mut self, xml: &str) -> Result<(), String> {
+        if self.tt.is_none() {
+            return self.load(xml);
+        }
+        let mut segment: TT =
+            from_str(xml).map_err(|error| format!("Invalid document: {}", error))?;
+        let segment_cells = get_cell_resolution(segment.ttp_cell_resolution.as_ref());
+        if segment_cells != self.tt_root_config.cell_resolution {
+            convert_head_cells(
+                &mut segment.head,
+                segment_cells,
+                self.tt_root_config.cell_resolution,
+            );
+        }
+        let visible_subtitles = self.take_visible_subtitles();
+        self.appended_documents += 1;
+        let suffix = self.appended_documents;
+        let tt = self.tt.as_mut().unwrap();
+        // Images without id are named after their position, which eviction
+        // changes.
+        for (div_index, div) in tt.body.divs.iter_mut().enumerate() {
+            if div.is_image() && div.id.is_none() {
+                div.id = Some(format!("image-{}", div_index));
+            }
+        }
+        let mut keys: HashSet<CueKey> = HashSet::new();
+        let mut ids: HashSet<String> = HashSet::new();
+        for div in tt.body.divs.iter() {
+            if let Some(id) = div.id.as_ref().filter(|_| div.is_image()) {
+                keys.insert((
+                    div.source_id.clone().unwrap_or_else(|| id.clone()),
+                    get_time(div.begin.as_ref()),
+                    get_time(div.end.as_ref()),
+                ));
+                ids.insert(id.clone());
+            }
+            for p in div.p.iter() {
+                keys.insert(get_paragraph_key(p));
+                ids.insert(p.id.clone());
+            }
+        }
+        merge_head(&mut tt.head, segment.head);
+        for (div_index, mut div) in segment.body.divs.into_iter().enumerate() {
+            if div.is_image() {
+                let id = div
+                    .id
+                    .take()
+                    .unwrap_or_else(|| format!("image-{}-{}", suffix, div_index));
+                let key = (id, get_time(div.begin.as_ref()), get_time(div.end.as_ref()));
+                if keys.contains(&key) {
+                    continue;
+                }
+                let id = get_unique_id(&key.0, suffix, &ids);
+                if id != key.0 {
+                    div.source_id = Some(key.0.clone());
+                }
+                div.id = Some(id.clone());
+                ids.insert(id);
+                keys.insert(key);
+            }
+            div.p.retain(|p| !keys.contains(&get_paragraph_key(p)));
+            for p in div.p.iter_mut() {
+                let key = get_paragraph_key(p);
+                let id = get_unique_id(&p.id, suffix, &ids);
+                if id != p.id {
+                    p.source_id = Some(std::mem::replace(&mut p.id, id));
+                }
+                ids.insert(p.id.clone());
+                keys.insert(key);
+            }
+            if div.is_image() || !div.p.is_empty() {
+                tt.body.divs.push(div);
+            }
+        }
+        self.evict_subtitles();
+        self.reindex(visible_subtitles);
+        Ok(())
+    }
+
+    /// Decoded broadcast streams (DVB, Teletext) replace what is on screen
+    /// with every update: the cues with ids starting with `prefix` shown at
+    /// `ms` end there, and the ones beginning at `ms` are removed.
+    pub fn replace_stream_cues(&mut self, prefix: &str, ms: i64) {
+        let is_replaced = |id: Option<&String>, begin: Option<&String>, end: Option<&String>| {
+            id.is_some_and(|id| id.starts_with(prefix))
+                && get_time(begin) <= ms
+                && get_time(end) > ms
+        };
+        let is_changed = self.tt.as_ref().is_some_and(|tt| {
+            tt.body.divs.iter().any(|div| {
+                (div.is_image()
+                    && is_replaced(div.id.as_ref(), div.begin.as_ref(), div.end.as_ref()))
+                    || div
+                        .p
+                        .iter()
+                        .any(|p| is_replaced(Some(&p.id), Some(&p.begin), Some(&p.end)))
+            })
+        });
+        if !is_changed {
+            return;
+        }
+        let visible_subtitles = self.take_visible_subtitles();
+        let end = format_clock_time(ms, '.');
+        let tt = self.tt.as_mut().unwrap();
+        for div in tt.body.divs.iter_mut() {
+            if div.is_image() && is_replaced(div.id.as_ref(), div.begin.as_ref(), div.end.as_ref())
+            {
+                div.end = Some(end.clone());
+            }
+            div.p.retain(|p| {
+                !is_replaced(Some(&p.id), Some(&p.begin), Some(&p.end))
+                    || get_time(Some(&p.begin)) < ms
+            });
+            for p in div.p.iter_mut() {
+                if is_replaced(Some(&p.id), Some(&p.begin), Some(&p.end)) {
+                    p.end = end.clone();
+                }
+            }
+        }
+        // Images that began at `ms` now end there too.
+        tt.body.divs.retain(|div| {
+            if div.is_image() {
+                get_time(div.end.as_ref()) > get_time(div.begin.as_ref())
+            } else {
+                !div.p.is_empty()
+            }
+        });
+        self.reindex(visible_subtitles);
+    }
+
+    /// Removes the cues that ended before the back-buffer.
+    fn evict_subtitles(&mut self) {
+        let limit = self.current_ms.saturating_sub(self.back_buffer_ms);
+        let tt = match self.tt.as_mut() {
+            Some(tt) => tt,
+            None => return,
+        };
+        for div in tt.body.divs.iter_mut() {
+            div.p.retain(|p| get_time(Some(&p.end)) >= limit);
+        }
+        tt.body.divs.retain(|div| {
+            if div.is_image() {
+                get_time(div.end.as_ref()) >= limit
+            } else {
+                !div.p.is_empty()
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_view::RecordingView;
+
+    fn segment(paragraphs: &[(&str, &str, &str)]) -> String {
+        let paragraphs: String = paragraphs
+            .iter()
+            .map(|(id, begin, end)| {
+                format!(
+                    r#"<tt:p xml:id="{}" begin="{}" end="{}"><tt:span>{}</tt:span></tt:p>"#,
+                    id, begin, end, id
+                )
+            })
+            .collect();
+        format!(
+            r#"<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:timeBase="media">
+  <tt:head>
+    <tt:layout>
+      <tt:region xml:id="r1" tts:origin="10% 80%" tts:extent="80% 15%"/>
+    </tt:layout>
+  </tt:head>
+  <tt:body><tt:div region="r1">{}</tt:div></tt:body>
+</tt:tt>"#,
+            paragraphs
+        )
+    }
+
+    fn get_ids(subtitles: &Subtitles) -> Vec<String> {
+        subtitles
+            .get_timed_subtitles()
+            .iter()
+            .map(|timed_subtitle| timed_subtitle.subtitle.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn merges_segments_without_duplicates() {
+        let mut subtitles = Subtitles::new();
+        subtitles
+            .append_document(&segment(&[
+                ("sub1", "00:00:01.000", "00:00:02.000"),
+                ("sub2", "00:00:03.000", "00:00:05.000"),
+            ]))
+            .unwrap();
+        // The next segment repeats the cue across the boundary and numbers
+        // its own cues from 1 again.
+        subtitles
+            .append_document(&segment(&[
+                ("sub2", "00:00:03.000", "00:00:05.000"),
+                ("sub1", "00:00:06.000", "00:00:07.000"),
+            ]))
+            .unwrap();
+        assert_eq!(get_ids(&subtitles), vec!["sub1", "sub2", "sub1-1"]);
+        assert_eq!(subtitles.get_subtitles_at(6500)[0].id, "sub1-1");
+        assert!(subtitles.append_document("<tt:tt").is_err());
+    }
+
+    #[test]
+    fn keeps_one_copy_of_renamed_cues_repeated_by_segments() {
+        let mut subtitles = Subtitles::new();
+        subtitles
+            .append_document(&segment(&[("sub1", "00:00:01.000", "00:00:02.000")]))
+            .unwrap();
+        let colliding = segment(&[("sub1", "00:00:06.000", "00:00:07.000")]);
+        for _ in 0..3 {
+            subtitles.append_document(&colliding).unwrap();
+        }
+        assert_eq!(get_ids(&subtitles), vec!["sub1", "sub1-1"]);
+    }
+
+    #[test]
+    fn evicts_cues_behind_the_back_buffer() {
+        let mut subtitles = Subtitles::new();
+        subtitles.set_back_buffer(5000).unwrap();
+        subtitles
+            .append_document(&segment(&[("a", "00:00:01.000", "00:00:02.000")]))
+            .unwrap();
+        subtitles
+            .append_document(&segment(&[("b", "00:00:10.000", "00:00:12.000")]))
+            .unwrap();
+        subtitles.update_subtitles_for_ms(10000);
+        subtitles
+            .append_document(&segment(&[("c", "00:00:20.000", "00:00:22.000")]))
+            .unwrap();
+        assert_eq!(get_ids(&subtitles), vec!["b", "c"]);
+        assert!(subtitles.set_back_buffer(-1).is_err());
+    }
+
+    #[test]
+    fn keeps_subtitles_on_screen_while_appending() {
+        let mut subtitles = Subtitles::new();
+        let view = RecordingView::default();
+        subtitles.set_view(Some(Box::new(view.clone())));
+        subtitles.set_back_buffer(0).unwrap();
+        subtitles
+            .append_document(&segment(&[
+                ("a", "00:00:01.000", "00:00:02.000"),
+                ("b", "00:00:03.000", "00:00:05.000"),
+            ]))
+            .unwrap();
+        subtitles.update_subtitles_for_ms(3000);
+        subtitles
+            .append_document(&segment(&[("c", "00:00:06.000", "00:00:07.000")]))
+            .unwrap();
+        assert_eq!(get_ids(&subtitles), vec!["b", "c"]);
+        assert_eq!(*view.shown.lock().unwrap(), vec!["b"]);
+        subtitles.update_subtitles_for_ms(5000);
+        assert!(view.shown.lock().unwrap().is_empty());
+    }
+}
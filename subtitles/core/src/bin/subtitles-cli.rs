@@ -0,0 +1,290 @@
+// Command line tool to inspect, validate and convert subtitle documents
+// without a browser, e.g. to check files in batch on a server.
+use std::fs;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use subtitles_core::{format_clock_time, Severity, Subtitles};
+
+const USAGE: &str = "Usage:
+  subtitles-cli info <file>
+  subtitles-cli validate <file>...
+  subtitles-cli convert --to vtt|srt|ebu-tt-d <file> [--output <file>] [timeline]
+  subtitles-cli dump --at <time> <file> [--json] [timeline]
+
+Timeline options, applied to the output times:
+  --offset <time>   media time at which the document starts
+  --delay <ms>      delay in milliseconds, may be negative
+  --scale <factor>  media time per document time, e.g. 1.0427
+
+Times are clock times (00:01:02.000) or seconds (62.5). Transport streams
+(.ts) are read with their first subtitle service, timed from their start.";
+
+/// Size of the display element that pixel values are computed for.
+const ELEMENT_WIDTH: i32 = 1920;
+const ELEMENT_HEIGHT: i32 = 1080;
+
+/// "HH:MM:SS.mmm", "MM:SS.mmm" or seconds.
+fn parse_time(time: &str) -> Result<i64, String> {
+    let mut seconds = 0.0;
+    for block in time.split(':') {
+        let value = block
+            .parse::<f64>()
+            .map_err(|_| format!("Invalid time: {}", time))?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!("Invalid time: {}", time));
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Ok((seconds * 1000.0).round() as i64)
+}
+
+fn load(path: &str) -> Result<Subtitles, String> {
+    let mut subtitles = Subtitles::new();
+    load_into(path, &mut subtitles)?;
+    Ok(subtitles)
+}
+
+fn load_into(path: &str, subtitles: &mut Subtitles) -> Result<(), String> {
+    subtitles.set_element_size(ELEMENT_WIDTH, ELEMENT_HEIGHT);
+    if path.ends_with(".ts") {
+        let data = fs::read(path).map_err(|error| format!("{}: {}", path, error))?;
+        return subtitles
+            .append_ts(&data, None)
+            .map_err(|error| format!("{}: {}", path, error));
+    }
+    let xml = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    subtitles
+        .load(&xml)
+        .map_err(|error| format!("{}: {}", path, error))
+}
+
+/// Writes a long output to stdout. A closed pipe (e.g. `| head`) is not an
+/// error.
+fn print_output(output: &str) -> Result<(), String> {
+    let mut stdout = io::stdout();
+    match stdout
+        .write_all(output.as_bytes())
+        .and_then(|_| stdout.flush())
+    {
+        Err(error) if error.kind() != io::ErrorKind::BrokenPipe => Err(error.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Applies the timeline options to a loaded document.
+fn apply_timeline(args: &mut Vec<String>, subtitles: &mut Subtitles) -> Result<(), String> {
+    if let Some(offset) = take_option(args, "--offset")? {
+        subtitles.set_time_offset(parse_time(&offset)?);
+    }
+    if let Some(delay) = take_option(args, "--delay")? {
+        let delay = delay
+            .parse::<i64>()
+            .map_err(|_| format!("Invalid delay: {}", delay))?;
+        subtitles.set_subtitle_delay(delay);
+    }
+    if let Some(scale) = take_option(args, "--scale")? {
+        let scale = scale
+            .parse::<f64>()
+            .map_err(|_| format!("Invalid time scale: {}", scale))?;
+        subtitles.set_time_scale(scale)?;
+    }
+    Ok(())
+}
+
+/// Value of an option like `--to vtt`, removed from the arguments.
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            if index + 1 >= args.len() {
+                return Err(format!("Missing value for {}", name));
+            }
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Ok(Some(value))
+        }
+        None => Ok(None),
+    }
+}
+
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+fn get_single_file(args: &[String]) -> Result<&str, String> {
+    match args {
+        [file] => Ok(file),
+        [] => Err("Missing file".to_string()),
+        _ => Err(format!("Unexpected arguments: {}", args[1..].join(" "))),
+    }
+}
+
+fn info(args: &[String]) -> Result<bool, String> {
+    let path = get_single_file(args)?;
+    let subtitles = load(path)?;
+    let tt = match subtitles.tt.as_ref() {
+        Some(tt) => tt,
+        None => return Err(format!("{}: No document loaded", path)),
+    };
+    println!("File: {}", path);
+    if !tt.xml_lang.is_empty() {
+        println!("Language: {}", tt.xml_lang);
+    }
+    let styles = &tt.head.styling.styles;
+    println!("Styles: {}", styles.len());
+    for style in styles.iter() {
+        println!("  {}", style.id);
+    }
+    let regions = &tt.head.layout.regions;
+    println!("Regions: {}", regions.len());
+    for region in regions.iter() {
+        println!(
+            "  {} origin {} extent {}",
+            region.id,
+            region.origin.as_deref().unwrap_or("auto"),
+            region.extent.as_deref().unwrap_or("auto")
+        );
+    }
+    println!("Subtitles: {}", subtitles.subtitle_refs.len());
+    println!(
+        "Duration: {}",
+        format_clock_time(subtitles.get_duration_ms(), '.')
+    );
+    Ok(true)
+}
+
+/// Every file is checked, the result fails if any of them has errors.
+fn validate(args: &[String]) -> Result<bool, String> {
+    if args.is_empty() {
+        return Err("Missing file".to_string());
+    }
+    let mut is_valid = true;
+    for path in args.iter() {
+        let diagnostics = match load(path) {
+            Ok(subtitles) => subtitles.validate(),
+            Err(error) => {
+                println!("{}: error: {}", path, error);
+                is_valid = false;
+                continue;
+            }
+        };
+        let errors = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .count();
+        for diagnostic in diagnostics.iter() {
+            let severity = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            let line = diagnostic
+                .line
+                .map(|line| line.to_string())
+                .unwrap_or_default();
+            println!(
+                "{}:{}: {}: {} ({})",
+                path, line, severity, diagnostic.message, diagnostic.path
+            );
+        }
+        println!(
+            "{}: {} errors, {} warnings",
+            path,
+            errors,
+            diagnostics.len() - errors
+        );
+        is_valid &= errors == 0;
+    }
+    Ok(is_valid)
+}
+
+fn convert(mut args: Vec<String>) -> Result<bool, String> {
+    let format = take_option(&mut args, "--to")?.ok_or("Missing --to")?;
+    let output = take_option(&mut args, "--output")?;
+    let mut subtitles = Subtitles::new();
+    apply_timeline(&mut args, &mut subtitles)?;
+    let path = get_single_file(&args)?;
+    load_into(path, &mut subtitles)?;
+    let converted = match format.as_str() {
+        "vtt" => subtitles.to_vtt(),
+        "srt" => subtitles.to_srt(),
+        "ebu-tt-d" => subtitles.to_ebu_tt_d(),
+        _ => return Err(format!("Unknown format: {}", format)),
+    };
+    match output {
+        Some(output) => {
+            fs::write(&output, converted).map_err(|error| format!("{}: {}", output, error))?
+        }
+        None => print_output(&converted)?,
+    }
+    Ok(true)
+}
+
+fn dump(mut args: Vec<String>) -> Result<bool, String> {
+    let ms = parse_time(&take_option(&mut args, "--at")?.ok_or("Missing --at")?)?;
+    let json = take_flag(&mut args, "--json");
+    let mut subtitles = Subtitles::new();
+    apply_timeline(&mut args, &mut subtitles)?;
+    let path = get_single_file(&args)?;
+    load_into(path, &mut subtitles)?;
+    let active_subtitles = subtitles.get_subtitles_at(ms);
+    if json {
+        let json =
+            serde_json::to_string_pretty(&active_subtitles).map_err(|error| error.to_string())?;
+        print_output(&json)?;
+        return Ok(true);
+    }
+    println!(
+        "{}: {} subtitles",
+        format_clock_time(ms, '.'),
+        active_subtitles.len()
+    );
+    for subtitle in active_subtitles.iter() {
+        let region = subtitle
+            .region
+            .as_ref()
+            .map(|region| region.id.as_str())
+            .unwrap_or("-");
+        println!("{} (region {})", subtitle.id, region);
+        if let Some(image) = subtitle.image.as_ref() {
+            println!("  [image {}]", image.mime_type);
+        }
+        for line in subtitle.lines.iter() {
+            let text: String = line.iter().map(|run| run.text.as_str()).collect();
+            println!("  {}", text);
+        }
+    }
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+    let command = args.remove(0);
+    let result = match command.as_str() {
+        "info" => info(&args),
+        "validate" => validate(&args),
+        "convert" => convert(args),
+        "dump" => dump(args),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(true)
+        }
+        _ => Err(format!("Unknown command: {}\n\n{}", command, USAGE)),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,407 @@
+// Closed captions of the video: CEA-608 and CEA-708 carried as cc_data
+// triplets in the SEI user data of H.264 and H.265 streams. The selected
+// caption channel or service is decoded into windows of styled lines, and
+// every change of the screen is appended as cues shown until the next one,
+// so they render like any other subtitle.
+use super::append::UNTIL_NEXT_UPDATE_MS;
+use super::cea608::Cea608Decoder;
+use super::cea708::Cea708Decoder;
+use super::convert::{escape_attribute, format_clock_time};
+use super::units::format_number;
+use super::webvtt::escape_run_text;
+use super::Subtitles;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptionRun {
+    pub text: String,
+    /// Hexadecimal colour, with an alpha when translucent.
+    pub color: String,
+    /// None when transparent.
+    pub background: Option<String>,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptionLine {
+    pub runs: Vec<CaptionRun>,
+}
+
+/// Lines in a region of the video, in percent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptionWindow {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    pub lines: Vec<CaptionLine>,
+}
+
+/// Captions on screen from `begin`, nothing when there are no windows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptionScreen {
+    pub begin: i64,
+    pub windows: Vec<CaptionWindow>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptionTrack {
+    /// CEA-608 channel, 1 to 4.
+    Cea608(u8),
+    /// CEA-708 service, 1 to 63.
+    Cea708(u8),
+}
+
+/// Track of a name like "CC1" to "CC4" or "SERVICE1" to "SERVICE63".
+pub fn parse_caption_track(name: &str) -> Result<CaptionTrack, String> {
+    let name = name.trim().to_ascii_uppercase();
+    let track = if let Some(channel) = name.strip_prefix("CC") {
+        channel.parse().ok().map(CaptionTrack::Cea608)
+    } else if let Some(service) = name.strip_prefix("SERVICE") {
+        service.parse().ok().map(CaptionTrack::Cea708)
+    } else {
+        None
+    };
+    track.ok_or_else(|| format!("Invalid caption track: {}", name))
+}
+
+/// The cc_data triplets of the SEI messages of a NAL unit, with or without
+/// its header, of H.264 (type 6) or H.265 (types 39 and 40).
+pub fn get_sei_cc_data(nal: &[u8]) -> Vec<u8> {
+    // Emulation prevention bytes are dropped.
+    let mut data = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &byte in nal.iter() {
+        if byte == 3 && zeros >= 2 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        data.push(byte);
+    }
+    let mut index = match data.first() {
+        Some(header) if header & 0x1F == 6 => 1,
+        Some(header) if matches!((header >> 1) & 0x3F, 39 | 40) && data.len() > 1 => 2,
+        _ => 0,
+    };
+    let mut cc_data = Vec::new();
+    // Messages end with the RBSP trailing bits.
+    while index < data.len() && data[index] != 0x80 {
+        let mut read_value = || {
+            let mut value = 0;
+            while let Some(&byte) = data.get(index) {
+                index += 1;
+                value += byte as usize;
+                if byte != 0xFF {
+                    break;
+                }
+            }
+            value
+        };
+        let payload_type = read_value();
+        let payload_size = read_value();
+        let end = (index + payload_size).min(data.len());
+        let payload = &data[index..end];
+        index = end;
+        // Registered ITU-T T.35 user data of the ATSC: United States,
+        // provider 0x31, "GA94" and cc_data.
+        if payload_type != 4 || payload.len() < 10 || payload[..8] != *b"\xB5\x00\x31GA94\x03" {
+            continue;
+        }
+        if payload[8] & 0x40 == 0 {
+            continue;
+        }
+        let count = (payload[8] & 0x1F) as usize;
+        let triplets = &payload[10..];
+        cc_data.extend_from_slice(&triplets[..(count * 3).min(triplets.len() / 3 * 3)]);
+    }
+    cc_data
+}
+
+pub struct CaptionDecoder {
+    track: CaptionTrack,
+    cea608: Cea608Decoder,
+    cea708: Cea708Decoder,
+}
+
+impl CaptionDecoder {
+    pub fn new(track: CaptionTrack) -> Result<Self, String> {
+        let (channel, service) = match track {
+            CaptionTrack::Cea608(channel) => (channel, 1),
+            CaptionTrack::Cea708(service) => (1, service),
+        };
+        Ok(CaptionDecoder {
+            track,
+            cea608: Cea608Decoder::new(channel)?,
+            cea708: Cea708Decoder::new(service)?,
+        })
+    }
+
+    pub fn get_track(&self) -> CaptionTrack {
+        self.track
+    }
+
+    /// Reads cc_data triplets presented at `ms`, and returns the screen if
+    /// it changed.
+    pub fn decode(&mut self, cc_data: &[u8], ms: i64) -> Option<CaptionScreen> {
+        for triplet in cc_data.chunks_exact(3) {
+            if triplet[0] & 0x04 == 0 {
+                continue;
+            }
+            match (self.track, triplet[0] & 0x03) {
+                (CaptionTrack::Cea608(_), field @ (0 | 1)) => {
+                    self.cea608
+                        .push_pair(field as usize, triplet[1], triplet[2])
+                }
+                (CaptionTrack::Cea708(_), cc_type @ (2 | 3)) => {
+                    self.cea708.push_pair(cc_type == 3, triplet[1], triplet[2])
+                }
+                _ => (),
+            }
+        }
+        let windows = match self.track {
+            CaptionTrack::Cea608(_) => self.cea608.take_windows(),
+            CaptionTrack::Cea708(_) => self.cea708.take_windows(),
+        }?;
+        Some(CaptionScreen { begin: ms, windows })
+    }
+}
+
+/// Style id of a span attribute, "#" dropped from colours.
+fn get_style_id(name: &str, value: &str) -> String {
+    format!("cc-{}-{}", name, value.trim_start_matches('#'))
+}
+
+/// Document of a screen: a region and a paragraph for every window.
+fn to_ttml(screen: &CaptionScreen) -> String {
+    let mut styles: Vec<String> = Vec::new();
+    let mut add_style = |id: String, attribute: &str, value: &str| {
+        let style = format!(
+            "      <tt:style xml:id=\"{}\" {}=\"{}\"/>\n",
+            id, attribute, value
+        );
+        if !styles.contains(&style) {
+            styles.push(style);
+        }
+        id
+    };
+    let mut regions = String::new();
+    let mut paragraphs = String::new();
+    for (index, window) in screen.windows.iter().enumerate() {
+        let geometry = [window.left, window.top, window.width, window.height].map(format_number);
+        let region_id = format!("cc-{}", geometry.join("-"));
+        regions.push_str(&format!(
+            "      <tt:region xml:id=\"{}\" tts:origin=\"{}% {}%\" tts:extent=\"{}% {}%\"/>\n",
+            escape_attribute(&region_id),
+            geometry[0],
+            geometry[1],
+            geometry[2],
+            geometry[3]
+        ));
+        let content = window
+            .lines
+            .iter()
+            .map(|line| {
+                line.runs
+                    .iter()
+                    .map(|run| {
+                        let mut ids = vec![add_style(
+                            get_style_id("color", &run.color),
+                            "tts:color",
+                            &run.color,
+                        )];
+                        if let Some(background) = &run.background {
+                            ids.push(add_style(
+                                get_style_id("background", background),
+                                "tts:backgroundColor",
+                                background,
+                            ));
+                        }
+                        if run.italic {
+                            ids.push(add_style(
+                                "cc-italic".to_string(),
+                                "tts:fontStyle",
+                                "italic",
+                            ));
+                        }
+                        if run.underline {
+                            ids.push(add_style(
+                                "cc-underline".to_string(),
+                                "tts:textDecoration",
+                                "underline",
+                            ));
+                        }
+                        format!(
+                            "<tt:span style=\"{}\">{}</tt:span>",
+                            ids.join(" "),
+                            escape_run_text(&run.text)
+                        )
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("<tt:br/>");
+        paragraphs.push_str(&format!(
+            "      <tt:p xml:id=\"cc-{}-{}\" begin=\"{}\" end=\"{}\" region=\"{}\" style=\"cc\">{}</tt:p>\n",
+            screen.begin,
+            index,
+            format_clock_time(screen.begin, '.'),
+            format_clock_time(screen.begin + UNTIL_NEXT_UPDATE_MS, '.'),
+            escape_attribute(&region_id),
+            content
+        ));
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" xmlns:tts="http://www.w3.org/ns/ttml#styling" ttp:timeBase="media">
+  <tt:head>
+    <tt:styling>
+      <tt:style xml:id="cc" tts:textAlign="left" tts:fontFamily="monospaceSansSerif"/>
+{}    </tt:styling>
+    <tt:layout>
+{}    </tt:layout>
+  </tt:head>
+  <tt:body>
+    <tt:div>
+{}    </tt:div>
+  </tt:body>
+</tt:tt>
+"#,
+        styles.concat(),
+        regions,
+        paragraphs
+    )
+}
+
+impl Subtitles {
+    /// Selects the caption channel or service, CC1 if not set. The captions
+    /// on screen are hidden.
+    pub fn set_caption_track(&mut self, track: CaptionTrack) -> Result<(), String> {
+        self.caption_decoder = Some(CaptionDecoder::new(track)?);
+        self.replace_stream_cues("cc-", self.current_ms);
+        self.resync_visible_subtitles();
+        Ok(())
+    }
+
+    /// Appends the changes of the captions in cc_data triplets of a video
+    /// frame whose presentation time is `ms`.
+    pub fn append_cc_data(&mut self, cc_data: &[u8], ms: i64) -> Result<(), String> {
+        if self.caption_decoder.is_none() {
+            self.caption_decoder = Some(CaptionDecoder::new(CaptionTrack::Cea608(1))?);
+        }
+        let Some(screen) = self
+            .caption_decoder
+            .as_mut()
+            .and_then(|decoder| decoder.decode(cc_data, ms))
+        else {
+            return Ok(());
+        };
+        self.replace_stream_cues("cc-", screen.begin);
+        if !screen.windows.is_empty() {
+            self.append_document(&to_ttml(&screen))?;
+        }
+        self.resync_visible_subtitles();
+        Ok(())
+    }
+
+    /// Appends the captions in the SEI NAL unit of a video frame whose
+    /// presentation time is `ms`.
+    pub fn append_sei(&mut self, nal: &[u8], ms: i64) -> Result<(), String> {
+        let cc_data = get_sei_cc_data(nal);
+        if cc_data.is_empty() {
+            return Ok(());
+        }
+        self.append_cc_data(&cc_data, ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Field 1 triplets of byte pairs, with odd parity.
+    fn cc_data(pairs: &[(u8, u8)]) -> Vec<u8> {
+        let parity = |byte: u8| {
+            if byte.count_ones() % 2 == 1 {
+                byte
+            } else {
+                byte | 0x80
+            }
+        };
+        pairs
+            .iter()
+            .flat_map(|(first, second)| [0xFC, parity(*first), parity(*second)])
+            .collect()
+    }
+
+    fn get_texts(subtitles: &mut Subtitles, ms: i64) -> Vec<String> {
+        subtitles
+            .get_subtitles_at(ms)
+            .iter()
+            .map(|subtitle| {
+                subtitle
+                    .lines
+                    .iter()
+                    .flatten()
+                    .map(|run| run.text.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_caption_tracks() {
+        assert_eq!(parse_caption_track("cc3"), Ok(CaptionTrack::Cea608(3)));
+        assert_eq!(
+            parse_caption_track("SERVICE12"),
+            Ok(CaptionTrack::Cea708(12))
+        );
+        assert!(parse_caption_track("CC").is_err());
+        assert!(CaptionDecoder::new(CaptionTrack::Cea608(0)).is_err());
+    }
+
+    #[test]
+    fn reads_sei_cc_data() {
+        let mut nal = vec![0x06, 0x04, 18];
+        nal.extend_from_slice(b"\xB5\x00\x31GA94\x03");
+        // Two triplets, the second one with an emulation prevention byte.
+        nal.extend_from_slice(&[0x42, 0xFF, 0xFC, 0x94, 0x20, 0xFC, 0x00, 0x00, 0x03, 0x01]);
+        nal.extend_from_slice(&[0xFF, 0x80]);
+        assert_eq!(get_sei_cc_data(&nal), [0xFC, 0x94, 0x20, 0xFC, 0x00, 0x00]);
+        assert!(get_sei_cc_data(&[0x06, 0x05, 0x01, 0x00, 0x80]).is_empty());
+    }
+
+    #[test]
+    fn shows_captions_until_they_change() {
+        let mut subtitles = Subtitles::new();
+        subtitles
+            .append_cc_data(
+                &cc_data(&[
+                    (0x14, 0x20),
+                    (0x14, 0x20),
+                    (0x14, 0x70),
+                    (0x14, 0x70),
+                    (b'<', b'H'),
+                    (b'i', b'>'),
+                    (0x14, 0x2F),
+                    (0x14, 0x2F),
+                ]),
+                1000,
+            )
+            .unwrap();
+        assert_eq!(get_texts(&mut subtitles, 1500), ["<Hi>"]);
+        subtitles
+            .append_cc_data(&cc_data(&[(0x14, 0x2C), (0x14, 0x2C)]), 2000)
+            .unwrap();
+        assert_eq!(get_texts(&mut subtitles, 1500), ["<Hi>"]);
+        assert!(get_texts(&mut subtitles, 2500).is_empty());
+        // Another channel has nothing to show.
+        subtitles
+            .set_caption_track(CaptionTrack::Cea708(1))
+            .unwrap();
+        subtitles
+            .append_cc_data(&cc_data(&[(0x14, 0x29), (b'n', b'o')]), 3000)
+            .unwrap();
+        assert!(get_texts(&mut subtitles, 3500).is_empty());
+    }
+}
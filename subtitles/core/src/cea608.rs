@@ -0,0 +1,462 @@
+// CEA-608 (line 21) closed captions of one caption channel (CC1 to CC4),
+// carried in the cc_data of the video. The decoder runs the pop-on, roll-up
+// and paint-on modes over the displayed and non-displayed memories, placing
+// and styling the characters with the preamble address codes (PACs) and the
+// mid-row codes.
+use super::captions::{CaptionLine, CaptionRun, CaptionWindow};
+
+const ROWS: usize = 15;
+const COLUMNS: usize = 32;
+
+/// White, green, blue, cyan, red, yellow and magenta.
+const COLORS: [&str; 7] = [
+    "#ffffff", "#00ff00", "#0000ff", "#00ffff", "#ff0000", "#ffff00", "#ff00ff",
+];
+
+/// First row (1 to 15) of the PACs, by their first byte.
+const PAC_ROWS: [usize; 8] = [11, 1, 3, 12, 14, 5, 7, 9];
+
+/// Special characters, second byte 0x30 to 0x3F. 0x39 is a transparent space.
+const SPECIAL_CHARACTERS: [char; 16] = [
+    '®', '°', '½', '¿', '™', '¢', '£', '♪', 'à', ' ', 'è', 'â', 'ê', 'î', 'ô', 'û',
+];
+
+/// Extended Spanish, miscellaneous and French characters, 0x12 0x20 to 0x3F.
+const EXTENDED_CHARACTERS_1: [char; 32] = [
+    'Á', 'É', 'Ó', 'Ú', 'Ü', 'ü', '‘', '¡', '*', '\'', '─', '©', '℠', '•', '“', '”', 'À', 'Â', 'Ç',
+    'È', 'Ê', 'Ë', 'ë', 'Î', 'Ï', 'ï', 'Ô', 'Ù', 'ù', 'Û', '«', '»',
+];
+
+/// Extended Portuguese, German and Danish characters, 0x13 0x20 to 0x3F.
+const EXTENDED_CHARACTERS_2: [char; 32] = [
+    'Ã', 'ã', 'Í', 'Ì', 'ì', 'Ò', 'ò', 'Õ', 'õ', '{', '}', '\\', '^', '_', '|', '~', 'Ä', 'ä', 'Ö',
+    'ö', 'ß', '¥', '¤', '│', 'Å', 'å', 'Ø', 'ø', '┌', '┐', '└', '┘',
+];
+
+/// Character of the basic set, ASCII with a few accented letters.
+fn get_basic_character(code: u8) -> char {
+    match code {
+        0x2A => 'á',
+        0x5C => 'é',
+        0x5E => 'í',
+        0x5F => 'ó',
+        0x60 => 'ú',
+        0x7B => 'ç',
+        0x7C => '÷',
+        0x7D => 'Ñ',
+        0x7E => 'ñ',
+        0x7F => '█',
+        _ => code as char,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Style {
+    /// Index of `COLORS`.
+    color: usize,
+    italic: bool,
+    underline: bool,
+}
+
+const WHITE: Style = Style {
+    color: 0,
+    italic: false,
+    underline: false,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    character: char,
+    style: Style,
+}
+
+type Memory = [[Option<Cell>; COLUMNS]; ROWS];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    PopOn,
+    /// Roll-up with the number of rows of the window, 2 to 4.
+    RollUp(usize),
+    PaintOn,
+    /// Text service, not captions.
+    Text,
+}
+
+pub struct Cea608Decoder {
+    /// 1 to 4: CC1 and CC2 are in field 1, CC3 and CC4 in field 2.
+    channel: u8,
+    /// Data channel (0 or 1) of the characters of each field, set by the
+    /// last control code.
+    data_channels: [u8; 2],
+    /// Control codes are sent twice, the repetition is skipped.
+    last_control: Option<(u8, u8)>,
+    mode: Mode,
+    displayed: Memory,
+    non_displayed: Memory,
+    /// Cursor, 0-based.
+    row: usize,
+    column: usize,
+    style: Style,
+    is_changed: bool,
+}
+
+impl Cea608Decoder {
+    pub fn new(channel: u8) -> Result<Self, String> {
+        if !(1..=4).contains(&channel) {
+            return Err(format!("Invalid CEA-608 channel: {}", channel));
+        }
+        Ok(Cea608Decoder {
+            channel,
+            data_channels: [0, 0],
+            last_control: None,
+            mode: Mode::PopOn,
+            displayed: [[None; COLUMNS]; ROWS],
+            non_displayed: [[None; COLUMNS]; ROWS],
+            row: ROWS - 1,
+            column: 0,
+            style: WHITE,
+            is_changed: false,
+        })
+    }
+
+    /// Reads a byte pair of field 1 (0) or 2 (1). The parity bits are
+    /// dropped.
+    pub fn push_pair(&mut self, field: usize, first: u8, second: u8) {
+        let (first, second) = (first & 0x7F, second & 0x7F);
+        if first == 0 && second == 0 {
+            return;
+        }
+        if (0x10..=0x1F).contains(&first) {
+            self.data_channels[field] = (first >> 3) & 1;
+            if self.last_control == Some((first, second)) {
+                self.last_control = None;
+                return;
+            }
+            self.last_control = Some((first, second));
+            if self.is_selected(field) {
+                self.read_control(first & 0xF7, second);
+            }
+            return;
+        }
+        self.last_control = None;
+        // Extended data services of field 2 are not captions.
+        if first < 0x10 || !self.is_selected(field) {
+            return;
+        }
+        self.write(get_basic_character(first));
+        if second >= 0x20 {
+            self.write(get_basic_character(second));
+        }
+    }
+
+    fn is_selected(&self, field: usize) -> bool {
+        let channel = (self.channel - 1) as usize;
+        field == channel / 2 && self.data_channels[field] as usize == channel % 2
+    }
+
+    fn read_control(&mut self, first: u8, second: u8) {
+        match (first, second) {
+            (0x14 | 0x15, 0x20..=0x2F) => self.read_command(second),
+            (0x17, 0x21..=0x23) => {
+                self.column = (self.column + (second - 0x20) as usize).min(COLUMNS - 1)
+            }
+            // Mid-row codes: a colour, or italics keeping the colour.
+            (0x11, 0x20..=0x2F) => {
+                let color = ((second & 0x0E) >> 1) as usize;
+                self.style = Style {
+                    color: if color == 7 { self.style.color } else { color },
+                    italic: color == 7,
+                    underline: second & 1 == 1,
+                };
+                self.write(' ');
+            }
+            (0x11, 0x30..=0x3F) => self.write(SPECIAL_CHARACTERS[(second - 0x30) as usize]),
+            // Extended characters replace the standard one sent before them
+            // for older decoders.
+            (0x12 | 0x13, 0x20..=0x3F) => {
+                self.backspace();
+                let characters = if first == 0x12 {
+                    &EXTENDED_CHARACTERS_1
+                } else {
+                    &EXTENDED_CHARACTERS_2
+                };
+                self.write(characters[(second - 0x20) as usize]);
+            }
+            (_, 0x40..=0x7F) => self.read_preamble(first, second),
+            // Background and foreground attributes are not supported.
+            _ => (),
+        }
+    }
+
+    fn read_command(&mut self, command: u8) {
+        match command {
+            0x20 => self.mode = Mode::PopOn,
+            0x21 => self.backspace(),
+            0x24 => {
+                let (row, column) = (self.row, self.column);
+                self.get_memory()[row][column..].fill(None);
+                self.set_changed();
+            }
+            0x25..=0x27 => {
+                let rows = (command - 0x23) as usize;
+                if !matches!(self.mode, Mode::RollUp(_)) {
+                    self.displayed = [[None; COLUMNS]; ROWS];
+                    self.non_displayed = [[None; COLUMNS]; ROWS];
+                    self.row = ROWS - 1;
+                    self.column = 0;
+                    self.is_changed = true;
+                }
+                self.mode = Mode::RollUp(rows);
+            }
+            0x29 => self.mode = Mode::PaintOn,
+            0x2A | 0x2B => self.mode = Mode::Text,
+            0x2C => {
+                self.displayed = [[None; COLUMNS]; ROWS];
+                self.is_changed = true;
+            }
+            0x2D => {
+                if let Mode::RollUp(rows) = self.mode {
+                    let top = (self.row + 1).saturating_sub(rows);
+                    for row in 0..ROWS {
+                        self.displayed[row] = if (top..self.row).contains(&row) {
+                            self.displayed[row + 1]
+                        } else {
+                            [None; COLUMNS]
+                        };
+                    }
+                    self.column = 0;
+                    self.is_changed = true;
+                }
+            }
+            0x2E => self.non_displayed = [[None; COLUMNS]; ROWS],
+            0x2F => {
+                std::mem::swap(&mut self.displayed, &mut self.non_displayed);
+                self.mode = Mode::PopOn;
+                self.is_changed = true;
+            }
+            // Alarm, flash and reserved commands.
+            _ => (),
+        }
+    }
+
+    /// Preamble address code: row, and indent or colour of the text that
+    /// follows.
+    fn read_preamble(&mut self, first: u8, second: u8) {
+        let row = if first == 0x10 {
+            PAC_ROWS[0]
+        } else {
+            PAC_ROWS[(first & 7) as usize] + (second & 0x20 != 0) as usize
+        } - 1;
+        let attributes = second & 0x1F;
+        let underline = attributes & 1 == 1;
+        let (style, column) = if attributes & 0x10 != 0 {
+            let indent = ((attributes & 0x0E) >> 1) as usize * 4;
+            (Style { underline, ..WHITE }, indent)
+        } else {
+            let color = ((attributes & 0x0E) >> 1) as usize;
+            (
+                Style {
+                    color: if color == 7 { 0 } else { color },
+                    italic: color == 7,
+                    underline,
+                },
+                0,
+            )
+        };
+        // In roll-up mode the window moves to the new base row.
+        if let Mode::RollUp(rows) = self.mode {
+            if row != self.row {
+                let mut displayed = [[None; COLUMNS]; ROWS];
+                for offset in 0..rows.min(row + 1).min(self.row + 1) {
+                    displayed[row - offset] = self.displayed[self.row - offset];
+                }
+                self.displayed = displayed;
+                self.is_changed = true;
+            }
+        }
+        self.row = row;
+        self.column = column;
+        self.style = style;
+    }
+
+    /// Memory written by the current mode.
+    fn get_memory(&mut self) -> &mut Memory {
+        match self.mode {
+            Mode::PopOn => &mut self.non_displayed,
+            _ => &mut self.displayed,
+        }
+    }
+
+    fn set_changed(&mut self) {
+        if self.mode != Mode::PopOn {
+            self.is_changed = true;
+        }
+    }
+
+    fn write(&mut self, character: char) {
+        if self.mode == Mode::Text {
+            return;
+        }
+        let (row, column, style) = (self.row, self.column, self.style);
+        self.get_memory()[row][column] = Some(Cell { character, style });
+        self.column = (column + 1).min(COLUMNS - 1);
+        self.set_changed();
+    }
+
+    fn backspace(&mut self) {
+        if self.column > 0 {
+            self.column -= 1;
+            let (row, column) = (self.row, self.column);
+            self.get_memory()[row][column] = None;
+            self.set_changed();
+        }
+    }
+
+    /// The displayed rows if they changed since the last call. Every row is
+    /// a window placed on the caption grid, in the safe area.
+    pub fn take_windows(&mut self) -> Option<Vec<CaptionWindow>> {
+        if !self.is_changed {
+            return None;
+        }
+        self.is_changed = false;
+        let row_height = 80.0 / ROWS as f32;
+        let column_width = 80.0 / COLUMNS as f32;
+        let windows = self
+            .displayed
+            .iter()
+            .enumerate()
+            .filter_map(|(row, cells)| {
+                let first = cells.iter().position(|cell| cell.is_some())?;
+                let last = cells.iter().rposition(|cell| cell.is_some())?;
+                let mut runs: Vec<CaptionRun> = Vec::new();
+                for cell in cells[first..=last].iter() {
+                    let cell = cell.unwrap_or(Cell {
+                        character: ' ',
+                        style: WHITE,
+                    });
+                    let color = COLORS[cell.style.color];
+                    match runs.last_mut() {
+                        Some(run)
+                            if run.color == color
+                                && run.italic == cell.style.italic
+                                && run.underline == cell.style.underline =>
+                        {
+                            run.text.push(cell.character)
+                        }
+                        _ => runs.push(CaptionRun {
+                            text: cell.character.to_string(),
+                            color: color.to_string(),
+                            background: Some("#000000".to_string()),
+                            italic: cell.style.italic,
+                            underline: cell.style.underline,
+                        }),
+                    }
+                }
+                Some(CaptionWindow {
+                    left: 10.0 + first as f32 * column_width,
+                    top: 10.0 + row as f32 * row_height,
+                    width: (COLUMNS - first) as f32 * column_width,
+                    height: row_height,
+                    lines: vec![CaptionLine { runs }],
+                })
+            })
+            .collect();
+        Some(windows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_text(windows: &[CaptionWindow]) -> Vec<String> {
+        windows
+            .iter()
+            .map(|window| {
+                window.lines[0]
+                    .runs
+                    .iter()
+                    .map(|run| run.text.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn push(decoder: &mut Cea608Decoder, pairs: &[(u8, u8)]) {
+        for (first, second) in pairs {
+            decoder.push_pair(0, *first, *second);
+        }
+    }
+
+    #[test]
+    fn shows_pop_on_captions() {
+        let mut decoder = Cea608Decoder::new(1).unwrap();
+        push(
+            &mut decoder,
+            &[
+                (0x14, 0x20),
+                (0x14, 0x20),
+                // Row 14 indent 4, then row 15 in yellow.
+                (0x14, 0x52),
+                (0x14, 0x52),
+                (b'H', b'o'),
+                (b'l', b'a'),
+                (0x14, 0x6A),
+                (b'a', b'n'),
+                (b'o', b'!'),
+                // Italics keep the colour, after a space.
+                (0x11, 0x2E),
+                (0x11, 0x2E),
+                (0x7E, 0x00),
+            ],
+        );
+        assert!(decoder.take_windows().is_none());
+        push(&mut decoder, &[(0x14, 0x2F), (0x14, 0x2F)]);
+        let windows = decoder.take_windows().unwrap();
+        assert_eq!(get_text(&windows), ["Hola", "ano! ñ"]);
+        assert_eq!(windows[0].left, 20.0);
+        assert!((windows[0].top - (10.0 + 13.0 * 80.0 / 15.0)).abs() < 0.001);
+        let runs = &windows[1].lines[0].runs;
+        assert_eq!((runs[0].color.as_str(), runs[0].italic), ("#ffff00", false));
+        assert_eq!((runs[1].color.as_str(), runs[1].italic), ("#ffff00", true));
+        // Erasing the displayed memory clears the screen.
+        push(&mut decoder, &[(0x14, 0x2C)]);
+        assert_eq!(decoder.take_windows().unwrap(), []);
+    }
+
+    #[test]
+    fn rolls_up_captions() {
+        let mut decoder = Cea608Decoder::new(1).unwrap();
+        push(
+            &mut decoder,
+            &[(0x14, 0x25), (0x14, 0x25), (b'u', b'n'), (0x14, 0x2D)],
+        );
+        push(&mut decoder, &[(b'd', b'o'), (b's', 0x00), (0x14, 0x2D)]);
+        push(&mut decoder, &[(b't', b'r'), (b'e', b's')]);
+        let windows = decoder.take_windows().unwrap();
+        // Two rows: the first one rolled out.
+        assert_eq!(get_text(&windows), ["dos", "tres"]);
+        assert!((windows[1].top - (10.0 + 14.0 * 80.0 / 15.0)).abs() < 0.001);
+        // An extended character replaces the one before it.
+        push(&mut decoder, &[(b'a', 0x00), (0x13, 0x30), (0x13, 0x30)]);
+        assert_eq!(get_text(&decoder.take_windows().unwrap()), ["dos", "tresÄ"]);
+    }
+
+    #[test]
+    fn decodes_one_channel() {
+        let mut decoder = Cea608Decoder::new(2).unwrap();
+        push(
+            &mut decoder,
+            &[
+                (0x14, 0x29),
+                (b'C', b'C'),
+                (0x1C, 0x29),
+                (0x1C, 0x29),
+                (b'd', b'o'),
+                (b's', 0x00),
+            ],
+        );
+        assert_eq!(get_text(&decoder.take_windows().unwrap()), ["dos"]);
+        assert!(Cea608Decoder::new(5).is_err());
+    }
+}
@@ -0,0 +1,576 @@
+// CEA-708 (DTVCC) closed captions of one caption service, carried in the
+// cc_data of the video. The DTVCC packets are assembled, and the service
+// blocks of the selected service drive up to eight windows with their pen
+// attributes, colours and locations.
+use super::captions::{CaptionLine, CaptionRun, CaptionWindow};
+
+const WINDOWS: usize = 8;
+const MAX_ROWS: usize = 15;
+const MAX_COLUMNS: usize = 42;
+
+/// Characters of the G2 set that have a Unicode equivalent.
+fn get_g2_character(code: u8) -> Option<char> {
+    Some(match code {
+        0x20 | 0x21 => ' ',
+        0x25 => '…',
+        0x2A => 'Š',
+        0x2C => 'Œ',
+        0x30 => '█',
+        0x31 => '‘',
+        0x32 => '’',
+        0x33 => '“',
+        0x34 => '”',
+        0x35 => '•',
+        0x39 => '™',
+        0x3A => 'š',
+        0x3C => 'œ',
+        0x3D => '℠',
+        0x3F => 'Ÿ',
+        0x76 => '⅛',
+        0x77 => '⅜',
+        0x78 => '⅝',
+        0x79 => '⅞',
+        0x7A => '│',
+        0x7B => '┐',
+        0x7C => '└',
+        0x7D => '─',
+        0x7E => '┘',
+        0x7F => '┌',
+        _ => return None,
+    })
+}
+
+/// Colour of 2 bits per component, with its opacity: solid, flashing,
+/// translucent or transparent.
+fn get_color(color: u8, opacity: u8) -> Option<String> {
+    let component = |shift: u8| ((color >> shift) & 3) * 0x55;
+    let rgb = format!(
+        "#{:02x}{:02x}{:02x}",
+        component(4),
+        component(2),
+        component(0)
+    );
+    match opacity {
+        0 | 1 => Some(rgb),
+        2 => Some(format!("{}80", rgb)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pen {
+    italic: bool,
+    underline: bool,
+    foreground: String,
+    background: Option<String>,
+}
+
+impl Default for Pen {
+    fn default() -> Self {
+        Pen {
+            italic: false,
+            underline: false,
+            foreground: "#ffffff".to_string(),
+            background: Some("#000000".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Cell {
+    character: char,
+    pen: Pen,
+}
+
+#[derive(Debug, Clone)]
+struct Window {
+    visible: bool,
+    priority: u8,
+    /// Anchor in percent when relative, else in the 75 rows and 210 columns
+    /// of a 16:9 display.
+    relative: bool,
+    anchor_vertical: u8,
+    anchor_horizontal: u8,
+    /// 0 to 8, from top left to bottom right.
+    anchor_point: u8,
+    row_count: usize,
+    column_count: usize,
+    rows: Vec<Vec<Option<Cell>>>,
+    pen_row: usize,
+    pen_column: usize,
+    pen: Pen,
+}
+
+impl Window {
+    fn clear(&mut self) {
+        self.rows = vec![vec![None; self.column_count]; self.row_count];
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rows.iter().flatten().all(|cell| cell.is_none())
+    }
+
+    /// Region of the window, in percent of the video.
+    fn get_position(&self) -> (f32, f32, f32, f32) {
+        let (vertical, horizontal) = if self.relative {
+            (self.anchor_vertical as f32, self.anchor_horizontal as f32)
+        } else {
+            (
+                self.anchor_vertical as f32 * 100.0 / 75.0,
+                self.anchor_horizontal as f32 * 100.0 / 210.0,
+            )
+        };
+        let width = (self.column_count as f32 * 80.0 / 32.0).min(80.0);
+        let height = self.row_count as f32 * 80.0 / MAX_ROWS as f32;
+        let left =
+            10.0 + horizontal.min(100.0) * 0.8 - width * (self.anchor_point % 3) as f32 / 2.0;
+        let top = 10.0 + vertical.min(100.0) * 0.8 - height * (self.anchor_point / 3) as f32 / 2.0;
+        (
+            left.clamp(0.0, 100.0 - width),
+            top.clamp(0.0, 100.0 - height),
+            width,
+            height,
+        )
+    }
+}
+
+pub struct Cea708Decoder {
+    /// 1 to 63.
+    service: u8,
+    /// DTVCC packet being assembled.
+    packet: Vec<u8>,
+    windows: Vec<Option<Window>>,
+    current_window: usize,
+    is_changed: bool,
+}
+
+impl Cea708Decoder {
+    pub fn new(service: u8) -> Result<Self, String> {
+        if !(1..=63).contains(&service) {
+            return Err(format!("Invalid CEA-708 service: {}", service));
+        }
+        Ok(Cea708Decoder {
+            service,
+            packet: Vec::new(),
+            windows: vec![None; WINDOWS],
+            current_window: 0,
+            is_changed: false,
+        })
+    }
+
+    /// Reads the byte pair of a DTVCC packet start (`is_start`) or data.
+    pub fn push_pair(&mut self, is_start: bool, first: u8, second: u8) {
+        if is_start {
+            self.flush();
+        } else if self.packet.is_empty() {
+            return;
+        }
+        self.packet.extend_from_slice(&[first, second]);
+        let size = match self.packet[0] & 0x3F {
+            0 => 128,
+            code => code as usize * 2,
+        };
+        if self.packet.len() >= size {
+            self.flush();
+        }
+    }
+
+    /// Reads the packet being assembled, which may be cut short.
+    fn flush(&mut self) {
+        let packet = std::mem::take(&mut self.packet);
+        if packet.is_empty() {
+            return;
+        }
+        let size = match packet[0] & 0x3F {
+            0 => 128,
+            code => code as usize * 2,
+        };
+        let data = &packet[1..size.min(packet.len())];
+        let mut index = 0;
+        while index < data.len() {
+            let header = data[index];
+            let mut service = header >> 5;
+            let size = (header & 0x1F) as usize;
+            index += 1;
+            if service == 7 && index < data.len() {
+                service = data[index] & 0x3F;
+                index += 1;
+            }
+            if service == 0 || size == 0 {
+                break;
+            }
+            let end = (index + size).min(data.len());
+            if service == self.service {
+                self.read_block(&data[index..end]);
+            }
+            index = end;
+        }
+    }
+
+    fn read_block(&mut self, block: &[u8]) {
+        let mut index = 0;
+        while index < block.len() {
+            let code = block[index];
+            let parameters = &block[index + 1..];
+            index += 1 + match code {
+                0x10 => self.read_extended(parameters),
+                0x00..=0x1F => self.read_c0(code),
+                0x20..=0x7F => {
+                    self.write(if code == 0x7F { '♪' } else { code as char });
+                    0
+                }
+                0x80..=0x9F => self.read_c1(code, parameters),
+                _ => {
+                    // G1 is Latin-1.
+                    self.write(code as char);
+                    0
+                }
+            };
+        }
+    }
+
+    /// Control code of C0, returns the number of parameter bytes.
+    fn read_c0(&mut self, code: u8) -> usize {
+        match code {
+            0x08 => {
+                if let Some(window) = self.get_window() {
+                    if window.pen_column > 0 {
+                        window.pen_column -= 1;
+                        let (row, column) = (window.pen_row, window.pen_column);
+                        window.rows[row][column] = None;
+                    }
+                }
+                self.set_changed();
+            }
+            0x0C => {
+                if let Some(window) = self.get_window() {
+                    window.clear();
+                    window.pen_row = 0;
+                    window.pen_column = 0;
+                }
+                self.set_changed();
+            }
+            0x0D => {
+                if let Some(window) = self.get_window() {
+                    window.pen_column = 0;
+                    if window.pen_row + 1 < window.row_count {
+                        window.pen_row += 1;
+                    } else {
+                        window.rows.remove(0);
+                        window.rows.push(vec![None; window.column_count]);
+                    }
+                }
+                self.set_changed();
+            }
+            0x0E => {
+                if let Some(window) = self.get_window() {
+                    let row = window.pen_row;
+                    window.rows[row].fill(None);
+                    window.pen_column = 0;
+                }
+                self.set_changed();
+            }
+            0x11..=0x17 => return 1,
+            0x18..=0x1F => return 2,
+            _ => (),
+        }
+        0
+    }
+
+    /// Code after EXT1, returns the number of bytes read after EXT1.
+    fn read_extended(&mut self, parameters: &[u8]) -> usize {
+        let Some(&code) = parameters.first() else {
+            return 0;
+        };
+        1 + match code {
+            0x00..=0x07 => 0,
+            0x08..=0x0F => 1,
+            0x10..=0x17 => 2,
+            0x18..=0x1F => 3,
+            0x20..=0x7F => {
+                if let Some(character) = get_g2_character(code) {
+                    self.write(character);
+                }
+                0
+            }
+            0x80..=0x87 => 4,
+            0x88..=0x8F => 5,
+            0x90..=0x9F => parameters
+                .get(1)
+                .map_or(0, |size| 1 + (size & 0x1F) as usize),
+            // G3 only has the closed caption logo.
+            _ => {
+                self.write('㏄');
+                0
+            }
+        }
+    }
+
+    /// Command of C1, returns the number of parameter bytes.
+    fn read_c1(&mut self, code: u8, parameters: &[u8]) -> usize {
+        let count = match code {
+            0x88..=0x8D => 1,
+            0x90 | 0x92 => 2,
+            0x91 => 3,
+            0x97 => 4,
+            0x98..=0x9F => 6,
+            _ => 0,
+        };
+        if parameters.len() < count {
+            return parameters.len();
+        }
+        match code {
+            0x80..=0x87 => self.current_window = (code - 0x80) as usize,
+            0x88..=0x8C => {
+                let bitmap = parameters[0];
+                for (id, slot) in self.windows.iter_mut().enumerate() {
+                    if bitmap & (1 << id) == 0 {
+                        continue;
+                    }
+                    if let Some(window) = slot {
+                        if window.visible || code == 0x89 || code == 0x8B {
+                            self.is_changed = true;
+                        }
+                        match code {
+                            0x88 => window.clear(),
+                            0x89 => window.visible = true,
+                            0x8A => window.visible = false,
+                            0x8B => window.visible = !window.visible,
+                            _ => *slot = None,
+                        }
+                    }
+                }
+            }
+            0x8F => {
+                self.windows = vec![None; WINDOWS];
+                self.is_changed = true;
+            }
+            0x90 => {
+                if let Some(window) = self.get_window() {
+                    window.pen.italic = parameters[1] & 0x80 != 0;
+                    window.pen.underline = parameters[1] & 0x40 != 0;
+                }
+            }
+            0x91 => {
+                if let Some(window) = self.get_window() {
+                    let foreground = parameters[0];
+                    let background = parameters[1];
+                    window.pen.foreground = get_color(foreground & 0x3F, foreground >> 6)
+                        .unwrap_or_else(|| "#ffffff00".to_string());
+                    window.pen.background = get_color(background & 0x3F, background >> 6);
+                }
+            }
+            0x92 => {
+                if let Some(window) = self.get_window() {
+                    window.pen_row = ((parameters[0] & 0x0F) as usize).min(window.row_count - 1);
+                    window.pen_column =
+                        ((parameters[1] & 0x3F) as usize).min(window.column_count - 1);
+                }
+            }
+            0x98..=0x9F => self.define_window((code - 0x98) as usize, parameters),
+            // Delays, window attributes and reserved commands.
+            _ => (),
+        }
+        count
+    }
+
+    fn define_window(&mut self, id: usize, parameters: &[u8]) {
+        let row_count = ((parameters[3] & 0x0F) as usize + 1).min(MAX_ROWS);
+        let column_count = ((parameters[4] & 0x3F) as usize + 1).min(MAX_COLUMNS);
+        let window = self.windows[id].get_or_insert_with(|| Window {
+            visible: false,
+            priority: 0,
+            relative: false,
+            anchor_vertical: 0,
+            anchor_horizontal: 0,
+            anchor_point: 0,
+            row_count,
+            column_count,
+            rows: vec![vec![None; column_count]; row_count],
+            pen_row: 0,
+            pen_column: 0,
+            pen: Pen::default(),
+        });
+        window.visible = parameters[0] & 0x20 != 0;
+        window.priority = parameters[0] & 0x07;
+        window.relative = parameters[1] & 0x80 != 0;
+        window.anchor_vertical = parameters[1] & 0x7F;
+        window.anchor_horizontal = parameters[2];
+        window.anchor_point = (parameters[3] >> 4).min(8);
+        window.row_count = row_count;
+        window.column_count = column_count;
+        window.rows.resize(row_count, Vec::new());
+        for row in window.rows.iter_mut() {
+            row.resize(column_count, None);
+        }
+        window.pen_row = window.pen_row.min(row_count - 1);
+        window.pen_column = window.pen_column.min(column_count - 1);
+        self.current_window = id;
+        self.is_changed = true;
+    }
+
+    fn get_window(&mut self) -> Option<&mut Window> {
+        self.windows[self.current_window].as_mut()
+    }
+
+    fn set_changed(&mut self) {
+        if self.get_window().is_some_and(|window| window.visible) {
+            self.is_changed = true;
+        }
+    }
+
+    fn write(&mut self, character: char) {
+        if let Some(window) = self.get_window() {
+            if window.pen_column < window.column_count {
+                let (row, column) = (window.pen_row, window.pen_column);
+                window.rows[row][column] = Some(Cell {
+                    character,
+                    pen: window.pen.clone(),
+                });
+                window.pen_column += 1;
+            }
+        }
+        self.set_changed();
+    }
+
+    /// The visible windows if they changed since the last call, the lower
+    /// priorities over the higher ones.
+    pub fn take_windows(&mut self) -> Option<Vec<CaptionWindow>> {
+        self.flush();
+        if !self.is_changed {
+            return None;
+        }
+        self.is_changed = false;
+        let mut windows: Vec<&Window> = self
+            .windows
+            .iter()
+            .flatten()
+            .filter(|window| window.visible && !window.is_empty())
+            .collect();
+        windows.sort_by_key(|window| std::cmp::Reverse(window.priority));
+        Some(
+            windows
+                .into_iter()
+                .map(|window| {
+                    let lines = window
+                        .rows
+                        .iter()
+                        .map(|cells| {
+                            let last = cells.iter().rposition(|cell| cell.is_some());
+                            let mut runs: Vec<CaptionRun> = Vec::new();
+                            for cell in cells[..last.map_or(0, |last| last + 1)].iter() {
+                                let (character, pen) = match cell {
+                                    Some(cell) => (cell.character, &cell.pen),
+                                    None => (' ', &window.pen),
+                                };
+                                match runs.last_mut() {
+                                    Some(run)
+                                        if run.color == pen.foreground
+                                            && run.background == pen.background
+                                            && run.italic == pen.italic
+                                            && run.underline == pen.underline =>
+                                    {
+                                        run.text.push(character)
+                                    }
+                                    _ => runs.push(CaptionRun {
+                                        text: character.to_string(),
+                                        color: pen.foreground.clone(),
+                                        background: pen.background.clone(),
+                                        italic: pen.italic,
+                                        underline: pen.underline,
+                                    }),
+                                }
+                            }
+                            CaptionLine { runs }
+                        })
+                        .collect();
+                    let (left, top, width, height) = window.get_position();
+                    CaptionWindow {
+                        left,
+                        top,
+                        width,
+                        height,
+                        lines,
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pairs of a DTVCC packet with one block of service 1.
+    fn packet(block: &[u8]) -> Vec<(bool, u8, u8)> {
+        let mut data = vec![0, 0x20 | block.len() as u8];
+        data.extend_from_slice(block);
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        data[0] = (data.len() / 2) as u8;
+        data.chunks(2)
+            .enumerate()
+            .map(|(index, pair)| (index == 0, pair[0], pair[1]))
+            .collect()
+    }
+
+    fn push(decoder: &mut Cea708Decoder, block: &[u8]) {
+        for (is_start, first, second) in packet(block) {
+            decoder.push_pair(is_start, first, second);
+        }
+    }
+
+    fn get_text(window: &CaptionWindow) -> Vec<String> {
+        window
+            .lines
+            .iter()
+            .map(|line| line.runs.iter().map(|run| run.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn shows_windows() {
+        let mut decoder = Cea708Decoder::new(1).unwrap();
+        // Window 0, hidden, relative anchor at the bottom centre of 50% 90%
+        // with 2 rows of 20 columns.
+        push(&mut decoder, &[0x98, 0x00, 0xDA, 50, 0x71, 19, 0x00]);
+        push(&mut decoder, b"Hi\r");
+        // Italic yellow, then a G2 character.
+        push(&mut decoder, &[0x90, 0x00, 0x80, 0x91, 0x3C, 0x00, 0x00]);
+        push(&mut decoder, &[b'y', b'o', 0x10, 0x25]);
+        assert_eq!(decoder.take_windows().unwrap(), []);
+        push(&mut decoder, &[0x89, 0x01]);
+        let windows = decoder.take_windows().unwrap();
+        assert_eq!(get_text(&windows[0]), ["Hi", "yo…"]);
+        let run = &windows[0].lines[1].runs[0];
+        assert_eq!((run.color.as_str(), run.italic), ("#ffff00", true));
+        assert_eq!(run.background, Some("#000000".to_string()));
+        let window = &windows[0];
+        assert_eq!((window.width, window.height), (50.0, 80.0 * 2.0 / 15.0));
+        assert_eq!(window.left, 10.0 + 40.0 - 25.0);
+        assert!((window.top + window.height - 82.0).abs() < 0.001);
+        assert!(decoder.take_windows().is_none());
+    }
+
+    #[test]
+    fn scrolls_and_deletes_windows() {
+        let mut decoder = Cea708Decoder::new(1).unwrap();
+        push(&mut decoder, &[0x99, 0x20, 0x00, 0x00, 0x01, 9, 0x00]);
+        push(&mut decoder, b"one\rtwo\rthree");
+        assert_eq!(
+            get_text(&decoder.take_windows().unwrap()[0]),
+            ["two", "three"]
+        );
+        // Other services are ignored.
+        let other = [0x02, 0x40 | 2, b'n', b'o'];
+        for (index, pair) in other.chunks(2).enumerate() {
+            decoder.push_pair(index == 0, pair[0], pair[1]);
+        }
+        assert!(decoder.take_windows().is_none());
+        push(&mut decoder, &[0x8C, 0x02]);
+        assert_eq!(decoder.take_windows().unwrap(), []);
+        assert!(Cea708Decoder::new(0).is_err());
+    }
+}
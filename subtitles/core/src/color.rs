@@ -0,0 +1,174 @@
+// TTML <color> values: "#rrggbb", "#rrggbbaa", "rgb(r,g,b)", "rgba(r,g,b,a)"
+// and the named colours of TTML 1 / EBU-TT-D. Note that in TTML the alpha of
+// rgba() goes from 0 to 255, while in CSS it goes from 0 to 1.
+use serde::{Serialize, Serializer};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+const NAMED_COLORS: [(&str, Color); 19] = [
+    ("transparent", Color::rgba(0, 0, 0, 0)),
+    ("black", Color::rgb(0, 0, 0)),
+    ("silver", Color::rgb(192, 192, 192)),
+    ("gray", Color::rgb(128, 128, 128)),
+    ("white", Color::rgb(255, 255, 255)),
+    ("maroon", Color::rgb(128, 0, 0)),
+    ("red", Color::rgb(255, 0, 0)),
+    ("purple", Color::rgb(128, 0, 128)),
+    ("fuchsia", Color::rgb(255, 0, 255)),
+    ("magenta", Color::rgb(255, 0, 255)),
+    ("green", Color::rgb(0, 128, 0)),
+    ("lime", Color::rgb(0, 255, 0)),
+    ("olive", Color::rgb(128, 128, 0)),
+    ("yellow", Color::rgb(255, 255, 0)),
+    ("navy", Color::rgb(0, 0, 128)),
+    ("blue", Color::rgb(0, 0, 255)),
+    ("teal", Color::rgb(0, 128, 128)),
+    ("aqua", Color::rgb(0, 255, 255)),
+    ("cyan", Color::rgb(0, 255, 255)),
+];
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    pub fn parse(value: &str) -> Result<Color, String> {
+        let value = value.trim();
+        if let Some(hex) = value.strip_prefix('#') {
+            return Self::parse_hex(hex);
+        }
+        if let Some(components) = Self::function_arguments(value, "rgba") {
+            return match Self::parse_components(components)?[..] {
+                [r, g, b, a] => Ok(Color::rgba(r, g, b, a)),
+                _ => Err(format!("Invalid rgba colour: {}", value)),
+            };
+        }
+        if let Some(components) = Self::function_arguments(value, "rgb") {
+            return match Self::parse_components(components)?[..] {
+                [r, g, b] => Ok(Color::rgb(r, g, b)),
+                _ => Err(format!("Invalid rgb colour: {}", value)),
+            };
+        }
+        NAMED_COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(value))
+            .map(|(_, color)| *color)
+            .ok_or_else(|| format!("Unknown colour: {}", value))
+    }
+
+    fn parse_hex(hex: &str) -> Result<Color, String> {
+        if !hex.is_ascii() || (hex.len() != 6 && hex.len() != 8) {
+            return Err(format!("Invalid hexadecimal colour: #{}", hex));
+        }
+        let channel = |index: usize| {
+            u8::from_str_radix(&hex[index..index + 2], 16)
+                .map_err(|_| format!("Invalid hexadecimal colour: #{}", hex))
+        };
+        let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+        Ok(Color::rgba(channel(0)?, channel(2)?, channel(4)?, alpha))
+    }
+
+    fn function_arguments<'a>(value: &'a str, function: &str) -> Option<&'a str> {
+        let (name, rest) = value.split_once('(')?;
+        if name.trim() != function {
+            return None;
+        }
+        rest.trim_end().strip_suffix(')')
+    }
+
+    fn parse_components(components: &str) -> Result<Vec<u8>, String> {
+        components
+            .split(',')
+            .map(|component| {
+                component
+                    .trim()
+                    .parse::<u8>()
+                    .map_err(|_| format!("Invalid colour component: {}", component))
+            })
+            .collect()
+    }
+
+    pub fn is_transparent(self) -> bool {
+        self.a == 0
+    }
+
+    /// Value accepted by every CSS level, alpha is dropped.
+    pub fn to_css_rgb(self) -> String {
+        if self.is_transparent() {
+            return "transparent".to_string();
+        }
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    /// CSS value with the alpha converted to the 0-1 range of CSS3.
+    pub fn to_css(self) -> String {
+        if self.a == 255 || self.is_transparent() {
+            return self.to_css_rgb();
+        }
+        let alpha = format!("{:.3}", self.a as f32 / 255.0);
+        let alpha = alpha.trim_end_matches('0').trim_end_matches('.');
+        format!("rgba({},{},{},{})", self.r, self.g, self.b, alpha)
+    }
+
+    /// CSS declarations for `property`. Translucent colours are preceded by
+    /// an opaque fallback, so old CE-HTML browsers that do not understand
+    /// rgba() keep the first declaration and ignore the second one.
+    pub fn to_css_declarations(self, property: &str) -> Vec<String> {
+        let mut declarations = vec![format!("{}:{}", property, self.to_css_rgb())];
+        if self.a != 255 && !self.is_transparent() {
+            declarations.push(format!("{}:{}", property, self.to_css()));
+        }
+        declarations
+    }
+}
+
+/// Colors of the rendered output are CSS values.
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_css())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hexadecimal_colours() {
+        assert_eq!(Color::parse("#ff8000"), Ok(Color::rgb(255, 128, 0)));
+        assert_eq!(Color::parse("#ff800080"), Ok(Color::rgba(255, 128, 0, 128)));
+        assert!(Color::parse("#fff").is_err());
+        assert!(Color::parse("#gg0000").is_err());
+    }
+
+    #[test]
+    fn parses_functions_and_names() {
+        assert_eq!(Color::parse("rgb(1, 2, 3)"), Ok(Color::rgb(1, 2, 3)));
+        assert_eq!(Color::parse("rgba(1,2,3,0)"), Ok(Color::rgba(1, 2, 3, 0)));
+        assert_eq!(Color::parse("Yellow"), Ok(Color::rgb(255, 255, 0)));
+        assert!(Color::parse("rgb(1, 2)").is_err());
+        assert!(Color::parse("rgb(256, 0, 0)").is_err());
+        assert!(Color::parse("orange").is_err());
+    }
+
+    #[test]
+    fn converts_to_css() {
+        assert_eq!(Color::rgb(255, 0, 0).to_css(), "#ff0000");
+        assert_eq!(Color::rgba(0, 0, 0, 0).to_css(), "transparent");
+        assert_eq!(Color::rgba(0, 0, 0, 128).to_css(), "rgba(0,0,0,0.502)");
+        assert_eq!(
+            Color::rgba(0, 0, 0, 128).to_css_declarations("color"),
+            vec!["color:#000000", "color:rgba(0,0,0,0.502)"]
+        );
+    }
+}
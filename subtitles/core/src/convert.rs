@@ -0,0 +1,509 @@
+// Conversion of the loaded document to other subtitle formats. Subtitles are
+// rendered like for the screen and their computed styles are written back,
+// so the output does not depend on how the source organised its styles.
+// Images of the IMSC image profile have no text and are skipped.
+use super::color::Color;
+use super::render::{RenderedRun, RenderedSubtitle, TextStyle};
+use super::units::format_number;
+use super::Subtitles;
+
+/// A subtitle with the interval it is shown in.
+pub struct TimedSubtitle {
+    pub begin: i64,
+    pub end: i64,
+    pub subtitle: RenderedSubtitle,
+}
+
+/// "HH:MM:SS.mmm", or "HH:MM:SS,mmm" for SubRip.
+pub fn format_clock_time(ms: i64, separator: char) -> String {
+    let ms = ms.max(0);
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3600000,
+        ms / 60000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+pub fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub fn escape_attribute(text: &str) -> String {
+    escape_text(text).replace('"', "&quot;")
+}
+
+fn to_hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn to_hex_alpha(color: Color) -> String {
+    format!("{}{:02x}", to_hex(color), color.a)
+}
+
+fn is_italic(style: &TextStyle) -> bool {
+    matches!(
+        style.font_style.as_deref(),
+        Some("italic") | Some("oblique")
+    )
+}
+
+fn is_bold(style: &TextStyle) -> bool {
+    style.font_weight.as_deref() == Some("bold")
+}
+
+fn is_underlined(style: &TextStyle) -> bool {
+    style
+        .text_decoration
+        .as_deref()
+        .is_some_and(|text_decoration| text_decoration.contains("underline"))
+}
+
+/// Class of the default WebVTT colour classes matching the colour, if any.
+fn get_vtt_color_class(color: Color) -> Option<&'static str> {
+    const CLASSES: [(&str, Color); 8] = [
+        ("white", Color::rgb(255, 255, 255)),
+        ("lime", Color::rgb(0, 255, 0)),
+        ("cyan", Color::rgb(0, 255, 255)),
+        ("red", Color::rgb(255, 0, 0)),
+        ("yellow", Color::rgb(255, 255, 0)),
+        ("magenta", Color::rgb(255, 0, 255)),
+        ("blue", Color::rgb(0, 0, 255)),
+        ("black", Color::rgb(0, 0, 0)),
+    ];
+    CLASSES
+        .iter()
+        .find(|(_, class_color)| *class_color == color)
+        .map(|(class, _)| *class)
+}
+
+fn get_vtt_run(run: &RenderedRun) -> String {
+    let mut text = escape_text(&run.text);
+    if let Some(ruby) = run.ruby.as_ref() {
+        text = format!("<ruby>{}<rt>{}</rt></ruby>", text, escape_text(&ruby.text));
+    }
+    if let Some(class) = run.style.color.and_then(get_vtt_color_class) {
+        text = format!("<c.{}>{}</c>", class, text);
+    }
+    if is_underlined(&run.style) {
+        text = format!("<u>{}</u>", text);
+    }
+    if is_bold(&run.style) {
+        text = format!("<b>{}</b>", text);
+    }
+    if is_italic(&run.style) {
+        text = format!("<i>{}</i>", text);
+    }
+    text
+}
+
+/// SubRip has no ruby, the annotation follows its base in parentheses.
+fn get_srt_run(run: &RenderedRun) -> String {
+    let mut text = escape_text(&run.text);
+    if let Some(ruby) = run.ruby.as_ref() {
+        text = format!("{} ({})", text, escape_text(&ruby.text));
+    }
+    if let Some(color) = run.style.color {
+        text = format!("<font color=\"{}\">{}</font>", to_hex(color), text);
+    }
+    if is_underlined(&run.style) {
+        text = format!("<u>{}</u>", text);
+    }
+    if is_bold(&run.style) {
+        text = format!("<b>{}</b>", text);
+    }
+    if is_italic(&run.style) {
+        text = format!("<i>{}</i>", text);
+    }
+    text
+}
+
+fn get_text_lines(subtitle: &RenderedSubtitle, get_run: fn(&RenderedRun) -> String) -> String {
+    subtitle
+        .lines
+        .iter()
+        .map(|line| line.iter().map(get_run).collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Attributes of the `tts` namespace of a style.
+type EbuStyle = Vec<(&'static str, String)>;
+
+impl Subtitles {
+    /// Subtitles of the document with text, sorted by begin time, with the
+    /// media times of the timeline. Animations are rendered as they are at
+    /// the current time.
+    pub fn get_timed_subtitles(&self) -> Vec<TimedSubtitle> {
+        let mut timed_subtitles: Vec<TimedSubtitle> = (0..self.subtitle_refs.len())
+            .filter_map(|index| self.get_subtitle_element(index))
+            .filter_map(|element| {
+                let begin = element.get_begin();
+                let end = element.get_end();
+                if begin < 0 || end <= begin {
+                    return None;
+                }
+                let subtitle = self.build_subtitle(&element)?;
+                let has_text = subtitle
+                    .lines
+                    .iter()
+                    .flatten()
+                    .any(|run| !run.text.is_empty());
+                if !has_text {
+                    return None;
+                }
+                Some(TimedSubtitle {
+                    begin: self.timeline.to_media(begin),
+                    end: self.timeline.to_media(end),
+                    subtitle,
+                })
+            })
+            .collect();
+        timed_subtitles.sort_by_key(|timed_subtitle| timed_subtitle.begin);
+        timed_subtitles
+    }
+
+    /// Subtitles shown at the media time `ms`, text and images, in document
+    /// order. Unlike the scheduler it does not depend on the previous
+    /// updates, so it can jump to any time.
+    pub fn get_subtitles_at(&mut self, ms: i64) -> Vec<RenderedSubtitle> {
+        self.current_ms = self.timeline.to_document(ms);
+        let ms = self.current_ms;
+        (0..self.subtitle_refs.len())
+            .filter_map(|index| self.get_subtitle_element(index))
+            .filter(|element| element.get_begin() <= ms && ms < element.get_end())
+            .filter_map(|element| self.build_subtitle(&element))
+            .collect()
+    }
+
+    /// WebVTT cue settings that place the cue over its region, as
+    /// percentages of the element.
+    fn get_vtt_settings(&self, subtitle: &RenderedSubtitle) -> String {
+        let width = self.element_size.width as f32;
+        let height = self.element_size.height as f32;
+        let mut settings: Vec<String> = Vec::new();
+        if let Some(region) = subtitle
+            .region
+            .as_ref()
+            .filter(|_| width > 0.0 && height > 0.0)
+        {
+            let geometry = &region.geometry;
+            let percentage = |value: f32, size: f32| format_number(value * 100.0 / size);
+            let display_align = self
+                .get_region(&region.id)
+                .and_then(|region| region.display_align.as_deref());
+            settings.push(match display_align {
+                Some("after") => format!(
+                    "line:{}%,end",
+                    percentage(geometry.top + geometry.height, height)
+                ),
+                Some("center") => format!(
+                    "line:{}%,center",
+                    percentage(geometry.top + geometry.height / 2.0, height)
+                ),
+                _ => format!("line:{}%,start", percentage(geometry.top, height)),
+            });
+            settings.push(format!(
+                "position:{}%,line-left",
+                percentage(geometry.left, width)
+            ));
+            settings.push(format!("size:{}%", percentage(geometry.width, width)));
+        }
+        let text_align = match subtitle.style.text_align.as_deref() {
+            Some("left") => "left",
+            Some("right") => "right",
+            Some("start") => "start",
+            Some("end") => "end",
+            _ => "center",
+        };
+        settings.push(format!("align:{}", text_align));
+        settings.join(" ")
+    }
+
+    pub fn to_vtt(&self) -> String {
+        let mut vtt = String::from("WEBVTT\n");
+        for timed_subtitle in self.get_timed_subtitles() {
+            let subtitle = &timed_subtitle.subtitle;
+            vtt.push_str(&format!(
+                "\n{}\n{} --> {} {}\n{}\n",
+                subtitle.id,
+                format_clock_time(timed_subtitle.begin, '.'),
+                format_clock_time(timed_subtitle.end, '.'),
+                self.get_vtt_settings(subtitle),
+                get_text_lines(subtitle, get_vtt_run)
+            ));
+        }
+        vtt
+    }
+
+    pub fn to_srt(&self) -> String {
+        self.get_timed_subtitles()
+            .iter()
+            .enumerate()
+            .map(|(index, timed_subtitle)| {
+                format!(
+                    "{}\n{} --> {}\n{}\n",
+                    index + 1,
+                    format_clock_time(timed_subtitle.begin, ','),
+                    format_clock_time(timed_subtitle.end, ','),
+                    get_text_lines(&timed_subtitle.subtitle, get_srt_run)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Font size as a percentage of the cell height, the unit of EBU-TT-D.
+    fn get_font_size_percentage(&self, style: &TextStyle) -> String {
+        let cell_height = self.get_cell_size("y");
+        if cell_height <= 0.0 {
+            return "100%".to_string();
+        }
+        let height = format_number(style.font_size.height * 100.0 / cell_height);
+        let width = format_number(style.font_size.width * 100.0 / cell_height);
+        if width == height {
+            format!("{}%", height)
+        } else {
+            format!("{}% {}%", width, height)
+        }
+    }
+
+    fn get_ebu_run_style(&self, style: &TextStyle) -> EbuStyle {
+        let mut attributes: Vec<(&'static str, String)> = Vec::new();
+        if let Some(font_family) = style.font_family.as_ref() {
+            attributes.push(("fontFamily", font_family.clone()));
+        }
+        attributes.push(("fontSize", self.get_font_size_percentage(style)));
+        if let Some(color) = style.color {
+            attributes.push(("color", to_hex_alpha(color)));
+        }
+        if let Some(background_color) = style.background_color {
+            attributes.push(("backgroundColor", to_hex_alpha(background_color)));
+        }
+        if let Some(font_style) = style.font_style.as_ref() {
+            attributes.push(("fontStyle", font_style.clone()));
+        }
+        if let Some(font_weight) = style.font_weight.as_ref() {
+            attributes.push(("fontWeight", font_weight.clone()));
+        }
+        if let Some(text_decoration) = style.text_decoration.as_ref() {
+            attributes.push(("textDecoration", text_decoration.clone()));
+        }
+        attributes
+    }
+
+    fn get_ebu_paragraph_style(style: &TextStyle) -> EbuStyle {
+        let mut attributes: Vec<(&'static str, String)> = Vec::new();
+        if let Some(text_align) = style.text_align.as_ref() {
+            attributes.push(("textAlign", text_align.clone()));
+        }
+        if let Some(multi_row_align) = style.multi_row_align.as_ref() {
+            attributes.push(("multiRowAlign", multi_row_align.clone()));
+        }
+        attributes
+    }
+
+    /// Regions of the subtitles with their origin and extent in percentages
+    /// of the root container.
+    fn get_ebu_regions(&self, timed_subtitles: &[TimedSubtitle]) -> Vec<String> {
+        let width = self.element_size.width as f32;
+        let height = self.element_size.height as f32;
+        let mut region_ids: Vec<&str> = Vec::new();
+        let mut regions: Vec<String> = Vec::new();
+        for region in timed_subtitles
+            .iter()
+            .filter_map(|timed_subtitle| timed_subtitle.subtitle.region.as_ref())
+        {
+            if region_ids.contains(&region.id.as_str()) || width <= 0.0 || height <= 0.0 {
+                continue;
+            }
+            region_ids.push(&region.id);
+            let geometry = &region.geometry;
+            let percentage = |value: f32, size: f32| format_number(value * 100.0 / size);
+            let display_align = self
+                .get_region(&region.id)
+                .and_then(|region| region.display_align.clone())
+                .unwrap_or_else(|| "before".to_string());
+            regions.push(format!(
+                "      <tt:region xml:id=\"{}\" tts:origin=\"{}% {}%\" tts:extent=\"{}% {}%\" tts:displayAlign=\"{}\"/>\n",
+                escape_attribute(&region.id),
+                percentage(geometry.left, width),
+                percentage(geometry.top, height),
+                percentage(geometry.width, width),
+                percentage(geometry.height, height),
+                escape_attribute(&display_align)
+            ));
+        }
+        regions
+    }
+
+    /// EBU-TT-D document with referential styling: every distinct computed
+    /// style becomes a `tt:style`.
+    pub fn to_ebu_tt_d(&self) -> String {
+        let timed_subtitles = self.get_timed_subtitles();
+        let mut styles: Vec<EbuStyle> = Vec::new();
+        let mut get_style_id = |style: EbuStyle| -> String {
+            let index = match styles.iter().position(|attributes| *attributes == style) {
+                Some(index) => index,
+                None => {
+                    styles.push(style);
+                    styles.len() - 1
+                }
+            };
+            format!("s{}", index)
+        };
+        let mut paragraphs = String::new();
+        for (index, timed_subtitle) in timed_subtitles.iter().enumerate() {
+            let subtitle = &timed_subtitle.subtitle;
+            let mut attributes = format!(
+                "xml:id=\"sub{}\" begin=\"{}\" end=\"{}\" style=\"{}\"",
+                index,
+                format_clock_time(timed_subtitle.begin, '.'),
+                format_clock_time(timed_subtitle.end, '.'),
+                get_style_id(Self::get_ebu_paragraph_style(&subtitle.style))
+            );
+            if let Some(region) = subtitle.region.as_ref() {
+                attributes.push_str(&format!(" region=\"{}\"", escape_attribute(&region.id)));
+            }
+            let lines: Vec<String> = subtitle
+                .lines
+                .iter()
+                .map(|line| {
+                    line.iter()
+                        .map(|run| {
+                            format!(
+                                "<tt:span style=\"{}\">{}</tt:span>",
+                                get_style_id(self.get_ebu_run_style(&run.style)),
+                                escape_text(&run.text)
+                            )
+                        })
+                        .collect::<String>()
+                })
+                .collect();
+            paragraphs.push_str(&format!(
+                "      <tt:p {}>{}</tt:p>\n",
+                attributes,
+                lines.join("<tt:br/>")
+            ));
+        }
+        let styles: String = styles
+            .iter()
+            .enumerate()
+            .map(|(index, attributes)| {
+                let attributes: String = attributes
+                    .iter()
+                    .map(|(name, value)| format!(" tts:{}=\"{}\"", name, escape_attribute(value)))
+                    .collect();
+                format!("      <tt:style xml:id=\"s{}\"{}/>\n", index, attributes)
+            })
+            .collect();
+        let cell_resolution = &self.tt_root_config.cell_resolution;
+        let lang = self
+            .tt
+            .as_ref()
+            .map(|tt| tt.xml_lang.clone())
+            .unwrap_or_default();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" xmlns:tts="http://www.w3.org/ns/ttml#styling" xmlns:ebuttm="urn:ebu:tt:metadata" xmlns:ebutts="urn:ebu:tt:style" ttp:timeBase="media" ttp:cellResolution="{} {}" xml:lang="{}">
+  <tt:head>
+    <tt:metadata>
+      <ebuttm:documentMetadata>
+        <ebuttm:conformsToStandard>urn:ebu:tt:distribution:2018-04</ebuttm:conformsToStandard>
+      </ebuttm:documentMetadata>
+    </tt:metadata>
+    <tt:styling>
+{}    </tt:styling>
+    <tt:layout>
+{}    </tt:layout>
+  </tt:head>
+  <tt:body>
+    <tt:div>
+{}    </tt:div>
+  </tt:body>
+</tt:tt>
+"#,
+            cell_resolution.columns,
+            cell_resolution.rows,
+            escape_attribute(&lang),
+            styles,
+            self.get_ebu_regions(&timed_subtitles).concat(),
+            paragraphs
+        )
+    }
+
+    /// End of the last subtitle, the duration of the document.
+    pub fn get_duration_ms(&self) -> i64 {
+        (0..self.subtitle_refs.len())
+            .filter_map(|index| self.get_subtitle_element(index))
+            .map(|element| element.get_end())
+            .max()
+            .unwrap_or(0)
+            .max(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling">
+  <tt:head>
+    <tt:styling><tt:style xml:id="s1" tts:fontStyle="italic" tts:color="yellow"/></tt:styling>
+    <tt:layout><tt:region xml:id="r1" tts:origin="10% 80%" tts:extent="80% 10%" tts:displayAlign="after"/></tt:layout>
+  </tt:head>
+  <tt:body>
+    <tt:div region="r1">
+      <tt:p xml:id="p1" begin="00:00:01.000" end="00:00:02.500"><tt:span style="s1">Fish &amp; chips</tt:span><tt:br/><tt:span>&lt;3</tt:span></tt:p>
+    </tt:div>
+  </tt:body>
+</tt:tt>"#;
+
+    fn load() -> Subtitles {
+        let mut subtitles = Subtitles::new();
+        subtitles.set_element_size(1000, 500);
+        subtitles.load(DOCUMENT).unwrap();
+        subtitles
+    }
+
+    #[test]
+    fn formats_clock_times() {
+        assert_eq!(format_clock_time(3723004, '.'), "01:02:03.004");
+        assert_eq!(format_clock_time(-5, ','), "00:00:00,000");
+    }
+
+    #[test]
+    fn converts_to_vtt() {
+        assert_eq!(
+            load().to_vtt(),
+            "WEBVTT\n\np1\n00:00:01.000 --> 00:00:02.500 line:90%,end position:10%,line-left size:80% align:center\n<i><c.yellow>Fish &amp; chips</c></i>\n&lt;3\n"
+        );
+    }
+
+    #[test]
+    fn converts_to_srt() {
+        assert_eq!(
+            load().to_srt(),
+            "1\n00:00:01,000 --> 00:00:02,500\n<i><font color=\"#ffff00\">Fish &amp; chips</font></i>\n&lt;3\n"
+        );
+    }
+
+    #[test]
+    fn converts_to_a_valid_ebu_tt_d_document() {
+        let mut converted = Subtitles::new();
+        converted.set_element_size(1000, 500);
+        converted.load(&load().to_ebu_tt_d()).unwrap();
+        assert_eq!(converted.validate(), Vec::new());
+        let subtitles = converted.get_subtitles_at(1000);
+        assert_eq!(subtitles.len(), 1);
+        assert_eq!(subtitles[0].lines[0][0].text, "Fish & chips");
+        assert_eq!(
+            subtitles[0].lines[0][0].style.font_style.as_deref(),
+            Some("italic")
+        );
+    }
+}
@@ -1,24 +1,17 @@
 //use std::collections::HashMap;
-use wasm_bindgen::prelude::*;
-
-#[wasm_bindgen]
-extern "C" {
-    // Use `js_namespace` here to bind `console.log(..)` instead of just
-    // `log(..)`
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
-}
 
 struct CuePointsMargin {
-    positive: i32,
-    negative: i32,
+    positive: i64,
+    negative: i64,
 }
 
+#[allow(dead_code)]
 struct SubtitlesConfig {
     begin_cue_points_margin: CuePointsMargin,
     end_cue_points_margin: CuePointsMargin,
 }
 
+#[allow(dead_code)]
 struct GlobalConfiguration {
     subtitles: SubtitlesConfig,
     cue_points_margin: CuePointsMargin,
@@ -43,11 +36,12 @@ const GLOBAL_CONFIGURATION: GlobalConfiguration = GlobalConfiguration {
 
 pub struct Cuepoint {
     pub id: String,
-    pub ms: i32,
-    pub timestopass: i32,
-    pub negativemargin: Option<i32>,
-    pub positivemargin: Option<i32>,
-    //pub callback: Option<Box<dyn Fn(i32, &Cuepoint)>>,
+    pub ms: i64,
+    pub timestopass: i64,
+    pub negativemargin: Option<i64>,
+    pub positivemargin: Option<i64>,
+    //pub callback: Option<Box<dyn Fn(i64, &Cuepoint)>>,
+    #[allow(dead_code)]
     pub once: bool,
 }
 
@@ -74,7 +68,9 @@ impl Cuepoints {
         self.cuepoints.last().unwrap()
     }
 
-    pub fn get_cuepoints_by_time(&self, ms: i32) -> Vec<&Cuepoint> {
+    /// Cuepoints whose window contains `ms`. `margin_scale` widens the
+    /// positive margin, for updates that advance more than real time.
+    pub fn get_cuepoints_by_time(&self, ms: i64, margin_scale: f64) -> Vec<&Cuepoint> {
         let mut found = Vec::new();
         for cuepoint in &self.cuepoints {
             let negativemargin = cuepoint.negativemargin.unwrap_or(
@@ -89,6 +85,7 @@ impl Cuepoints {
                     .begin_cue_points_margin
                     .positive,
             );
+            let positivemargin = (positivemargin as f64 * margin_scale).round() as i64;
             if ms >= cuepoint.ms - negativemargin && ms < cuepoint.ms + positivemargin {
                 found.push(cuepoint);
             }
@@ -96,19 +93,19 @@ impl Cuepoints {
         found
     }
 
+    #[allow(dead_code)]
     pub fn remove_cuepoint(&mut self, cues: Vec<&Cuepoint>) {
         self.cuepoints.retain(|cue| !cues.contains(&cue));
     }
 
-    pub fn check_cuepoints(&mut self, ms: i32) {
-        let cues = self.get_cuepoints_by_time(ms);
+    #[allow(dead_code)]
+    pub fn check_cuepoints(&mut self, ms: i64) {
+        let cues = self.get_cuepoints_by_time(ms, 1.0);
         let mut to_remove_indices = Vec::new();
 
         for (index, cue) in cues.iter().enumerate() {
-            if cue.timestopass <= 0 {
-                if cue.once {
-                    to_remove_indices.push(index);
-                }
+            if cue.timestopass <= 0 && cue.once {
+                to_remove_indices.push(index);
                 //log(&format!("ms {} {}", ms, cue.id));
                 //(cue.callback)(ms, cue);
             }
@@ -0,0 +1,717 @@
+// Subtitle tracks of a DASH MPD: the text AdaptationSets with their language
+// and roles, and the URLs and presentation times of their segments, resolved
+// from BaseURL, SegmentTemplate and SegmentTimeline. The application fetches
+// the segments and appends them (`append_document`, `append_media_segment`).
+//
+// Times are milliseconds on the presentation timeline of the MPD, the start
+// of the first period is 0. For live MPDs they require the current time.
+use super::dates::{parse_date_time, parse_duration};
+use super::timeline::ticks_to_ms;
+use super::url::resolve_url;
+use quick_xml::de::from_str;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Debug, Default)]
+struct BaseUrl {
+    #[serde(rename = "$text", default)]
+    url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Mpd {
+    #[serde(rename = "@type")]
+    mpd_type: Option<String>,
+    #[serde(rename = "@mediaPresentationDuration")]
+    media_presentation_duration: Option<String>,
+    #[serde(rename = "@availabilityStartTime")]
+    availability_start_time: Option<String>,
+    #[serde(rename = "@timeShiftBufferDepth")]
+    time_shift_buffer_depth: Option<String>,
+    #[serde(rename = "BaseURL", default)]
+    base_urls: Vec<BaseUrl>,
+    #[serde(rename = "Period", default)]
+    periods: Vec<Period>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Period {
+    #[serde(rename = "@id")]
+    id: Option<String>,
+    #[serde(rename = "@start")]
+    start: Option<String>,
+    #[serde(rename = "@duration")]
+    duration: Option<String>,
+    #[serde(rename = "BaseURL", default)]
+    base_urls: Vec<BaseUrl>,
+    #[serde(rename = "AdaptationSet", default)]
+    adaptation_sets: Vec<AdaptationSet>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Descriptor {
+    #[serde(rename = "@value")]
+    value: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AdaptationSet {
+    #[serde(rename = "@id")]
+    id: Option<String>,
+    #[serde(rename = "@contentType")]
+    content_type: Option<String>,
+    #[serde(rename = "@mimeType")]
+    mime_type: Option<String>,
+    #[serde(rename = "@codecs")]
+    codecs: Option<String>,
+    #[serde(rename = "@lang")]
+    lang: Option<String>,
+    #[serde(rename = "Role", default)]
+    roles: Vec<Descriptor>,
+    #[serde(rename = "BaseURL", default)]
+    base_urls: Vec<BaseUrl>,
+    #[serde(rename = "SegmentTemplate")]
+    segment_template: Option<SegmentTemplate>,
+    #[serde(rename = "Representation", default)]
+    representations: Vec<Representation>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Representation {
+    #[serde(rename = "@id")]
+    id: Option<String>,
+    #[serde(rename = "@bandwidth")]
+    bandwidth: Option<u64>,
+    #[serde(rename = "@mimeType")]
+    mime_type: Option<String>,
+    #[serde(rename = "@codecs")]
+    codecs: Option<String>,
+    #[serde(rename = "BaseURL", default)]
+    base_urls: Vec<BaseUrl>,
+    #[serde(rename = "SegmentTemplate")]
+    segment_template: Option<SegmentTemplate>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+struct SegmentTemplate {
+    #[serde(rename = "@media")]
+    media: Option<String>,
+    #[serde(rename = "@initialization")]
+    initialization: Option<String>,
+    #[serde(rename = "@timescale")]
+    timescale: Option<u64>,
+    #[serde(rename = "@duration")]
+    duration: Option<u64>,
+    #[serde(rename = "@startNumber")]
+    start_number: Option<u64>,
+    #[serde(rename = "@presentationTimeOffset")]
+    presentation_time_offset: Option<u64>,
+    #[serde(rename = "SegmentTimeline")]
+    segment_timeline: Option<SegmentTimeline>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+struct SegmentTimeline {
+    #[serde(rename = "S", default)]
+    segments: Vec<TimelineSegment>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct TimelineSegment {
+    #[serde(rename = "@t")]
+    t: Option<u64>,
+    #[serde(rename = "@d")]
+    d: u64,
+    #[serde(rename = "@r")]
+    r: Option<i64>,
+}
+
+impl SegmentTemplate {
+    /// The attributes of the Representation template override the ones of
+    /// the AdaptationSet.
+    fn merge(
+        parent: Option<&SegmentTemplate>,
+        child: Option<&SegmentTemplate>,
+    ) -> Option<SegmentTemplate> {
+        let (parent, child) = match (parent, child) {
+            (None, None) => return None,
+            (Some(template), None) | (None, Some(template)) => return Some(template.clone()),
+            (Some(parent), Some(child)) => (parent, child),
+        };
+        Some(SegmentTemplate {
+            media: child.media.clone().or_else(|| parent.media.clone()),
+            initialization: child
+                .initialization
+                .clone()
+                .or_else(|| parent.initialization.clone()),
+            timescale: child.timescale.or(parent.timescale),
+            duration: child.duration.or(parent.duration),
+            start_number: child.start_number.or(parent.start_number),
+            presentation_time_offset: child
+                .presentation_time_offset
+                .or(parent.presentation_time_offset),
+            segment_timeline: child
+                .segment_timeline
+                .clone()
+                .or_else(|| parent.segment_timeline.clone()),
+        })
+    }
+}
+
+/// A segment to fetch and its presentation interval.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashSegment {
+    pub number: u64,
+    pub url: String,
+    pub begin: i64,
+    pub end: i64,
+}
+
+/// A Representation of a text AdaptationSet.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashTextTrack {
+    pub period_id: Option<String>,
+    pub period_start: i64,
+    pub adaptation_set_id: Option<String>,
+    pub representation_id: Option<String>,
+    pub lang: Option<String>,
+    /// Values of the Role descriptors, e.g. "subtitle" or "caption".
+    pub roles: Vec<String>,
+    pub mime_type: Option<String>,
+    pub codecs: Option<String>,
+    pub bandwidth: Option<u64>,
+    /// Init segment of fragmented MP4 tracks.
+    pub initialization: Option<String>,
+    pub segments: Vec<DashSegment>,
+}
+
+fn is_text(content_type: Option<&str>, mime_type: Option<&str>, codecs: Option<&str>) -> bool {
+    let is_text_codec = codecs.is_some_and(|codecs| {
+        codecs
+            .split(',')
+            .any(|codec| codec.trim().starts_with("stpp") || codec.trim().starts_with("wvtt"))
+    });
+    content_type == Some("text")
+        || matches!(mime_type, Some("application/ttml+xml") | Some("text/vtt"))
+        || (mime_type == Some("application/mp4") && is_text_codec)
+}
+
+fn get_base_url(base: &str, base_urls: &[BaseUrl]) -> String {
+    match base_urls.first() {
+        Some(base_url) => resolve_url(base, base_url.url.trim()),
+        None => base.to_string(),
+    }
+}
+
+/// Replaces the identifiers of a template: `$RepresentationID$`,
+/// `$Number$`, `$Time$` and `$Bandwidth$`, with an optional width like
+/// `$Number%05d$`.
+fn fill_template(
+    template: &str,
+    representation: &Representation,
+    number: u64,
+    time: u64,
+) -> String {
+    let mut result = String::new();
+    let mut parts = template.split('$');
+    if let Some(first) = parts.next() {
+        result.push_str(first);
+    }
+    let parts: Vec<&str> = parts.collect();
+    let mut index = 0;
+    while index < parts.len() {
+        let identifier = parts[index];
+        // An identifier is closed by the next "$", the last part is text.
+        if index + 1 >= parts.len() {
+            result.push('$');
+            result.push_str(identifier);
+            break;
+        }
+        let (name, format) = match identifier.split_once('%') {
+            Some((name, format)) => (name, Some(format)),
+            None => (identifier, None),
+        };
+        let width = format
+            .and_then(|format| format.strip_prefix('0'))
+            .and_then(|format| format.strip_suffix('d'))
+            .and_then(|width| width.parse::<usize>().ok())
+            .unwrap_or(0);
+        let value = match name {
+            "" => Some("$".to_string()),
+            "RepresentationID" => Some(representation.id.clone().unwrap_or_default()),
+            "Number" => Some(format!("{:0width$}", number, width = width)),
+            "Time" => Some(format!("{:0width$}", time, width = width)),
+            "Bandwidth" => Some(format!(
+                "{:0width$}",
+                representation.bandwidth.unwrap_or(0),
+                width = width
+            )),
+            _ => None,
+        };
+        match value {
+            Some(value) => result.push_str(&value),
+            None => {
+                result.push('$');
+                result.push_str(identifier);
+                result.push('$');
+            }
+        }
+        result.push_str(parts[index + 1]);
+        index += 2;
+    }
+    result
+}
+
+/// Segments listed per Representation. Live MPDs without timeShiftBufferDepth
+/// keep the ones nearest to the live edge, long static MPDs the first ones.
+pub const MAX_SEGMENTS: usize = 10000;
+
+fn ms_to_ticks(ms: i64, timescale: u64) -> i128 {
+    ms as i128 * timescale as i128 / 1000
+}
+
+/// Interval of a period on the presentation timeline and the live edge.
+struct PeriodTiming {
+    start: i64,
+    end: Option<i64>,
+    /// Presentation time of the live edge, for dynamic MPDs.
+    now: Option<i64>,
+    /// Presentation time of the oldest segment still available.
+    time_shift_start: Option<i64>,
+}
+
+fn get_segments(
+    template: &SegmentTemplate,
+    representation: &Representation,
+    base_url: &str,
+    timing: &PeriodTiming,
+) -> Vec<DashSegment> {
+    let media = match template.media.as_ref() {
+        Some(media) => media,
+        None => return Vec::new(),
+    };
+    let timescale = template
+        .timescale
+        .filter(|timescale| *timescale > 0)
+        .unwrap_or(1);
+    let offset = template.presentation_time_offset.unwrap_or(0) as i128;
+    let start_number = template.start_number.unwrap_or(1);
+    // Last time of the period in ticks, where segments repeated with a
+    // negative `r` stop.
+    let limit = timing
+        .end
+        .or(timing.now)
+        .map(|end| ms_to_ticks(end - timing.start, timescale) + offset);
+    let is_live = timing.now.is_some();
+    let mut segments = Vec::new();
+    let mut push = |number: u64, time: i128, duration: i128| {
+        let begin = timing.start + ticks_to_ms(time - offset, timescale);
+        let end = timing.start + ticks_to_ms(time + duration - offset, timescale);
+        if timing.time_shift_start.is_some_and(|start| end <= start) {
+            return;
+        }
+        segments.push(DashSegment {
+            number,
+            url: resolve_url(
+                base_url,
+                &fill_template(media, representation, number, time.max(0) as u64),
+            ),
+            begin,
+            end,
+        });
+    };
+    if let Some(timeline) = template.segment_timeline.as_ref() {
+        let mut number = start_number;
+        let mut time: i128 = 0;
+        let mut listed = 0;
+        for (index, segment) in timeline.segments.iter().enumerate() {
+            if let Some(t) = segment.t {
+                time = t as i128;
+            }
+            let duration = segment.d as i128;
+            if duration == 0 {
+                continue;
+            }
+            let repeat = match segment.r.unwrap_or(0) {
+                r if r >= 0 => r as i128,
+                _ => {
+                    let next = timeline
+                        .segments
+                        .get(index + 1)
+                        .and_then(|next| next.t)
+                        .map(|t| t as i128)
+                        .or(limit);
+                    match next {
+                        Some(next) => ((next - time + duration - 1) / duration - 1).max(0),
+                        None => 0,
+                    }
+                }
+            };
+            let mut count = repeat + 1;
+            if let Some(limit) = limit.filter(|_| is_live) {
+                // Repeats older than the last MAX_SEGMENTS are not listed.
+                let skipped = ((limit - time) / duration - MAX_SEGMENTS as i128).clamp(0, count);
+                number += skipped as u64;
+                time += skipped * duration;
+                count -= skipped;
+            }
+            for _ in 0..count {
+                if is_live && limit.is_some_and(|limit| time + duration > limit) {
+                    break;
+                }
+                if !is_live && listed >= MAX_SEGMENTS {
+                    break;
+                }
+                push(number, time, duration);
+                listed += 1;
+                number += 1;
+                time += duration;
+            }
+        }
+    } else if let Some(duration) = template.duration.filter(|duration| *duration > 0) {
+        let duration = duration as i128;
+        let count = match (timing.end, timing.now) {
+            (Some(end), _) => {
+                let ticks = ms_to_ticks(end - timing.start, timescale);
+                (ticks + duration - 1) / duration
+            }
+            // Segments completely available at the live edge.
+            (None, Some(now)) => ms_to_ticks(now - timing.start, timescale) / duration,
+            (None, None) => 0,
+        };
+        let mut first = match timing.time_shift_start {
+            Some(start) => (ms_to_ticks(start - timing.start, timescale) / duration).max(0),
+            None => 0,
+        };
+        let mut count = count.max(0);
+        if is_live {
+            first = first.max(count - MAX_SEGMENTS as i128);
+        } else {
+            count = count.min(first + MAX_SEGMENTS as i128);
+        }
+        for index in first..count {
+            push(
+                start_number + index as u64,
+                offset + index * duration,
+                duration,
+            );
+        }
+    }
+    if segments.len() > MAX_SEGMENTS {
+        segments.drain(..segments.len() - MAX_SEGMENTS);
+    }
+    segments
+}
+
+/// Text tracks of an MPD. `mpd_url` resolves the relative URLs and `now`
+/// (milliseconds since the Unix epoch) is the current time for live MPDs.
+pub fn parse_mpd(xml: &str, mpd_url: &str, now: Option<i64>) -> Result<Vec<DashTextTrack>, String> {
+    let mpd: Mpd = from_str(xml).map_err(|error| format!("Invalid MPD: {}", error))?;
+    let is_dynamic = mpd.mpd_type.as_deref() == Some("dynamic");
+    let availability_start = mpd
+        .availability_start_time
+        .as_deref()
+        .map(parse_date_time)
+        .transpose()?;
+    let live_edge = match (is_dynamic, now, availability_start) {
+        (true, Some(now), Some(availability_start)) => Some(now - availability_start),
+        _ => None,
+    };
+    let time_shift_buffer_depth = mpd
+        .time_shift_buffer_depth
+        .as_deref()
+        .map(parse_duration)
+        .transpose()?;
+    let presentation_duration = mpd
+        .media_presentation_duration
+        .as_deref()
+        .map(parse_duration)
+        .transpose()?;
+    let mpd_base_url = get_base_url(mpd_url, &mpd.base_urls);
+    let mut tracks = Vec::new();
+    let mut previous_end: Option<i64> = Some(0);
+    for (period_index, period) in mpd.periods.iter().enumerate() {
+        let start = match period.start.as_deref() {
+            Some(start) => parse_duration(start)?,
+            None => previous_end.ok_or("Missing start of period")?,
+        };
+        let next_start = mpd
+            .periods
+            .get(period_index + 1)
+            .and_then(|next| next.start.as_deref())
+            .map(parse_duration)
+            .transpose()?;
+        let end = match period.duration.as_deref() {
+            Some(duration) => Some(start + parse_duration(duration)?),
+            None => next_start.or(presentation_duration.filter(|_| !is_dynamic)),
+        };
+        previous_end = end;
+        let timing = PeriodTiming {
+            start,
+            end,
+            now: live_edge,
+            time_shift_start: live_edge
+                .zip(time_shift_buffer_depth)
+                .map(|(now, depth)| now - depth),
+        };
+        let period_base_url = get_base_url(&mpd_base_url, &period.base_urls);
+        for adaptation_set in period.adaptation_sets.iter() {
+            let adaptation_base_url = get_base_url(&period_base_url, &adaptation_set.base_urls);
+            for representation in adaptation_set.representations.iter() {
+                let mime_type = representation
+                    .mime_type
+                    .clone()
+                    .or_else(|| adaptation_set.mime_type.clone());
+                let codecs = representation
+                    .codecs
+                    .clone()
+                    .or_else(|| adaptation_set.codecs.clone());
+                if !is_text(
+                    adaptation_set.content_type.as_deref(),
+                    mime_type.as_deref(),
+                    codecs.as_deref(),
+                ) {
+                    continue;
+                }
+                let base_url = get_base_url(&adaptation_base_url, &representation.base_urls);
+                let template = SegmentTemplate::merge(
+                    adaptation_set.segment_template.as_ref(),
+                    representation.segment_template.as_ref(),
+                );
+                let (initialization, segments) = match template.as_ref() {
+                    Some(template) => (
+                        template.initialization.as_ref().map(|initialization| {
+                            resolve_url(
+                                &base_url,
+                                &fill_template(initialization, representation, 0, 0),
+                            )
+                        }),
+                        get_segments(template, representation, &base_url, &timing),
+                    ),
+                    // A single file for the whole period, e.g. a TTML document.
+                    None => (
+                        None,
+                        end.map(|end| DashSegment {
+                            number: 1,
+                            url: base_url.clone(),
+                            begin: start,
+                            end,
+                        })
+                        .into_iter()
+                        .collect(),
+                    ),
+                };
+                tracks.push(DashTextTrack {
+                    period_id: period.id.clone(),
+                    period_start: start,
+                    adaptation_set_id: adaptation_set.id.clone(),
+                    representation_id: representation.id.clone(),
+                    lang: adaptation_set.lang.clone(),
+                    roles: adaptation_set
+                        .roles
+                        .iter()
+                        .filter_map(|role| role.value.clone())
+                        .collect(),
+                    mime_type,
+                    codecs,
+                    bandwidth: representation.bandwidth,
+                    initialization,
+                    segments,
+                });
+            }
+        }
+    }
+    Ok(tracks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT10S">
+  <BaseURL>media/</BaseURL>
+  <Period id="p0" start="PT0S">
+    <AdaptationSet id="1" contentType="video" mimeType="video/mp4">
+      <Representation id="v1" bandwidth="1000000"/>
+    </AdaptationSet>
+    <AdaptationSet id="2" mimeType="application/mp4" codecs="stpp" lang="ca">
+      <Role schemeIdUri="urn:mpeg:dash:role:2011" value="subtitle"/>
+      <SegmentTemplate timescale="1000" initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/$Number%03d$.mp4" duration="4000" startNumber="1"/>
+      <Representation id="sub-ca" bandwidth="2000"/>
+    </AdaptationSet>
+    <AdaptationSet id="3" mimeType="application/ttml+xml" lang="en">
+      <Representation id="sub-en"><BaseURL>en.ttml</BaseURL></Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+
+    #[test]
+    fn lists_the_text_tracks_of_a_vod_mpd() {
+        let tracks = parse_mpd(VOD, "https://cdn.example.com/vod/manifest.mpd", None).unwrap();
+        assert_eq!(tracks.len(), 2);
+        let track = &tracks[0];
+        assert_eq!(track.lang.as_deref(), Some("ca"));
+        assert_eq!(track.roles, vec!["subtitle"]);
+        assert_eq!(track.codecs.as_deref(), Some("stpp"));
+        assert_eq!(
+            track.initialization.as_deref(),
+            Some("https://cdn.example.com/vod/media/sub-ca/init.mp4")
+        );
+        let segments: Vec<(u64, &str, i64, i64)> = track
+            .segments
+            .iter()
+            .map(|segment| {
+                (
+                    segment.number,
+                    segment.url.as_str(),
+                    segment.begin,
+                    segment.end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (
+                    1,
+                    "https://cdn.example.com/vod/media/sub-ca/001.mp4",
+                    0,
+                    4000
+                ),
+                (
+                    2,
+                    "https://cdn.example.com/vod/media/sub-ca/002.mp4",
+                    4000,
+                    8000
+                ),
+                (
+                    3,
+                    "https://cdn.example.com/vod/media/sub-ca/003.mp4",
+                    8000,
+                    12000
+                ),
+            ]
+        );
+        assert_eq!(
+            tracks[1].segments,
+            vec![DashSegment {
+                number: 1,
+                url: "https://cdn.example.com/vod/media/en.ttml".to_string(),
+                begin: 0,
+                end: 10000,
+            }]
+        );
+    }
+
+    #[test]
+    fn computes_the_segment_timeline() {
+        let mpd = r#"<MPD type="static" mediaPresentationDuration="PT1M">
+  <Period start="PT10S">
+    <AdaptationSet contentType="text" mimeType="application/mp4" codecs="wvtt">
+      <SegmentTemplate timescale="90000" presentationTimeOffset="900000" media="t$Time$.mp4">
+        <SegmentTimeline>
+          <S t="900000" d="180000" r="1"/>
+          <S d="90000"/>
+          <S t="1440000" d="180000" r="-1"/>
+        </SegmentTimeline>
+      </SegmentTemplate>
+      <Representation id="vtt"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+        let tracks = parse_mpd(mpd, "", None).unwrap();
+        let segments: Vec<(&str, i64, i64)> = tracks[0]
+            .segments
+            .iter()
+            .map(|segment| (segment.url.as_str(), segment.begin, segment.end))
+            .collect();
+        assert_eq!(
+            &segments[..4],
+            &[
+                ("t900000.mp4", 10000, 12000),
+                ("t1080000.mp4", 12000, 14000),
+                ("t1260000.mp4", 14000, 15000),
+                ("t1440000.mp4", 16000, 18000),
+            ]
+        );
+        // Repeated until the end of the period, at 60s.
+        assert_eq!(segments.len(), 25);
+        assert_eq!(segments[24].2, 60000);
+    }
+
+    #[test]
+    fn lists_the_available_segments_of_a_live_mpd() {
+        let mpd = r#"<MPD type="dynamic" availabilityStartTime="2024-01-01T00:00:00Z" timeShiftBufferDepth="PT10S">
+  <Period id="live" start="PT0S">
+    <AdaptationSet mimeType="application/mp4" codecs="stpp.ttml.etd1">
+      <Representation id="s"><SegmentTemplate media="$Number$.m4s" duration="2" startNumber="0"/></Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+        let now = parse_date_time("2024-01-01T00:01:00.500Z").unwrap();
+        let tracks = parse_mpd(mpd, "https://live.example.com/a/", Some(now)).unwrap();
+        let numbers: Vec<u64> = tracks[0]
+            .segments
+            .iter()
+            .map(|segment| segment.number)
+            .collect();
+        assert_eq!(numbers, vec![25, 26, 27, 28, 29]);
+        assert_eq!(
+            tracks[0].segments[4].url,
+            "https://live.example.com/a/29.m4s"
+        );
+        assert!(parse_mpd("<MPD", "", None).is_err());
+    }
+
+    #[test]
+    fn limits_the_segments_of_a_live_mpd_without_buffer_depth() {
+        let mpd = r#"<MPD type="dynamic" availabilityStartTime="2000-01-01T00:00:00Z">
+  <Period start="PT0S">
+    <AdaptationSet mimeType="application/mp4" codecs="wvtt">
+      <SegmentTemplate media="$Number$.m4s" timescale="1000" duration="1000" startNumber="1"/>
+      <Representation id="a"/>
+    </AdaptationSet>
+    <AdaptationSet mimeType="application/mp4" codecs="wvtt">
+      <SegmentTemplate media="$Time$.m4s" timescale="1000">
+        <SegmentTimeline><S t="0" d="1000" r="-1"/></SegmentTimeline>
+      </SegmentTemplate>
+      <Representation id="b"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+        let now = parse_date_time("2024-01-01T00:00:00Z").unwrap();
+        let live_edge = now - parse_date_time("2000-01-01T00:00:00Z").unwrap();
+        let tracks = parse_mpd(mpd, "", Some(now)).unwrap();
+        for track in tracks.iter() {
+            assert_eq!(track.segments.len(), MAX_SEGMENTS);
+            assert_eq!(track.segments.last().unwrap().end, live_edge);
+        }
+        assert_eq!(
+            tracks[0].segments.last().unwrap().number,
+            (live_edge / 1000) as u64
+        );
+    }
+
+    #[test]
+    fn fills_templates() {
+        let representation = Representation {
+            id: Some("r1".to_string()),
+            bandwidth: Some(500),
+            mime_type: None,
+            codecs: None,
+            base_urls: Vec::new(),
+            segment_template: None,
+        };
+        assert_eq!(
+            fill_template(
+                "$RepresentationID$-$Bandwidth$-$Time%08d$-$$-$Other$",
+                &representation,
+                3,
+                42
+            ),
+            "r1-500-00000042-$-$Other$"
+        );
+    }
+}
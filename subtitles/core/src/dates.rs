@@ -0,0 +1,134 @@
+// ISO 8601 dates and durations of streaming manifests (e.g. the attributes of
+// DASH MPDs), in milliseconds.
+
+fn parse_number(text: &str, what: &str, value: &str) -> Result<f64, String> {
+    text.parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite() && *number >= 0.0)
+        .ok_or_else(|| format!("Invalid {}: {}", what, value))
+}
+
+/// "PT1H2M3.5S" or "P1DT2H". Years and months are rejected, their length
+/// depends on the date.
+pub fn parse_duration(duration: &str) -> Result<i64, String> {
+    let error = || format!("Invalid duration: {}", duration);
+    let rest = duration.trim().strip_prefix('P').ok_or_else(error)?;
+    let (date, time) = match rest.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (rest, None),
+    };
+    let mut seconds = 0.0;
+    let mut read = |text: &str, units: &[(char, f64)]| -> Result<(), String> {
+        let mut number = String::new();
+        for c in text.chars() {
+            match units.iter().find(|(unit, _)| *unit == c) {
+                Some((_, factor)) if !number.is_empty() => {
+                    seconds += parse_number(&number, "duration", duration)? * factor;
+                    number.clear();
+                }
+                _ if c.is_ascii_digit() || c == '.' => number.push(c),
+                _ => return Err(error()),
+            }
+        }
+        if number.is_empty() {
+            Ok(())
+        } else {
+            Err(error())
+        }
+    };
+    read(date, &[('W', 604800.0), ('D', 86400.0)])?;
+    if let Some(time) = time {
+        if time.is_empty() {
+            return Err(error());
+        }
+        read(time, &[('H', 3600.0), ('M', 60.0), ('S', 1.0)])?;
+    }
+    Ok((seconds * 1000.0).round() as i64)
+}
+
+/// Days from 1970-01-01 to a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// "2024-01-02T03:04:05.678Z", with "Z", an offset ("+01:00") or no zone
+/// (UTC), as milliseconds since the Unix epoch.
+pub fn parse_date_time(date_time: &str) -> Result<i64, String> {
+    let error = || format!("Invalid date: {}", date_time);
+    let date_time = date_time.trim();
+    let (date, time) = date_time.split_once('T').ok_or_else(error)?;
+    let date: Vec<i64> = date
+        .split('-')
+        .map(|block| block.parse::<i64>().map_err(|_| error()))
+        .collect::<Result<_, _>>()?;
+    let (year, month, day) = match date[..] {
+        [year, month, day] if (1..=12).contains(&month) && (1..=31).contains(&day) => {
+            (year, month, day)
+        }
+        _ => return Err(error()),
+    };
+    let (time, offset) = if let Some(time) = time.strip_suffix('Z') {
+        (time, 0)
+    } else if let Some(index) = time.rfind(['+', '-']) {
+        let (hours, minutes) = time[index + 1..].split_once(':').ok_or_else(error)?;
+        let hours = hours.parse::<i64>().map_err(|_| error())?;
+        let minutes = minutes.parse::<i64>().map_err(|_| error())?;
+        let sign = if time[index..].starts_with('-') {
+            -1
+        } else {
+            1
+        };
+        (&time[..index], sign * (hours * 60 + minutes) * 60000)
+    } else {
+        (time, 0)
+    };
+    let blocks: Vec<&str> = time.split(':').collect();
+    let (hours, minutes, seconds) = match blocks[..] {
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        [hours, minutes] => (hours, minutes, "0"),
+        _ => return Err(error()),
+    };
+    let hours = hours.parse::<i64>().map_err(|_| error())?;
+    let minutes = minutes.parse::<i64>().map_err(|_| error())?;
+    let seconds = parse_number(seconds, "date", date_time)?;
+    let days = days_from_civil(year, month, day);
+    Ok(
+        (days * 86400 + hours * 3600 + minutes * 60) * 1000 + (seconds * 1000.0).round() as i64
+            - offset,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("PT1H2M3.5S"), Ok(3723500));
+        assert_eq!(parse_duration("P1DT2H"), Ok(93600000));
+        assert_eq!(parse_duration("PT0S"), Ok(0));
+        assert!(parse_duration("P1M").is_err());
+        assert!(parse_duration("PT").is_err());
+        assert!(parse_duration("1H").is_err());
+    }
+
+    #[test]
+    fn parses_date_times() {
+        assert_eq!(parse_date_time("1970-01-01T00:00:00Z"), Ok(0));
+        assert_eq!(
+            parse_date_time("2024-02-29T12:30:15.250Z"),
+            Ok(1709209815250)
+        );
+        assert_eq!(
+            parse_date_time("2024-02-29T13:30:15.250+01:00"),
+            Ok(1709209815250)
+        );
+        assert!(parse_date_time("2024-13-01T00:00:00Z").is_err());
+        assert!(parse_date_time("yesterday").is_err());
+    }
+}
@@ -0,0 +1,948 @@
+// DVB subtitles (ETSI EN 300 743) of broadcast and TS streams: bitmaps
+// described by segments (page composition, region composition, CLUT, object
+// data, display definition) carried in PES packets. Every display set is
+// decoded to RGBA bitmaps placed on the display, and appended as IMSC image
+// divisions so they are scheduled and rendered like the images of a TTML
+// document. A display set is shown until its page time-out or the next one.
+use super::convert::format_clock_time;
+use super::png::{encode_base64, encode_png};
+use super::Subtitles;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+const PAGE_COMPOSITION: u8 = 0x10;
+const REGION_COMPOSITION: u8 = 0x11;
+const CLUT_DEFINITION: u8 = 0x12;
+const OBJECT_DATA: u8 = 0x13;
+const DISPLAY_DEFINITION: u8 = 0x14;
+const END_OF_DISPLAY_SET: u8 = 0x80;
+
+/// Largest display width and height of a display definition segment.
+const MAX_DISPLAY_SIZE: usize = 4096;
+
+/// Page states of the page composition segment.
+const ACQUISITION_POINT: u8 = 1;
+const MODE_CHANGE: u8 = 2;
+
+/// Pixel codes of the 2, 4 and 8-bit CLUTs, as RGBA.
+#[derive(Clone)]
+struct Clut {
+    version: Option<u8>,
+    two: [[u8; 4]; 4],
+    four: [[u8; 4]; 16],
+    eight: [[u8; 4]; 256],
+}
+
+impl Clut {
+    /// The default CLUTs of the specification (section 10).
+    fn new() -> Self {
+        let mut four = [[0; 4]; 16];
+        for (code, entry) in four.iter_mut().enumerate().skip(1) {
+            let level = if code & 8 == 0 { 255 } else { 127 };
+            let channel = |bit: usize| if code & bit != 0 { level } else { 0 };
+            *entry = [channel(1), channel(2), channel(4), 255];
+        }
+        let mut eight = [[0; 4]; 256];
+        for (code, entry) in eight.iter_mut().enumerate().skip(1) {
+            let bit = |mask: usize| (code & mask != 0) as u8;
+            let channel = |low: usize, high: usize, low_level: u8, high_level: u8| {
+                bit(low) * low_level + bit(high) * high_level
+            };
+            *entry = match (code & 0x80 != 0, code & 0x08 != 0) {
+                (false, false) if code & 0x70 == 0 => {
+                    [bit(0x01) * 255, bit(0x02) * 255, bit(0x04) * 255, 64]
+                }
+                (false, false) => [
+                    channel(0x01, 0x10, 0x55, 0xAA),
+                    channel(0x02, 0x20, 0x55, 0xAA),
+                    channel(0x04, 0x40, 0x55, 0xAA),
+                    255,
+                ],
+                (false, true) => [
+                    channel(0x01, 0x10, 0x55, 0xAA),
+                    channel(0x02, 0x20, 0x55, 0xAA),
+                    channel(0x04, 0x40, 0x55, 0xAA),
+                    127,
+                ],
+                (true, false) => [
+                    0x7F + channel(0x01, 0x10, 0x2B, 0x55),
+                    0x7F + channel(0x02, 0x20, 0x2B, 0x55),
+                    0x7F + channel(0x04, 0x40, 0x2B, 0x55),
+                    255,
+                ],
+                (true, true) => [
+                    channel(0x01, 0x10, 0x2B, 0x55),
+                    channel(0x02, 0x20, 0x2B, 0x55),
+                    channel(0x04, 0x40, 0x2B, 0x55),
+                    255,
+                ],
+            };
+        }
+        Clut {
+            version: None,
+            two: [
+                [0, 0, 0, 0],
+                [255, 255, 255, 255],
+                [0, 0, 0, 255],
+                [127, 127, 127, 255],
+            ],
+            four,
+            eight,
+        }
+    }
+
+    fn get_color(&self, depth: u8, code: u8) -> [u8; 4] {
+        match depth {
+            2 => self.two[code as usize & 3],
+            4 => self.four[code as usize & 15],
+            _ => self.eight[code as usize],
+        }
+    }
+}
+
+/// RGBA of a Y, Cr, Cb, T (transparency) entry, ITU-R BT.601. A Y of 0 is
+/// fully transparent.
+fn to_rgba(y: u8, cr: u8, cb: u8, t: u8) -> [u8; 4] {
+    if y == 0 {
+        return [0, 0, 0, 0];
+    }
+    let (y, cr, cb) = (y as f32, cr as f32 - 128.0, cb as f32 - 128.0);
+    let clamp = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+    [
+        clamp(y + 1.402 * cr),
+        clamp(y - 0.344136 * cb - 0.714136 * cr),
+        clamp(y + 1.772 * cb),
+        255 - t,
+    ]
+}
+
+struct RegionObject {
+    object_id: u16,
+    x: usize,
+    y: usize,
+}
+
+struct Region {
+    version: u8,
+    width: usize,
+    height: usize,
+    /// Bits per pixel: 2, 4 or 8.
+    depth: u8,
+    clut_id: u8,
+    /// Pixel codes, `width * height`.
+    pixels: Vec<u8>,
+    objects: Vec<RegionObject>,
+}
+
+struct PageRegion {
+    region_id: u8,
+    x: usize,
+    y: usize,
+}
+
+/// A region of a display set, positioned on the display.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DvbBitmap {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    /// `width * height` pixels of 4 bytes.
+    pub rgba: Vec<u8>,
+}
+
+/// The page shown from `begin` for at most `timeout` milliseconds. No
+/// bitmaps clears the screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DvbDisplaySet {
+    pub begin: i64,
+    pub timeout: i64,
+    pub display_width: usize,
+    pub display_height: usize,
+    pub bitmaps: Vec<DvbBitmap>,
+}
+
+/// Pixel code maps of the object data, from the code depth to the region
+/// depth.
+struct MapTables {
+    two_to_four: [u8; 4],
+    two_to_eight: [u8; 4],
+    four_to_eight: [u8; 16],
+}
+
+impl MapTables {
+    fn new() -> Self {
+        let mut four_to_eight = [0; 16];
+        for (code, entry) in four_to_eight.iter_mut().enumerate() {
+            *entry = code as u8 * 0x11;
+        }
+        MapTables {
+            two_to_four: [0x0, 0x7, 0x8, 0xF],
+            two_to_eight: [0x00, 0x77, 0x88, 0xFF],
+            four_to_eight,
+        }
+    }
+
+    fn map(&self, code: u8, code_depth: u8, region_depth: u8) -> u8 {
+        match (code_depth, region_depth) {
+            (2, 4) => self.two_to_four[code as usize],
+            (2, 8) => self.two_to_eight[code as usize],
+            (4, 8) => self.four_to_eight[code as usize],
+            _ if code_depth > region_depth => code >> (code_depth - region_depth),
+            _ => code,
+        }
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    /// Past the end the data reads as zeros, which ends the pixel strings.
+    fn read(&mut self, bits: usize) -> u8 {
+        let mut value = 0;
+        for _ in 0..bits {
+            let byte = self.data.get(self.position / 8).copied().unwrap_or(0);
+            value = (value << 1) | ((byte >> (7 - self.position % 8)) & 1);
+            self.position += 1;
+        }
+        value
+    }
+
+    fn bytes_read(&self) -> usize {
+        self.position.div_ceil(8)
+    }
+}
+
+/// Runs of pixel codes of a 2, 4 or 8-bit pixel code string, until its end
+/// code. Returns the runs and the bytes read.
+fn read_pixel_string(data: &[u8], depth: u8) -> (Vec<(u8, usize)>, usize) {
+    let mut reader = BitReader { data, position: 0 };
+    let mut runs = Vec::new();
+    loop {
+        let code = reader.read(depth as usize);
+        if code != 0 {
+            runs.push((code, 1));
+            continue;
+        }
+        let run = match depth {
+            2 => {
+                if reader.read(1) == 1 {
+                    Some((3 + reader.read(3) as usize, reader.read(2)))
+                } else if reader.read(1) == 1 {
+                    Some((1, 0))
+                } else {
+                    match reader.read(2) {
+                        0 => None,
+                        1 => Some((2, 0)),
+                        2 => Some((12 + reader.read(4) as usize, reader.read(2))),
+                        _ => Some((29 + reader.read(8) as usize, reader.read(2))),
+                    }
+                }
+            }
+            4 => {
+                if reader.read(1) == 0 {
+                    match reader.read(3) {
+                        0 => None,
+                        length => Some((2 + length as usize, 0)),
+                    }
+                } else if reader.read(1) == 0 {
+                    Some((4 + reader.read(2) as usize, reader.read(4)))
+                } else {
+                    match reader.read(2) {
+                        0 => Some((1, 0)),
+                        1 => Some((2, 0)),
+                        2 => Some((9 + reader.read(4) as usize, reader.read(4))),
+                        _ => Some((25 + reader.read(8) as usize, reader.read(4))),
+                    }
+                }
+            }
+            _ => {
+                if reader.read(1) == 0 {
+                    match reader.read(7) {
+                        0 => None,
+                        length => Some((length as usize, 0)),
+                    }
+                } else {
+                    Some((reader.read(7) as usize, reader.read(8)))
+                }
+            }
+        };
+        match run {
+            Some((length, code)) => runs.push((code, length)),
+            None => return (runs, reader.bytes_read()),
+        }
+    }
+}
+
+impl Region {
+    /// Draws the pixel data sub-blocks of one field of an object, from line
+    /// `y` every other line.
+    fn draw_field(&mut self, data: &[u8], x: usize, y: usize, non_modifying: bool) {
+        let mut maps = MapTables::new();
+        let (mut column, mut line) = (x, y);
+        let mut position = 0;
+        while let Some(kind) = data.get(position) {
+            position += 1;
+            let rest = data.get(position..).unwrap_or_default();
+            match kind {
+                0x10..=0x12 => {
+                    let code_depth = 2 << (kind - 0x10);
+                    let (runs, length) = read_pixel_string(rest, code_depth);
+                    position += length;
+                    for (code, run) in runs {
+                        // With the non-modifying colour flag, code 1 leaves the
+                        // region unchanged.
+                        if !(non_modifying && code == 1) && line < self.height {
+                            let value = maps.map(code, code_depth, self.depth);
+                            let start = line * self.width;
+                            for pixel in column.min(self.width)..(column + run).min(self.width) {
+                                self.pixels[start + pixel] = value;
+                            }
+                        }
+                        column += run;
+                    }
+                }
+                0x20 => {
+                    let mut reader = BitReader {
+                        data: rest,
+                        position: 0,
+                    };
+                    for entry in maps.two_to_four.iter_mut() {
+                        *entry = reader.read(4);
+                    }
+                    position += 2;
+                }
+                0x21 => {
+                    for (entry, value) in maps.two_to_eight.iter_mut().zip(rest) {
+                        *entry = *value;
+                    }
+                    position += 4;
+                }
+                0x22 => {
+                    for (entry, value) in maps.four_to_eight.iter_mut().zip(rest) {
+                        *entry = *value;
+                    }
+                    position += 16;
+                }
+                0xF0 => {
+                    column = x;
+                    line += 2;
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn fill(&mut self, code: u8) {
+        self.pixels.iter_mut().for_each(|pixel| *pixel = code);
+    }
+}
+
+fn read_u16(data: &[u8], index: usize) -> usize {
+    match data.get(index..index + 2) {
+        Some(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]) as usize,
+        None => 0,
+    }
+}
+
+/// Decoder of the DVB subtitle PES packets of a stream.
+pub struct DvbDecoder {
+    /// Composition and ancillary pages of the service, from the subtitling
+    /// descriptor. Without them every page is decoded.
+    pages: Option<(u16, Option<u16>)>,
+    display_width: usize,
+    display_height: usize,
+    /// Top left corner of the display window, where the regions are placed.
+    window: (usize, usize),
+    page_version: Option<u8>,
+    page_timeout: i64,
+    page_regions: Vec<PageRegion>,
+    regions: HashMap<u8, Region>,
+    cluts: HashMap<u8, Clut>,
+    /// Time of the page being composed, until its display set ends.
+    pending: Option<i64>,
+}
+
+impl Default for DvbDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DvbDecoder {
+    pub fn new() -> Self {
+        DvbDecoder {
+            pages: None,
+            // Without display definition segment the display is SD.
+            display_width: 720,
+            display_height: 576,
+            window: (0, 0),
+            page_version: None,
+            page_timeout: 0,
+            page_regions: Vec::new(),
+            regions: HashMap::new(),
+            cluts: HashMap::new(),
+            pending: None,
+        }
+    }
+
+    pub fn set_pages(&mut self, composition_page_id: u16, ancillary_page_id: Option<u16>) {
+        self.pages = Some((composition_page_id, ancillary_page_id));
+    }
+
+    /// Decodes the payload of a PES packet with presentation time `ms`,
+    /// returning the display sets it completes.
+    pub fn decode_pes(&mut self, data: &[u8], ms: i64) -> Result<Vec<DvbDisplaySet>, String> {
+        if data.len() < 2 || data[0] != 0x20 || data[1] != 0x00 {
+            return Err("Not a DVB subtitle PES packet".to_string());
+        }
+        let mut display_sets = Vec::new();
+        let mut position = 2;
+        while data.get(position) == Some(&0x0F) {
+            let header = data
+                .get(position..position + 6)
+                .ok_or("Truncated DVB subtitle segment")?;
+            let kind = header[1];
+            let page_id = read_u16(header, 2) as u16;
+            let length = read_u16(header, 4);
+            let segment = data
+                .get(position + 6..position + 6 + length)
+                .ok_or("Truncated DVB subtitle segment")?;
+            position += 6 + length;
+            let selected = match self.pages {
+                Some((composition, ancillary)) => {
+                    page_id == composition || Some(page_id) == ancillary
+                }
+                None => true,
+            };
+            if !selected {
+                continue;
+            }
+            match kind {
+                PAGE_COMPOSITION => self.read_page_composition(segment, ms),
+                REGION_COMPOSITION => self.read_region_composition(segment)?,
+                CLUT_DEFINITION => self.read_clut_definition(segment),
+                OBJECT_DATA => self.read_object_data(segment),
+                DISPLAY_DEFINITION => self.read_display_definition(segment)?,
+                END_OF_DISPLAY_SET => display_sets.extend(self.end_display_set()),
+                _ => (),
+            }
+        }
+        // Some encoders do not send the end of display set segment.
+        display_sets.extend(self.end_display_set());
+        Ok(display_sets)
+    }
+
+    fn read_page_composition(&mut self, data: &[u8], ms: i64) {
+        if data.len() < 2 {
+            return;
+        }
+        let version = data[1] >> 4;
+        let state = (data[1] >> 2) & 3;
+        if state == ACQUISITION_POINT || state == MODE_CHANGE {
+            // A new epoch: regions and CLUTs are defined again.
+            self.regions.clear();
+            self.cluts.clear();
+        } else if self.page_version == Some(version) {
+            return;
+        }
+        self.page_version = Some(version);
+        self.page_timeout = data[0] as i64 * 1000;
+        self.page_regions = data[2..]
+            .chunks_exact(6)
+            .map(|entry| PageRegion {
+                region_id: entry[0],
+                x: read_u16(entry, 2),
+                y: read_u16(entry, 4),
+            })
+            .collect();
+        self.pending = Some(ms);
+    }
+
+    fn read_region_composition(&mut self, data: &[u8]) -> Result<(), String> {
+        if data.len() < 10 {
+            return Ok(());
+        }
+        let region_id = data[0];
+        let version = data[1] >> 4;
+        let fill = data[1] & 0x08 != 0;
+        let width = read_u16(data, 2);
+        let height = read_u16(data, 4);
+        // The pixels of the region are allocated, so it must fit in the display.
+        if width == 0 || height == 0 || width > self.display_width || height > self.display_height {
+            return Err(format!(
+                "DVB region of {}x{} outside the display of {}x{}",
+                width, height, self.display_width, self.display_height
+            ));
+        }
+        let depth = match (data[6] >> 2) & 7 {
+            1 => 2,
+            2 => 4,
+            _ => 8,
+        };
+        if self
+            .regions
+            .get(&region_id)
+            .is_some_and(|region| region.version == version)
+        {
+            return Ok(());
+        }
+        let mut objects = Vec::new();
+        let mut position = 10;
+        while let Some(entry) = data.get(position..position + 6) {
+            let object_type = entry[2] >> 6;
+            objects.push(RegionObject {
+                object_id: read_u16(entry, 0) as u16,
+                x: read_u16(entry, 2) & 0xFFF,
+                y: read_u16(entry, 4) & 0xFFF,
+            });
+            // Character objects carry their foreground and background codes.
+            position += if object_type == 1 || object_type == 2 {
+                8
+            } else {
+                6
+            };
+        }
+        let region = self.regions.entry(region_id).or_insert_with(|| Region {
+            version,
+            width,
+            height,
+            depth,
+            clut_id: data[7],
+            pixels: vec![0; width * height],
+            objects: Vec::new(),
+        });
+        if region.width != width || region.height != height || region.depth != depth {
+            region.width = width;
+            region.height = height;
+            region.depth = depth;
+            region.pixels = vec![0; width * height];
+        }
+        region.version = version;
+        region.clut_id = data[7];
+        region.objects = objects;
+        if fill {
+            let code = match depth {
+                2 => (data[9] >> 2) & 3,
+                4 => data[9] >> 4,
+                _ => data[8],
+            };
+            region.fill(code);
+        }
+        Ok(())
+    }
+
+    fn read_clut_definition(&mut self, data: &[u8]) {
+        if data.len() < 2 {
+            return;
+        }
+        let version = data[1] >> 4;
+        let clut = self.cluts.entry(data[0]).or_insert_with(Clut::new);
+        if clut.version == Some(version) {
+            return;
+        }
+        clut.version = Some(version);
+        let mut position = 2;
+        while let Some(entry) = data.get(position..position + 2) {
+            let (id, flags) = (entry[0], entry[1]);
+            let color = if flags & 1 == 1 {
+                match data.get(position + 2..position + 6) {
+                    Some(values) => to_rgba(values[0], values[1], values[2], values[3]),
+                    None => return,
+                }
+            } else {
+                let value = read_u16(data, position + 2);
+                if position + 4 > data.len() {
+                    return;
+                }
+                to_rgba(
+                    ((value >> 10) << 2) as u8,
+                    (((value >> 6) & 15) << 4) as u8,
+                    (((value >> 2) & 15) << 4) as u8,
+                    ((value & 3) << 6) as u8,
+                )
+            };
+            position += if flags & 1 == 1 { 6 } else { 4 };
+            if flags & 0x80 != 0 && id < 4 {
+                clut.two[id as usize] = color;
+            }
+            if flags & 0x40 != 0 && id < 16 {
+                clut.four[id as usize] = color;
+            }
+            if flags & 0x20 != 0 {
+                clut.eight[id as usize] = color;
+            }
+        }
+    }
+
+    fn read_object_data(&mut self, data: &[u8]) {
+        if data.len() < 7 {
+            return;
+        }
+        let object_id = read_u16(data, 0) as u16;
+        let coding_method = (data[2] >> 2) & 3;
+        let non_modifying = data[2] & 2 != 0;
+        // Objects coded as character strings are not supported.
+        if coding_method != 0 {
+            return;
+        }
+        let top_length = read_u16(data, 3);
+        let bottom_length = read_u16(data, 5);
+        let top = data.get(7..7 + top_length).unwrap_or_default();
+        let bottom = match bottom_length {
+            // Without bottom field the top one is repeated.
+            0 => top,
+            _ => data
+                .get(7 + top_length..7 + top_length + bottom_length)
+                .unwrap_or_default(),
+        };
+        for region in self.regions.values_mut() {
+            let positions: Vec<(usize, usize)> = region
+                .objects
+                .iter()
+                .filter(|object| object.object_id == object_id)
+                .map(|object| (object.x, object.y))
+                .collect();
+            for (x, y) in positions {
+                region.draw_field(top, x, y, non_modifying);
+                region.draw_field(bottom, x, y + 1, non_modifying);
+            }
+        }
+    }
+
+    fn read_display_definition(&mut self, data: &[u8]) -> Result<(), String> {
+        if data.len() < 5 {
+            return Ok(());
+        }
+        let width = read_u16(data, 1) + 1;
+        let height = read_u16(data, 3) + 1;
+        if width > MAX_DISPLAY_SIZE || height > MAX_DISPLAY_SIZE {
+            return Err(format!("Invalid DVB display size: {}x{}", width, height));
+        }
+        self.display_width = width;
+        self.display_height = height;
+        self.window = if data[0] & 0x08 != 0 && data.len() >= 13 {
+            (read_u16(data, 5), read_u16(data, 9))
+        } else {
+            (0, 0)
+        };
+        Ok(())
+    }
+
+    /// The composed page, if a page composition was received since the last
+    /// display set. Fully transparent regions are left out.
+    fn end_display_set(&mut self) -> Option<DvbDisplaySet> {
+        let begin = self.pending.take()?;
+        let default_clut = Clut::new();
+        let bitmaps = self
+            .page_regions
+            .iter()
+            .filter_map(|page_region| {
+                let region = self.regions.get(&page_region.region_id)?;
+                let clut = self.cluts.get(&region.clut_id).unwrap_or(&default_clut);
+                let rgba: Vec<u8> = region
+                    .pixels
+                    .iter()
+                    .flat_map(|code| clut.get_color(region.depth, *code))
+                    .collect();
+                if rgba.chunks(4).all(|pixel| pixel[3] == 0) {
+                    return None;
+                }
+                Some(DvbBitmap {
+                    x: self.window.0 + page_region.x,
+                    y: self.window.1 + page_region.y,
+                    width: region.width,
+                    height: region.height,
+                    rgba,
+                })
+            })
+            .collect();
+        Some(DvbDisplaySet {
+            begin,
+            timeout: self.page_timeout,
+            display_width: self.display_width,
+            display_height: self.display_height,
+            bitmaps,
+        })
+    }
+}
+
+/// Percentage of the display with 4 decimals, a tenth of a pixel on UHD.
+fn format_percent(value: usize, total: usize) -> String {
+    let percent = format!("{:.4}", value as f64 * 100.0 / total as f64);
+    format!("{}%", percent.trim_end_matches('0').trim_end_matches('.'))
+}
+
+/// IMSC image profile document of a display set: a region and a division
+/// with the PNG for every bitmap.
+fn to_ttml(display_set: &DvbDisplaySet) -> String {
+    let (width, height) = (display_set.display_width, display_set.display_height);
+    let mut regions = String::new();
+    let mut divs = String::new();
+    for (index, bitmap) in display_set.bitmaps.iter().enumerate() {
+        let region_id = format!(
+            "dvb-{}x{}-{}-{}-{}-{}",
+            width, height, bitmap.x, bitmap.y, bitmap.width, bitmap.height
+        );
+        regions.push_str(&format!(
+            "      <tt:region xml:id=\"{}\" tts:origin=\"{} {}\" tts:extent=\"{} {}\"/>\n",
+            region_id,
+            format_percent(bitmap.x, width),
+            format_percent(bitmap.y, height),
+            format_percent(bitmap.width, width),
+            format_percent(bitmap.height, height)
+        ));
+        divs.push_str(&format!(
+            "    <tt:div xml:id=\"dvb-{}-{}\" region=\"{}\" begin=\"{}\" end=\"{}\"><smpte:image imageType=\"PNG\" encoding=\"Base64\">{}</smpte:image></tt:div>\n",
+            display_set.begin,
+            index,
+            region_id,
+            format_clock_time(display_set.begin, '.'),
+            format_clock_time(display_set.begin + display_set.timeout, '.'),
+            encode_base64(&encode_png(bitmap.width, bitmap.height, &bitmap.rgba))
+        ));
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" xmlns:tts="http://www.w3.org/ns/ttml#styling" xmlns:smpte="http://www.smpte-ra.org/schemas/2052-1/2010/smpte-tt" ttp:timeBase="media">
+  <tt:head>
+    <tt:layout>
+{}    </tt:layout>
+  </tt:head>
+  <tt:body>
+{}  </tt:body>
+</tt:tt>
+"#,
+        regions, divs
+    )
+}
+
+impl Subtitles {
+    /// Decodes only the DVB subtitle service of the composition and
+    /// ancillary pages of the subtitling descriptor of the PMT.
+    pub fn set_dvb_pages(&mut self, composition_page_id: u16, ancillary_page_id: Option<u16>) {
+        self.dvb_decoder
+            .get_or_insert_with(DvbDecoder::new)
+            .set_pages(composition_page_id, ancillary_page_id);
+    }
+
+    /// Appends the display sets of the payload of a DVB subtitle PES packet
+    /// whose presentation time is `ms`.
+    pub fn append_dvb_pes(&mut self, data: &[u8], ms: i64) -> Result<(), String> {
+        let display_sets = self
+            .dvb_decoder
+            .get_or_insert_with(DvbDecoder::new)
+            .decode_pes(data, ms)?;
+        for display_set in display_sets.iter() {
+            self.replace_stream_cues("dvb-", display_set.begin);
+            if !display_set.bitmaps.is_empty() {
+                self.append_document(&to_ttml(display_set))?;
+                for (index, bitmap) in display_set.bitmaps.iter().enumerate() {
+                    self.dvb_bitmaps.insert(
+                        format!("dvb-{}-{}", display_set.begin, index),
+                        bitmap.clone(),
+                    );
+                }
+            }
+        }
+        // The bitmaps of the evicted and replaced cues are dropped.
+        let ids: HashSet<&String> = self
+            .tt
+            .iter()
+            .flat_map(|tt| tt.body.divs.iter())
+            .filter_map(|div| div.id.as_ref())
+            .collect();
+        self.dvb_bitmaps.retain(|id, _| ids.contains(id));
+        if !display_sets.is_empty() {
+            self.resync_visible_subtitles();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(kind: u8, page_id: u16, data: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0x0F, kind];
+        segment.extend_from_slice(&page_id.to_be_bytes());
+        segment.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        segment.extend_from_slice(data);
+        segment
+    }
+
+    /// A display set of a 4x2 region with a 2-bit object at (1, 0): a white
+    /// pixel, a run of 2 black pixels, then a pixel of the redefined CLUT entry 1
+    /// on the bottom field.
+    fn pes(page_version: u8, page_state: u8, timeout: u8) -> Vec<u8> {
+        let mut data = vec![0x20, 0x00];
+        data.extend(segment(
+            DISPLAY_DEFINITION,
+            1,
+            &[0x00, 0x02, 0xCF, 0x02, 0x3F],
+        ));
+        data.extend(segment(
+            PAGE_COMPOSITION,
+            1,
+            &[
+                timeout,
+                (page_version << 4) | (page_state << 2),
+                0,
+                0,
+                0,
+                100,
+                0,
+                50,
+            ],
+        ));
+        // Region 0, filled with code 0, 4x2, 4-bit, CLUT 0, object 7.
+        data.extend(segment(
+            REGION_COMPOSITION,
+            1,
+            &[0, 0x08, 0, 4, 0, 2, 2 << 2, 0, 0, 0, 0, 7, 0, 1, 0, 0],
+        ));
+        // CLUT 0: 4-bit entry 1 redefined as an opaque red.
+        data.extend(segment(
+            CLUT_DEFINITION,
+            1,
+            &[0, 0x00, 1, 0x41, 81, 240, 90, 0],
+        ));
+        // Top field: 2-bit string "01", "00 1 000 10" (3 pixels of code 2),
+        // end "00 0 0 00". Bottom field: 4-bit string "0001" and its end.
+        let top = [0x10, 0b0100_1000, 0b1000_0000, 0x00];
+        let bottom = [0x11, 0b0001_0000, 0b0000_0000];
+        let mut object = vec![0, 7, 0x00];
+        object.extend_from_slice(&(top.len() as u16).to_be_bytes());
+        object.extend_from_slice(&(bottom.len() as u16).to_be_bytes());
+        object.extend_from_slice(&top);
+        object.extend_from_slice(&bottom);
+        data.extend(segment(OBJECT_DATA, 1, &object));
+        data.extend(segment(END_OF_DISPLAY_SET, 1, &[]));
+        data.push(0xFF);
+        data
+    }
+
+    #[test]
+    fn decodes_display_sets() {
+        let mut decoder = DvbDecoder::new();
+        let display_sets = decoder.decode_pes(&pes(0, MODE_CHANGE, 5), 1000).unwrap();
+        assert_eq!(display_sets.len(), 1);
+        let display_set = &display_sets[0];
+        assert_eq!(
+            (
+                display_set.begin,
+                display_set.timeout,
+                display_set.display_width,
+                display_set.display_height
+            ),
+            (1000, 5000, 720, 576)
+        );
+        let bitmap = &display_set.bitmaps[0];
+        assert_eq!(
+            (bitmap.x, bitmap.y, bitmap.width, bitmap.height),
+            (100, 50, 4, 2)
+        );
+        let pixels: Vec<&[u8]> = bitmap.rgba.chunks(4).collect();
+        // 2-bit code 1 is mapped to the 4-bit code 7, white; code 2 to 8, black.
+        assert_eq!(pixels[0], [0, 0, 0, 0]);
+        assert_eq!(pixels[1], [255, 255, 255, 255]);
+        assert_eq!(pixels[2], [0, 0, 0, 255]);
+        assert_eq!(pixels[3], [0, 0, 0, 255]);
+        assert_eq!(pixels[4], [0, 0, 0, 0]);
+        assert_eq!(pixels[5], [238, 14, 14, 255]);
+        // The same page version is not shown again.
+        assert!(decoder.decode_pes(&pes(0, 0, 5), 2000).unwrap().is_empty());
+        // Other pages are skipped.
+        decoder.set_pages(2, None);
+        assert!(decoder
+            .decode_pes(&pes(1, MODE_CHANGE, 5), 2000)
+            .unwrap()
+            .is_empty());
+        assert!(decoder.decode_pes(&[0x10, 0x00], 0).is_err());
+    }
+
+    #[test]
+    fn uses_the_default_cluts() {
+        let clut = Clut::new();
+        assert_eq!(clut.get_color(2, 3), [127, 127, 127, 255]);
+        assert_eq!(clut.get_color(4, 9), [127, 0, 0, 255]);
+        assert_eq!(clut.get_color(8, 0x01), [255, 0, 0, 64]);
+        assert_eq!(clut.get_color(8, 0x18), [0xAA, 0, 0, 127]);
+        assert_eq!(clut.get_color(8, 0xFF), [0x80, 0x80, 0x80, 255]);
+    }
+
+    #[test]
+    fn shows_display_sets_until_the_next_one() {
+        let mut subtitles = Subtitles::new();
+        subtitles.set_element_size(1440, 1152);
+        subtitles
+            .append_dvb_pes(&pes(0, MODE_CHANGE, 10), 1000)
+            .unwrap();
+        subtitles.update_subtitles_for_ms(1500);
+        let shown = subtitles.get_subtitles_at(1500);
+        assert_eq!(shown.len(), 1);
+        let image = shown[0].image.as_ref().unwrap();
+        assert!(image.src.starts_with("data:image/png;base64,iVBORw0KGgo"));
+        // The decoded pixels for a canvas.
+        let bitmap = image.bitmap.as_ref().unwrap();
+        assert_eq!(
+            (bitmap.x, bitmap.y, bitmap.width, bitmap.height),
+            (100, 50, 4, 2)
+        );
+        assert_eq!(bitmap.rgba[4..8], [255, 255, 255, 255]);
+        let region = shown[0].region.as_ref().unwrap();
+        let geometry = &region.geometry;
+        assert_eq!(
+            (geometry.left.round(), geometry.top.round()),
+            (200.0, 100.0)
+        );
+        subtitles.append_dvb_pes(&pes(1, 0, 10), 3000).unwrap();
+        let ids = |subtitles: &mut Subtitles, ms: i64| -> Vec<String> {
+            subtitles
+                .get_subtitles_at(ms)
+                .into_iter()
+                .map(|subtitle| subtitle.id)
+                .collect()
+        };
+        assert_eq!(ids(&mut subtitles, 2999), ["dvb-1000-0"]);
+        assert_eq!(ids(&mut subtitles, 3000), ["dvb-3000-0"]);
+        assert_eq!(ids(&mut subtitles, 12999), ["dvb-3000-0"]);
+        assert!(ids(&mut subtitles, 13000).is_empty());
+        // The bitmaps of the evicted cues are dropped.
+        subtitles.set_back_buffer(0).unwrap();
+        subtitles.update_subtitles_for_ms(5000);
+        subtitles.append_dvb_pes(&pes(2, 0, 10), 5000).unwrap();
+        let mut bitmap_ids: Vec<&String> = subtitles.dvb_bitmaps.keys().collect();
+        bitmap_ids.sort();
+        assert_eq!(bitmap_ids, ["dvb-3000-0", "dvb-5000-0"]);
+    }
+
+    #[test]
+    fn rejects_regions_larger_than_the_display() {
+        let mut data = vec![0x20, 0x00];
+        data.extend(segment(
+            PAGE_COMPOSITION,
+            1,
+            &[5, MODE_CHANGE << 2, 0, 0, 0, 0, 0, 0],
+        ));
+        // Region 0 of 65535x65535.
+        data.extend(segment(
+            REGION_COMPOSITION,
+            1,
+            &[0, 0x08, 0xFF, 0xFF, 0xFF, 0xFF, 2 << 2, 0, 0, 0],
+        ));
+        assert!(DvbDecoder::new().decode_pes(&data, 0).is_err());
+        let mut data = vec![0x20, 0x00];
+        data.extend(segment(
+            DISPLAY_DEFINITION,
+            1,
+            &[0x00, 0xFF, 0xFF, 0xFF, 0xFF],
+        ));
+        assert!(DvbDecoder::new().decode_pes(&data, 0).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+// Value of tts:textEmphasis: "none" | [<style> || <color>] [<position>], where
+// <style> is "auto", [filled | open] || [circle | dot | sesame] or a quoted
+// character, and <position> is "before", "after" or "outside". The colour
+// defaults to the colour of the text, resolved by `Subtitles`.
+use super::color::Color;
+use super::outline::split_outside_parentheses;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEmphasisValue {
+    /// `text-emphasis-style` ready for CSS, e.g. "filled circle" or "\"*\"".
+    pub style: String,
+    pub color: Option<Color>,
+    pub position: String,
+}
+
+/// `Ok(None)` is an explicit "none".
+pub fn parse_text_emphasis(value: &str) -> Result<Option<TextEmphasisValue>, String> {
+    if value.trim() == "none" {
+        return Ok(None);
+    }
+    let mut fill: Option<&str> = None;
+    let mut shape: Option<&str> = None;
+    let mut mark: Option<String> = None;
+    let mut color: Option<Color> = None;
+    let mut position: Option<&str> = None;
+    for token in split_outside_parentheses(value, char::is_whitespace) {
+        match token {
+            "filled" | "open" if fill.is_none() => fill = Some(token),
+            "circle" | "dot" | "sesame" if shape.is_none() => shape = Some(token),
+            // Horizontal text only, where "auto" is a filled circle.
+            "auto" if fill.is_none() && shape.is_none() => {
+                fill = Some("filled");
+                shape = Some("circle");
+            }
+            "before" | "after" | "outside" if position.is_none() => position = Some(token),
+            token if token.starts_with('"') || token.starts_with('\'') => {
+                let character =
+                    token.trim_matches(|character| character == '"' || character == '\'');
+                // Quotes and escapes inside the mark would end the CSS string.
+                if character.is_empty() || character.contains(['"', '\'', '\\']) {
+                    return Err(format!("Invalid text emphasis: {}", value));
+                }
+                mark = Some(format!("\"{}\"", character));
+            }
+            token if color.is_none() => {
+                color = Some(
+                    Color::parse(token).map_err(|_| format!("Invalid text emphasis: {}", value))?,
+                )
+            }
+            _ => return Err(format!("Invalid text emphasis: {}", value)),
+        }
+    }
+    let style = match mark {
+        Some(mark) => mark,
+        None => format!("{} {}", fill.unwrap_or("filled"), shape.unwrap_or("circle")),
+    };
+    Ok(Some(TextEmphasisValue {
+        style,
+        color,
+        position: position.unwrap_or("before").to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_text_emphasis() {
+        assert_eq!(parse_text_emphasis("none"), Ok(None));
+        let emphasis = parse_text_emphasis("open dot red after").unwrap().unwrap();
+        assert_eq!(emphasis.style, "open dot");
+        assert_eq!(emphasis.color, Some(Color::rgb(255, 0, 0)));
+        assert_eq!(emphasis.position, "after");
+        let emphasis = parse_text_emphasis("auto").unwrap().unwrap();
+        assert_eq!(emphasis.style, "filled circle");
+        assert_eq!(emphasis.position, "before");
+        let emphasis = parse_text_emphasis("'*' outside").unwrap().unwrap();
+        assert_eq!(emphasis.style, "\"*\"");
+    }
+
+    #[test]
+    fn rejects_invalid_text_emphasis() {
+        assert!(parse_text_emphasis("circle dot").is_err());
+        assert!(parse_text_emphasis("red blue").is_err());
+        assert!(parse_text_emphasis("''").is_err());
+        assert!(parse_text_emphasis("'x\";color:red'").is_err());
+    }
+}
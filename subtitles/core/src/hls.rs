@@ -0,0 +1,322 @@
+// Subtitles of HLS streams: the `EXT-X-MEDIA` renditions of type SUBTITLES of
+// a master playlist, the segments of their media playlists and the WebVTT
+// segments, placed on the media timeline with their `X-TIMESTAMP-MAP`.
+//
+// Segment times are milliseconds from the first segment of the playlist.
+use super::dates::parse_date_time;
+use super::ts::get_pts_ms;
+use super::url::resolve_url;
+use super::webvtt::{self, TimestampMap};
+use super::Subtitles;
+use serde::Serialize;
+
+/// A subtitle rendition of a master playlist.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlsRendition {
+    pub group_id: String,
+    pub name: String,
+    pub language: Option<String>,
+    pub default: bool,
+    pub autoselect: bool,
+    pub forced: bool,
+    /// e.g. "public.accessibility.describes-music-and-sound".
+    pub characteristics: Option<String>,
+    /// Media playlist, absent for renditions carried in the video.
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlsSegment {
+    pub sequence: u64,
+    pub url: String,
+    pub begin: i64,
+    pub duration: i64,
+    /// Increased by every `EXT-X-DISCONTINUITY`, the segments of another
+    /// discontinuity have their own timestamps.
+    pub discontinuity_sequence: u64,
+    /// Wall clock time of the start of the segment, milliseconds since the
+    /// Unix epoch, from the last `EXT-X-PROGRAM-DATE-TIME`.
+    pub program_date_time: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlsMediaPlaylist {
+    pub target_duration: i64,
+    pub media_sequence: u64,
+    /// `EXT-X-ENDLIST`: no more segments will be added, not live.
+    pub ended: bool,
+    pub segments: Vec<HlsSegment>,
+}
+
+/// Attributes of a tag, `KEY=VALUE,KEY="quoted, value"`.
+fn parse_attributes(list: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = list.trim();
+    while !rest.is_empty() {
+        let (key, value) = match rest.split_once('=') {
+            Some(split) => split,
+            None => break,
+        };
+        let (value, next) = if let Some(quoted) = value.strip_prefix('"') {
+            match quoted.split_once('"') {
+                Some((value, next)) => (value, next),
+                None => (quoted, ""),
+            }
+        } else {
+            match value.split_once(',') {
+                Some((value, next)) => (value, next),
+                None => (value, ""),
+            }
+        };
+        attributes.push((key.trim().to_string(), value.to_string()));
+        rest = next.trim_start_matches(',').trim_start();
+    }
+    attributes
+}
+
+fn get_attribute<'a>(attributes: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(attribute, _)| attribute == key)
+        .map(|(_, value)| value.as_str())
+}
+
+fn check_header(text: &str) -> Result<(), String> {
+    if text.trim_start_matches('\u{feff}').starts_with("#EXTM3U") {
+        Ok(())
+    } else {
+        Err("Invalid playlist: missing #EXTM3U header".to_string())
+    }
+}
+
+/// Subtitle renditions of a master playlist, `url` resolves their URIs.
+pub fn parse_master_playlist(text: &str, url: &str) -> Result<Vec<HlsRendition>, String> {
+    check_header(text)?;
+    let mut renditions = Vec::new();
+    for line in text.lines() {
+        let attributes = match line.trim().strip_prefix("#EXT-X-MEDIA:") {
+            Some(list) => parse_attributes(list),
+            None => continue,
+        };
+        if get_attribute(&attributes, "TYPE") != Some("SUBTITLES") {
+            continue;
+        }
+        let is_yes = |key: &str| get_attribute(&attributes, key) == Some("YES");
+        renditions.push(HlsRendition {
+            group_id: get_attribute(&attributes, "GROUP-ID")
+                .unwrap_or_default()
+                .to_string(),
+            name: get_attribute(&attributes, "NAME")
+                .unwrap_or_default()
+                .to_string(),
+            language: get_attribute(&attributes, "LANGUAGE").map(str::to_string),
+            default: is_yes("DEFAULT"),
+            autoselect: is_yes("AUTOSELECT"),
+            forced: is_yes("FORCED"),
+            characteristics: get_attribute(&attributes, "CHARACTERISTICS").map(str::to_string),
+            url: get_attribute(&attributes, "URI").map(|uri| resolve_url(url, uri)),
+        });
+    }
+    Ok(renditions)
+}
+
+fn parse_seconds(value: &str, tag: &str) -> Result<i64, String> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(|seconds| (seconds * 1000.0).round() as i64)
+        .ok_or_else(|| format!("Invalid {}: {}", tag, value))
+}
+
+fn parse_integer(value: &str, tag: &str) -> Result<u64, String> {
+    value
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| format!("Invalid {}: {}", tag, value))
+}
+
+/// Segments of a media playlist, `url` resolves their URIs.
+pub fn parse_media_playlist(text: &str, url: &str) -> Result<HlsMediaPlaylist, String> {
+    check_header(text)?;
+    let mut playlist = HlsMediaPlaylist {
+        target_duration: 0,
+        media_sequence: 0,
+        ended: false,
+        segments: Vec::new(),
+    };
+    let mut discontinuity_sequence = 0;
+    let mut begin = 0;
+    let mut duration: Option<i64> = None;
+    let mut program_date_time: Option<i64> = None;
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(value) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+            playlist.target_duration = parse_seconds(value, "EXT-X-TARGETDURATION")?;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+            playlist.media_sequence = parse_integer(value, "EXT-X-MEDIA-SEQUENCE")?;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-DISCONTINUITY-SEQUENCE:") {
+            discontinuity_sequence = parse_integer(value, "EXT-X-DISCONTINUITY-SEQUENCE")?;
+        } else if line == "#EXT-X-DISCONTINUITY" {
+            discontinuity_sequence += 1;
+            // The wall clock of the previous segments does not continue.
+            program_date_time = None;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-PROGRAM-DATE-TIME:") {
+            program_date_time = Some(parse_date_time(value)?);
+        } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+            let seconds = value.split(',').next().unwrap_or("");
+            duration = Some(parse_seconds(seconds, "EXTINF")?);
+        } else if line == "#EXT-X-ENDLIST" {
+            playlist.ended = true;
+        } else if !line.starts_with('#') {
+            let duration = duration
+                .take()
+                .ok_or_else(|| format!("Segment without EXTINF: {}", line))?;
+            playlist.segments.push(HlsSegment {
+                sequence: playlist.media_sequence + playlist.segments.len() as u64,
+                url: resolve_url(url, line),
+                begin,
+                duration,
+                discontinuity_sequence,
+                program_date_time,
+            });
+            begin += duration;
+            program_date_time = program_date_time.map(|date_time| date_time + duration);
+        }
+    }
+    Ok(playlist)
+}
+
+/// Milliseconds to add to the cue times of a segment with `timestamp_map`
+/// to place them on the media timeline, whose start has the presentation
+/// time `initial_pts`. Presentation times that wrapped around after the
+/// start are unwrapped.
+fn get_cue_offset(timestamp_map: TimestampMap, initial_pts: u64) -> i64 {
+    get_pts_ms(timestamp_map.mpegts, initial_pts) - timestamp_map.local
+}
+
+impl Subtitles {
+    /// Appends the cues of a WebVTT segment of an HLS subtitle playlist.
+    /// `initial_pts` is the MPEG-2 TS presentation time (90 kHz) of the start
+    /// of the media timeline, usually the one of the first video segment.
+    /// Segments without `X-TIMESTAMP-MAP` are already on the media timeline.
+    pub fn append_webvtt_segment(&mut self, text: &str, initial_pts: u64) -> Result<(), String> {
+        let mut file = webvtt::parse_webvtt(text)?;
+        if let Some(timestamp_map) = file.timestamp_map {
+            let offset = get_cue_offset(timestamp_map, initial_pts);
+            for cue in file.cues.iter_mut() {
+                cue.begin += offset;
+                cue.end += offset;
+            }
+        }
+        if file.cues.is_empty() {
+            return Ok(());
+        }
+        self.append_document(&webvtt::to_ttml(&file.cues))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ts::PTS_ROLLOVER;
+
+    #[test]
+    fn lists_the_subtitle_renditions() {
+        let master = r#"#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aud",NAME="Català",LANGUAGE="ca",URI="audio/ca.m3u8"
+#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID="subs",NAME="Català",LANGUAGE="ca",DEFAULT=YES,AUTOSELECT=YES,URI="subs/ca.m3u8"
+#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID="subs",NAME="Català (sords)",LANGUAGE="ca",CHARACTERISTICS="public.accessibility.transcribes-spoken-dialog,public.accessibility.describes-music-and-sound",URI="subs/ca-sdh.m3u8"
+#EXT-X-STREAM-INF:BANDWIDTH=2000000,AUDIO="aud",SUBTITLES="subs"
+video/720.m3u8
+"#;
+        let renditions =
+            parse_master_playlist(master, "https://cdn.example.com/hls/master.m3u8").unwrap();
+        assert_eq!(renditions.len(), 2);
+        assert_eq!(renditions[0].group_id, "subs");
+        assert!(renditions[0].default);
+        assert!(!renditions[1].default);
+        assert_eq!(
+            renditions[1].url.as_deref(),
+            Some("https://cdn.example.com/hls/subs/ca-sdh.m3u8")
+        );
+        assert_eq!(
+            renditions[1].characteristics.as_deref(),
+            Some("public.accessibility.transcribes-spoken-dialog,public.accessibility.describes-music-and-sound")
+        );
+        assert!(parse_master_playlist("video.m3u8", "").is_err());
+    }
+
+    #[test]
+    fn computes_the_segment_timing() {
+        let media = "#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXT-X-MEDIA-SEQUENCE:100
+#EXT-X-PROGRAM-DATE-TIME:2024-01-01T00:00:00.000Z
+#EXTINF:6.000,
+seg100.vtt
+#EXTINF:5.5,
+seg101.vtt
+#EXT-X-DISCONTINUITY
+#EXTINF:6,
+seg102.vtt
+#EXT-X-ENDLIST
+";
+        let playlist = parse_media_playlist(media, "https://cdn.example.com/subs/ca.m3u8").unwrap();
+        assert_eq!(playlist.target_duration, 6000);
+        assert!(playlist.ended);
+        let segments: Vec<(u64, i64, i64, u64, Option<i64>)> = playlist
+            .segments
+            .iter()
+            .map(|segment| {
+                (
+                    segment.sequence,
+                    segment.begin,
+                    segment.duration,
+                    segment.discontinuity_sequence,
+                    segment.program_date_time,
+                )
+            })
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (100, 0, 6000, 0, Some(1704067200000)),
+                (101, 6000, 5500, 0, Some(1704067206000)),
+                (102, 11500, 6000, 1, None),
+            ]
+        );
+        assert_eq!(
+            playlist.segments[2].url,
+            "https://cdn.example.com/subs/seg102.vtt"
+        );
+        assert!(parse_media_playlist("#EXTM3U\nseg.vtt", "").is_err());
+    }
+
+    #[test]
+    fn aligns_the_cues_with_the_timestamp_map() {
+        let segment = "WEBVTT
+X-TIMESTAMP-MAP=MPEGTS:1800000,LOCAL:00:00:10.000
+
+00:00:10.000 --> 00:00:12.000
+Hello
+";
+        let mut subtitles = Subtitles::new();
+        // The media starts at PTS 900000 (10s), the segment at 20s of PTS.
+        subtitles.append_webvtt_segment(segment, 900000).unwrap();
+        let timed_subtitles = subtitles.get_timed_subtitles();
+        assert_eq!(
+            (timed_subtitles[0].begin, timed_subtitles[0].end),
+            (10000, 12000)
+        );
+        // The presentation time wrapped around after the start.
+        let map = TimestampMap {
+            mpegts: 90000,
+            local: 0,
+        };
+        assert_eq!(get_cue_offset(map, PTS_ROLLOVER - 90000), 2000);
+    }
+}
@@ -0,0 +1,182 @@
+// IMSC image profile: the image of a timed division is embedded in the
+// document as base64 (`smpte:image`) or referenced by URL. Embedded images are
+// sent as data URLs, URLs are resolved by the caller (e.g. against the base URL
+// of the DASH segments).
+use super::render::RenderedImage;
+use super::{Div, Image, TT};
+
+/// Resolves the URL of an external image, `None` when it can not be loaded.
+pub type ImageResolver = Box<dyn Fn(&str) -> Option<String> + Send>;
+
+/// PNG is the only format of the profile, so it is the default.
+fn get_mime_type(image: &Image) -> String {
+    if let Some(mime_type) = image.mime_type.as_ref() {
+        return mime_type.clone();
+    }
+    match image.image_type.as_ref() {
+        Some(image_type) => format!("image/{}", image_type.to_lowercase()),
+        None => "image/png".to_string(),
+    }
+}
+
+fn find_image<'a>(tt: &'a TT, id: &str) -> Option<&'a Image> {
+    tt.head
+        .metadata
+        .iter()
+        .flat_map(|metadata| metadata.images.iter())
+        .chain(tt.body.divs.iter().flat_map(|div| div.images.iter()))
+        .find(|image| image.id.as_deref() == Some(id))
+}
+
+fn from_embedded(image: &Image) -> Result<RenderedImage, String> {
+    if let Some(encoding) = image.encoding.as_ref() {
+        if !encoding.eq_ignore_ascii_case("base64") {
+            return Err(format!("Unsupported image encoding: {}", encoding));
+        }
+    }
+    // Base64 data is usually split in lines.
+    let data: String = image
+        .data
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect();
+    if data.is_empty() {
+        return Err(format!(
+            "Empty image: {}",
+            image.id.as_deref().unwrap_or_default()
+        ));
+    }
+    let mime_type = get_mime_type(image);
+    Ok(RenderedImage {
+        src: format!("data:{};base64,{}", mime_type, data),
+        mime_type,
+        data: Some(data),
+        bitmap: None,
+    })
+}
+
+/// `#id` of an embedded image, a data URL or an external URL.
+fn from_reference(
+    tt: &TT,
+    reference: &str,
+    resolver: Option<&ImageResolver>,
+) -> Result<RenderedImage, String> {
+    if let Some(id) = reference.strip_prefix('#') {
+        let image = find_image(tt, id).ok_or_else(|| format!("Image not found: {}", reference))?;
+        return from_embedded(image);
+    }
+    if let Some(data_url) = reference.strip_prefix("data:") {
+        let (mime_type, data) = match data_url.split_once(";base64,") {
+            Some((mime_type, data)) => (mime_type.to_string(), Some(data.to_string())),
+            None => ("image/png".to_string(), None),
+        };
+        return Ok(RenderedImage {
+            src: reference.to_string(),
+            mime_type,
+            data,
+            bitmap: None,
+        });
+    }
+    // Without a resolver the browser loads the URL relative to the page.
+    let src = match resolver {
+        Some(resolver) => {
+            resolver(reference).ok_or_else(|| format!("Image not resolved: {}", reference))?
+        }
+        None => reference.to_string(),
+    };
+    Ok(RenderedImage {
+        src,
+        mime_type: "image/png".to_string(),
+        data: None,
+        bitmap: None,
+    })
+}
+
+/// URL of the image of a division that goes to the resolver, if it is not
+/// embedded.
+pub fn get_external_url(div: &Div) -> Option<&str> {
+    let reference = match div.background_image.as_ref() {
+        Some(reference) => reference,
+        None => div
+            .images
+            .first()
+            .filter(|image| image.data.is_none())?
+            .src
+            .as_ref()?,
+    };
+    Some(reference)
+        .filter(|reference| !reference.starts_with('#') && !reference.starts_with("data:"))
+        .map(|reference| reference.as_str())
+}
+
+/// Image shown by a division: its `smpte:backgroundImage` or its first
+/// `image` child.
+pub fn resolve_image(
+    tt: &TT,
+    div: &Div,
+    resolver: Option<&ImageResolver>,
+) -> Result<RenderedImage, String> {
+    if let Some(reference) = div.background_image.as_ref() {
+        return from_reference(tt, reference, resolver);
+    }
+    match div.images.first() {
+        Some(image) => match image.src.as_ref() {
+            Some(src) if image.data.is_none() => from_reference(tt, src, resolver),
+            _ => from_embedded(image),
+        },
+        None => Err("Division without image".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Subtitles;
+
+    const DOCUMENT: &str = r##"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:smpte="http://www.smpte-ra.org/schemas/2052-1/2010/smpte-tt">
+  <head>
+    <metadata>
+      <smpte:image xml:id="img1" imageType="PNG" encoding="Base64">
+        iVBORw0KGgo
+        AAAANSUhEUg==
+      </smpte:image>
+    </metadata>
+  </head>
+  <body>
+    <div begin="00:00:01.000" end="00:00:02.000" smpte:backgroundImage="#img1"/>
+    <div begin="00:00:02.000" end="00:00:03.000" smpte:backgroundImage="images/2.png"/>
+    <div begin="00:00:03.000" end="00:00:04.000" smpte:backgroundImage="#missing"/>
+  </body>
+</tt>"##;
+
+    #[test]
+    fn resolves_embedded_and_external_images() {
+        let mut subtitles = Subtitles::new();
+        subtitles.load(DOCUMENT).unwrap();
+        let tt = subtitles.tt.as_ref().unwrap();
+        let divs = &tt.body.divs;
+        let embedded = resolve_image(tt, &divs[0], None).unwrap();
+        assert_eq!(
+            embedded.src,
+            "data:image/png;base64,iVBORw0KGgoAAAANSUhEUg=="
+        );
+        assert_eq!(embedded.mime_type, "image/png");
+        assert_eq!(
+            resolve_image(tt, &divs[1], None).unwrap().src,
+            "images/2.png"
+        );
+        let resolver: ImageResolver =
+            Box::new(|url: &str| Some(format!("https://cdn.example/{}", url)));
+        assert_eq!(
+            resolve_image(tt, &divs[1], Some(&resolver)).unwrap().src,
+            "https://cdn.example/images/2.png"
+        );
+        assert_eq!(
+            resolve_image(tt, &divs[2], None),
+            Err("Image not found: #missing".to_string())
+        );
+        assert_eq!(get_external_url(&divs[0]), None);
+        assert_eq!(get_external_url(&divs[1]), Some("images/2.png"));
+    }
+}
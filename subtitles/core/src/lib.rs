@@ -0,0 +1,2204 @@
+use quick_xml::de::from_str;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+mod append;
+mod captions;
+mod cea608;
+mod cea708;
+mod color;
+mod convert;
+mod cuepoints;
+mod dash;
+mod dates;
+mod dvb;
+mod emphasis;
+mod hls;
+mod images;
+mod mp4;
+mod outline;
+mod personalization;
+mod png;
+#[cfg(test)]
+mod recording_view;
+mod render;
+mod teletext;
+mod timeline;
+mod tracks;
+mod ts;
+mod units;
+mod url;
+mod validation;
+mod view;
+mod webvtt;
+pub use captions::{
+    get_sei_cc_data, parse_caption_track, CaptionDecoder, CaptionLine, CaptionRun, CaptionScreen,
+    CaptionTrack, CaptionWindow,
+};
+pub use cea608::Cea608Decoder;
+pub use cea708::Cea708Decoder;
+use color::Color;
+pub use convert::{format_clock_time, TimedSubtitle};
+pub use dash::{parse_mpd, DashSegment, DashTextTrack};
+pub use dvb::{DvbBitmap, DvbDecoder, DvbDisplaySet};
+pub use hls::{
+    parse_master_playlist, parse_media_playlist, HlsMediaPlaylist, HlsRendition, HlsSegment,
+};
+use images::ImageResolver;
+use personalization::Personalization;
+use render::{
+    RenderedImage, RenderedRegion, RenderedRuby, RenderedRun, RenderedSubtitle, RubyReserve,
+    TextEmphasis, TextOutline, TextShadow, TextStyle,
+};
+pub use teletext::{TeletextDecoder, TeletextLine, TeletextPage, TeletextRun};
+use timeline::Timeline;
+pub use tracks::{parse_track_kind, SubtitleTrack, TrackKind, TrackPreferences};
+pub use ts::{TsDemuxer, TsPes, TsStreamKind, TsSubtitleStream};
+use units::{format_number, format_px, FontSize, Length, Unit};
+pub use validation::{Diagnostic, Severity};
+pub use view::SubtitleView;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename = "tt")]
+pub struct TT {
+    #[serde(rename = "@xmlns:tt", default)]
+    pub xmlns_tt: String,
+    #[serde(rename = "@xmlns:ttp", default)]
+    pub xmlns_ttp: String,
+    #[serde(rename = "@xmlns:tts", default)]
+    pub xmlns_tts: String,
+    #[serde(rename = "@xmlns:ebuttm", default)]
+    pub xmlns_ebuttm: String,
+    #[serde(rename = "@xmlns:ebutts", default)]
+    pub xmlns_ebutts: String,
+    #[serde(rename = "@timeBase", default)]
+    pub ttp_time_base: String,
+    #[serde(rename = "@lang", default)]
+    pub xml_lang: String,
+    #[serde(rename = "@cellResolution")]
+    pub ttp_cell_resolution: Option<String>,
+    #[serde(rename = "@extent")]
+    pub tts_extent: Option<String>,
+    pub head: Head,
+    pub body: Body,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Head {
+    pub metadata: Option<Metadata>,
+    #[serde(default)]
+    pub styling: Styling,
+    #[serde(default)]
+    pub layout: Layout,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Metadata {
+    #[serde(rename = "@ppd")]
+    pub ppd: Option<String>,
+    #[serde(rename = "documentMetadata")]
+    pub document_metadata: Option<DocumentMetadata>,
+    /// Images of the IMSC image profile (`smpte:image`).
+    #[serde(rename = "image", default)]
+    pub images: Vec<Image>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DocumentMetadata {
+    /// Designators of the standards the document conforms to, a document can
+    /// declare several (e.g. EBU-TT-D and IMSC1).
+    #[serde(rename = "conformsToStandard", default)]
+    pub conforms_to_standard: Vec<String>,
+    #[serde(rename = "documentCountryOfOrigin", default)]
+    pub document_country_of_origin: String,
+}
+
+/// Image of the IMSC image profile: a `smpte:image` with the PNG encoded in
+/// base64, or an IMSC 1.1 `image` that references it with `src`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Image {
+    #[serde(rename = "@id")]
+    pub id: Option<String>,
+    #[serde(rename = "@src")]
+    pub src: Option<String>,
+    /// `smpte:image` type, "PNG".
+    #[serde(rename = "@imageType")]
+    pub image_type: Option<String>,
+    /// `image` MIME type, "image/png".
+    #[serde(rename = "@type")]
+    pub mime_type: Option<String>,
+    #[serde(rename = "@encoding")]
+    pub encoding: Option<String>,
+    #[serde(rename = "$value")]
+    pub data: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Styling {
+    #[serde(rename = "style", default)]
+    pub styles: Vec<Style>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Style {
+    #[serde(rename = "@id", default)]
+    pub id: String,
+    #[serde(rename = "@fontFamily")]
+    pub font_family: Option<String>,
+    #[serde(rename = "@fontSize")]
+    pub font_size: Option<String>,
+    #[serde(rename = "@fontStyle")]
+    pub font_style: Option<String>,
+    #[serde(rename = "@fontWeight")]
+    pub font_weight: Option<String>,
+    #[serde(rename = "@textDecoration")]
+    pub text_decoration: Option<String>,
+    #[serde(rename = "@color")]
+    pub color: Option<String>,
+    #[serde(rename = "@textAlign")]
+    pub text_align: Option<String>,
+    #[serde(rename = "@backgroundColor")]
+    pub background_color: Option<String>,
+    #[serde(rename = "@lineHeight")]
+    pub line_height: Option<String>,
+    #[serde(rename = "@linePadding")]
+    pub line_padding: Option<String>,
+    #[serde(rename = "@multiRowAlign")]
+    pub multi_row_align: Option<String>,
+    #[serde(rename = "@textOutline")]
+    pub text_outline: Option<String>,
+    #[serde(rename = "@textShadow")]
+    pub text_shadow: Option<String>,
+    #[serde(rename = "@opacity")]
+    pub opacity: Option<String>,
+    #[serde(rename = "@visibility")]
+    pub visibility: Option<String>,
+    #[serde(rename = "@display")]
+    pub display: Option<String>,
+    #[serde(rename = "@ruby")]
+    pub ruby: Option<String>,
+    #[serde(rename = "@rubyPosition")]
+    pub ruby_position: Option<String>,
+    #[serde(rename = "@rubyAlign")]
+    pub ruby_align: Option<String>,
+    #[serde(rename = "@rubyReserve")]
+    pub ruby_reserve: Option<String>,
+    #[serde(rename = "@textEmphasis")]
+    pub text_emphasis: Option<String>,
+    #[serde(rename = "@textCombine")]
+    pub text_combine: Option<String>,
+    #[serde(rename = "@shear")]
+    pub shear: Option<String>,
+}
+
+/// `<set>` animation: the style attributes it carries apply to its parent
+/// element between `begin` and `end` (or `begin` + `dur`), relative to the
+/// begin of the parent.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Set {
+    #[serde(rename = "@begin")]
+    pub begin: Option<String>,
+    #[serde(rename = "@end")]
+    pub end: Option<String>,
+    #[serde(rename = "@dur")]
+    pub dur: Option<String>,
+    #[serde(flatten)]
+    pub style: Style,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Layout {
+    #[serde(rename = "region", default)]
+    pub regions: Vec<Region>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Region {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@origin")]
+    pub origin: Option<String>,
+    #[serde(rename = "@extent")]
+    pub extent: Option<String>,
+    #[serde(rename = "@padding")]
+    pub padding: Option<String>,
+    #[serde(rename = "@displayAlign")]
+    pub display_align: Option<String>,
+    #[serde(rename = "@writingMode")]
+    pub writing_mode: Option<String>,
+    #[serde(rename = "@showBackground")]
+    pub show_background: Option<String>,
+    #[serde(rename = "@overflow")]
+    pub overflow: Option<String>,
+    #[serde(rename = "@style")]
+    pub style: Option<String>,
+    #[serde(rename = "set", default)]
+    pub sets: Vec<Set>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Body {
+    #[serde(rename = "@style")]
+    pub style: Option<String>,
+    #[serde(rename = "div", default)]
+    pub divs: Vec<Div>,
+}
+
+/// A division of text paragraphs, or in the IMSC image profile a timed
+/// division that shows an image in its region.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Div {
+    #[serde(rename = "@id")]
+    pub id: Option<String>,
+    #[serde(rename = "@style")]
+    pub style: Option<String>,
+    #[serde(rename = "@region")]
+    pub region: Option<String>,
+    #[serde(rename = "@begin")]
+    pub begin: Option<String>,
+    #[serde(rename = "@end")]
+    pub end: Option<String>,
+    /// `smpte:backgroundImage`, "#id" of an embedded image or a URL.
+    #[serde(rename = "@backgroundImage")]
+    pub background_image: Option<String>,
+    #[serde(default)]
+    pub p: Vec<P>,
+    #[serde(rename = "image", default)]
+    pub images: Vec<Image>,
+    /// Id in the appended segment, when it was renamed to keep it unique.
+    #[serde(skip)]
+    pub source_id: Option<String>,
+}
+
+impl Div {
+    fn is_image(&self) -> bool {
+        self.background_image.is_some() || !self.images.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct P {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "@region")]
+    pub region: Option<String>,
+    #[serde(rename = "@style")]
+    pub style: Option<String>,
+    #[serde(rename = "@begin")]
+    pub begin: String,
+    #[serde(rename = "@end")]
+    pub end: String,
+    #[serde(rename = "$value")]
+    children: Option<Vec<Choice>>,
+    /// Id in the appended segment, when it was renamed to keep it unique.
+    #[serde(skip)]
+    pub source_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Choice {
+    #[serde(rename = "span")]
+    Span(Span),
+    #[serde(rename = "br")]
+    Br(Br),
+    #[serde(rename = "set")]
+    Set(Box<Set>),
+}
+
+impl P {
+    /// Every span of the paragraph, including the nested ones.
+    fn get_spans(&self) -> Vec<&Span> {
+        let mut spans: Vec<&Span> = Vec::new();
+        for child in self.children.iter().flatten() {
+            if let Choice::Span(span) = child {
+                span.push_spans(&mut spans);
+            }
+        }
+        spans
+    }
+
+    fn get_sets(&self) -> Vec<&Set> {
+        self.children
+            .iter()
+            .flatten()
+            .filter_map(|child| match child {
+                Choice::Set(set) => Some(set.as_ref()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Span {
+    #[serde(rename = "@style", default)]
+    pub style: String,
+    /// Ruby attributes are usually specified inline in IMSC documents.
+    #[serde(rename = "@ruby")]
+    pub ruby: Option<String>,
+    #[serde(rename = "@rubyPosition")]
+    pub ruby_position: Option<String>,
+    #[serde(rename = "@rubyAlign")]
+    pub ruby_align: Option<String>,
+    #[serde(rename = "$value")]
+    children: Option<Vec<SpanChoice>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum SpanChoice {
+    #[serde(rename = "$text")]
+    Text(String),
+    #[serde(rename = "span")]
+    Span(Box<Span>),
+    #[serde(rename = "br")]
+    Br(Br),
+    #[serde(rename = "set")]
+    Set(Box<Set>),
+}
+
+impl Span {
+    fn push_spans<'a>(&'a self, spans: &mut Vec<&'a Span>) {
+        spans.push(self);
+        for child in self.children.iter().flatten() {
+            if let SpanChoice::Span(span) = child {
+                span.push_spans(spans);
+            }
+        }
+    }
+
+    fn get_child_spans(&self) -> Vec<&Span> {
+        self.children
+            .iter()
+            .flatten()
+            .filter_map(|child| match child {
+                SpanChoice::Span(span) => Some(span.as_ref()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn get_text(&self) -> Option<String> {
+        let texts: Vec<&str> = self
+            .children
+            .iter()
+            .flatten()
+            .filter_map(|child| match child {
+                SpanChoice::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        // The parser trims the text around the `<set>` elements.
+        if texts.is_empty() {
+            None
+        } else {
+            Some(texts.join(" "))
+        }
+    }
+
+    fn get_sets(&self) -> Vec<&Set> {
+        self.children
+            .iter()
+            .flatten()
+            .filter_map(|child| match child {
+                SpanChoice::Set(set) => Some(set.as_ref()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Br {}
+
+fn time_to_ms(time: &str) -> Result<i64, String> {
+    let blocks: Vec<&str> = time.split(':').collect();
+    if blocks.len() != 3 {
+        return Err("Format de temps incorrecte".to_string());
+    }
+
+    let hours = blocks[0]
+        .parse::<i64>()
+        .map_err(|_| "Error en parsejar les hores".to_string())?;
+    let minutes = blocks[1]
+        .parse::<i64>()
+        .map_err(|_| "Error en parsejar els minuts".to_string())?;
+
+    let seconds_block: Vec<&str> = blocks[2].split('.').collect();
+    let seconds = seconds_block[0]
+        .parse::<i64>()
+        .map_err(|_| "Error en parsejar els segons".to_string())?;
+
+    let mut ms = hours
+        .checked_mul(60)
+        .and_then(|total| total.checked_add(minutes))
+        .and_then(|total| total.checked_mul(60))
+        .and_then(|total| total.checked_add(seconds))
+        .and_then(|total| total.checked_mul(1000))
+        .ok_or_else(|| "Temps fora de rang".to_string())?;
+
+    if seconds_block.len() > 1 {
+        let fraction = seconds_block[1];
+        if fraction.is_empty() || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err("Error en parsejar els mil·lisegons".to_string());
+        }
+        // The fraction is in seconds, so ".5" is 500 ms; digits past the millisecond are dropped.
+        let millis = format!("{:0<3}", &fraction[..fraction.len().min(3)])
+            .parse::<i64>()
+            .map_err(|_| "Error en parsejar els mil·lisegons".to_string())?;
+        ms = ms
+            .checked_add(millis)
+            .ok_or_else(|| "Temps fora de rang".to_string())?;
+    }
+
+    Ok(ms)
+}
+
+/// Offset time expression ("1.5s", "200ms", "2m", "1h") or a clock time
+/// handled by `time_to_ms`. Used for the times of `<set>`, which are relative
+/// to their parent.
+fn time_expression_to_ms(time: &str) -> Result<i64, String> {
+    let time = time.trim();
+    let (number, factor) = if let Some(number) = time.strip_suffix("ms") {
+        (number, 1.0)
+    } else if let Some(number) = time.strip_suffix('s') {
+        (number, 1000.0)
+    } else if let Some(number) = time.strip_suffix('m') {
+        (number, 60000.0)
+    } else if let Some(number) = time.strip_suffix('h') {
+        (number, 3600000.0)
+    } else {
+        return time_to_ms(time);
+    };
+    number
+        .parse::<f64>()
+        .map(|number| (number * factor).round() as i64)
+        .map_err(|_| format!("Invalid time expression: {}", time))
+}
+
+/// Timed element shown by the scheduler: a paragraph (indexes of the division
+/// and of the paragraph) or an image division of the IMSC image profile.
+#[derive(Debug, Clone, Copy)]
+pub enum SubtitleRef {
+    Paragraph(usize, usize),
+    Image(usize),
+}
+
+enum SubtitleElement<'a> {
+    Paragraph(&'a Div, &'a P),
+    Image(usize, &'a Div),
+}
+
+impl SubtitleElement<'_> {
+    /// Id of the subtitle, also used for its HTML element.
+    fn get_id(&self) -> String {
+        match self {
+            SubtitleElement::Paragraph(_, p) => p.id.clone(),
+            SubtitleElement::Image(div_index, div) => div
+                .id
+                .clone()
+                .unwrap_or_else(|| format!("image-{}", div_index)),
+        }
+    }
+
+    fn get_begin(&self) -> i64 {
+        let begin = match self {
+            SubtitleElement::Paragraph(_, p) => Some(&p.begin),
+            SubtitleElement::Image(_, div) => div.begin.as_ref(),
+        };
+        begin.and_then(|begin| time_to_ms(begin).ok()).unwrap_or(-1)
+    }
+
+    fn get_end(&self) -> i64 {
+        let end = match self {
+            SubtitleElement::Paragraph(_, p) => Some(&p.end),
+            SubtitleElement::Image(_, div) => div.end.as_ref(),
+        };
+        end.and_then(|end| time_to_ms(end).ok()).unwrap_or(-1)
+    }
+
+    fn get_div(&self) -> &Div {
+        match self {
+            SubtitleElement::Paragraph(div, _) => div,
+            SubtitleElement::Image(_, div) => div,
+        }
+    }
+
+    /// The region of a paragraph is inherited from its division.
+    fn get_region_id(&self) -> Option<&String> {
+        match self {
+            SubtitleElement::Paragraph(div, p) => p.region.as_ref().or(div.region.as_ref()),
+            SubtitleElement::Image(_, div) => div.region.as_ref(),
+        }
+    }
+}
+
+pub enum SubtilesAction {
+    Show(usize),
+    Hide(usize),
+    /// A `<set>` starts or ends, the subtitles on screen may change.
+    Animate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellResolution {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+/// `ttp:cellResolution` of a document, 40 by 24 if missing or invalid.
+fn get_cell_resolution(value: Option<&String>) -> CellResolution {
+    let default = CellResolution {
+        columns: 40,
+        rows: 24,
+    };
+    let cell_resolution_splitted: Vec<&str> = match value {
+        Some(value) => value.split_whitespace().collect(),
+        None => return default,
+    };
+    if cell_resolution_splitted.len() != 2 {
+        return default;
+    }
+    match (
+        cell_resolution_splitted[0].parse::<usize>(),
+        cell_resolution_splitted[1].parse::<usize>(),
+    ) {
+        (Ok(columns), Ok(rows)) if columns > 0 && rows > 0 => CellResolution { columns, rows },
+        _ => default,
+    }
+}
+
+/// Size of the root container in document pixels (`tts:extent` on `tt`), used
+/// to scale `px` lengths to the display element.
+pub struct RootExtent {
+    pub width: f32,
+    pub height: f32,
+}
+
+pub struct TTRootConfig {
+    cell_resolution: CellResolution,
+    extent: Option<RootExtent>,
+}
+
+pub struct ElementSize {
+    width: i32,
+    height: i32,
+}
+
+/// Position and size of a region in display pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RegionGeometry {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+pub struct Subtitles {
+    pub tt: Option<TT>,
+    /// Source of the document, scanned by `validate`.
+    pub source: String,
+    pub cuepoints: cuepoints::Cuepoints,
+    pub cuepoint_to_subtitles_action: HashMap<String, SubtilesAction>,
+    pub styles_index: HashMap<String, usize>,
+    pub region_index: HashMap<String, usize>,
+    pub default_styles: String,
+    pub tt_root_config: TTRootConfig,
+    pub element_size: ElementSize,
+    pub warnings: Vec<String>,
+    /// Indexes of the paragraphs currently shown, in display order.
+    pub visible_subtitles: Vec<usize>,
+    /// Which `<set>` animations were active when each visible paragraph was
+    /// drawn, so it is only redrawn when they change.
+    pub animation_states: HashMap<usize, Vec<bool>>,
+    /// Document time of the last update, see `timeline`.
+    pub current_ms: i64,
+    /// Media time of the last update.
+    pub media_ms: i64,
+    pub timeline: Timeline,
+    /// Document time kept behind the current time by `append_document`.
+    pub back_buffer_ms: i64,
+    /// Segments appended to the loaded document, suffix of the ids renamed
+    /// to keep them unique.
+    pub appended_documents: usize,
+    /// Subtitle track of the last MP4 init segment.
+    pub mp4_track: Option<mp4::Mp4Track>,
+    /// Decoder of the DVB subtitle PES packets, with the bitmaps being
+    /// composed.
+    pub dvb_decoder: Option<DvbDecoder>,
+    /// Decoded bitmaps of the DVB cues by id, for the structured output.
+    pub dvb_bitmaps: HashMap<String, DvbBitmap>,
+    /// Decoder of the Teletext PES packets, with the selected page.
+    pub teletext_decoder: Option<TeletextDecoder>,
+    /// Demuxer of the appended transport stream chunks.
+    pub ts_demuxer: Option<TsDemuxer>,
+    /// Subtitle service of the transport stream that is shown.
+    pub ts_stream: Option<TsSubtitleStream>,
+    /// Decoder of the closed captions of the video, with the selected
+    /// channel or service.
+    pub caption_decoder: Option<CaptionDecoder>,
+    /// Tracks of the title, the selected one is the loaded document.
+    pub tracks: Vec<SubtitleTrack>,
+    pub selected_track: Option<String>,
+    /// Preferences of the automatic track selection, None after the user
+    /// selected a track.
+    pub track_preferences: Option<TrackPreferences>,
+    /// Paragraphs and image divisions of the document, indexed by the actions
+    /// of the cuepoints.
+    pub subtitle_refs: Vec<SubtitleRef>,
+    pub image_resolver: Option<ImageResolver>,
+    /// Where the subtitles are drawn, nothing is drawn without a view.
+    pub view: Option<Box<dyn SubtitleView + Send>>,
+    pub personalization: Personalization,
+}
+
+impl Default for Subtitles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Subtitles {
+    pub fn new() -> Subtitles {
+        Subtitles {
+            tt: None,
+            source: String::new(),
+            cuepoints: cuepoints::Cuepoints::new(),
+            cuepoint_to_subtitles_action: HashMap::new(),
+            styles_index: HashMap::new(),
+            region_index: HashMap::new(),
+            default_styles: "".to_string(),
+            tt_root_config: TTRootConfig {
+                cell_resolution: CellResolution {
+                    columns: 40,
+                    rows: 24,
+                },
+                extent: None,
+            },
+            element_size: ElementSize {
+                width: 0,
+                height: 0,
+            },
+            warnings: Vec::new(),
+            visible_subtitles: Vec::new(),
+            animation_states: HashMap::new(),
+            current_ms: 0,
+            media_ms: 0,
+            timeline: Timeline::new(),
+            back_buffer_ms: append::DEFAULT_BACK_BUFFER_MS,
+            appended_documents: 0,
+            mp4_track: None,
+            dvb_decoder: None,
+            dvb_bitmaps: HashMap::new(),
+            teletext_decoder: None,
+            ts_demuxer: None,
+            ts_stream: None,
+            caption_decoder: None,
+            tracks: Vec::new(),
+            selected_track: None,
+            track_preferences: None,
+            subtitle_refs: Vec::new(),
+            image_resolver: None,
+            view: None,
+            personalization: Personalization::new(),
+        }
+    }
+
+    /// Sizes in pixels are derived from the element size, so a resize (e.g.
+    /// fullscreen or picture-in-picture) recomputes them and redraws the
+    /// subtitles on screen with the new geometry.
+    pub fn set_element_size(&mut self, width: i32, height: i32) {
+        if self.element_size.width == width && self.element_size.height == height {
+            return;
+        }
+        self.element_size.width = width;
+        self.element_size.height = height;
+        if self.tt.is_some() {
+            self.get_default_styles();
+            self.check_regions();
+            self.refresh_visible_subtitles();
+        }
+    }
+
+    pub fn set_personalization_enabled(&mut self, enabled: bool) {
+        self.personalization.enabled = enabled;
+        self.refresh_visible_subtitles();
+    }
+
+    pub fn set_personalization_font_scale(&mut self, font_scale: f32) -> Result<(), String> {
+        if !font_scale.is_finite() || font_scale <= 0.0 {
+            return Err(format!("Invalid font scale: {}", font_scale));
+        }
+        self.personalization.font_scale = font_scale;
+        self.refresh_visible_subtitles();
+        Ok(())
+    }
+
+    /// `None` restores the text colour of the document.
+    pub fn set_personalization_text_color(&mut self, color: Option<&str>) -> Result<(), String> {
+        self.personalization.text_color = color.map(Color::parse).transpose()?;
+        self.refresh_visible_subtitles();
+        Ok(())
+    }
+
+    /// `None` restores the background of the document.
+    pub fn set_personalization_background_opacity(
+        &mut self,
+        opacity: Option<f32>,
+    ) -> Result<(), String> {
+        if let Some(opacity) = opacity.filter(|opacity| !(0.0..=1.0).contains(opacity)) {
+            return Err(format!("Invalid background opacity: {}", opacity));
+        }
+        self.personalization.background_opacity = opacity;
+        self.refresh_visible_subtitles();
+        Ok(())
+    }
+
+    pub fn set_personalization_vertical_offset(&mut self, percentage: f32) -> Result<(), String> {
+        if !(-100.0..=100.0).contains(&percentage) {
+            return Err(format!("Invalid vertical offset: {}", percentage));
+        }
+        self.personalization.vertical_offset = percentage;
+        self.refresh_visible_subtitles();
+        Ok(())
+    }
+
+    /// Media time, in milliseconds, at which the document starts.
+    pub fn set_time_offset(&mut self, ms: i64) {
+        self.timeline.offset = ms;
+        self.resync_visible_subtitles();
+    }
+
+    /// Media milliseconds per document millisecond, e.g. 25 / 23.976 for a
+    /// document timed at 23.976 fps played at 25 fps.
+    pub fn set_time_scale(&mut self, scale: f64) -> Result<(), String> {
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(format!("Invalid time scale: {}", scale));
+        }
+        self.timeline.scale = scale;
+        self.resync_visible_subtitles();
+        Ok(())
+    }
+
+    /// User adjustment of the synchronisation, positive shows the subtitles
+    /// later.
+    pub fn set_subtitle_delay(&mut self, ms: i64) {
+        self.timeline.delay = ms;
+        self.resync_visible_subtitles();
+    }
+
+    pub fn set_playback_rate(&mut self, rate: f64) -> Result<(), String> {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(format!("Invalid playback rate: {}", rate));
+        }
+        self.timeline.playback_rate = rate;
+        Ok(())
+    }
+
+    pub fn set_view(&mut self, view: Option<Box<dyn SubtitleView + Send>>) {
+        self.view = view;
+        self.refresh_visible_subtitles();
+    }
+
+    /// Resolver of the URLs of the external images of the IMSC image profile.
+    pub fn set_image_resolver(&mut self, image_resolver: Option<ImageResolver>) {
+        self.image_resolver = image_resolver;
+        self.refresh_visible_subtitles();
+    }
+
+    pub fn load(&mut self, xml: &str) -> Result<(), String> {
+        //log(&format!("hola? {}", xml));
+        let tt = from_str(xml).map_err(|error| format!("Invalid document: {}", error))?;
+        for index in self.visible_subtitles.clone() {
+            self.apply_subtitle_action(index, false);
+        }
+        self.tt = Some(tt);
+        self.source = xml.to_string();
+        self.appended_documents = 0;
+        self.dvb_bitmaps.clear();
+        self.get_tt_root_config();
+        self.reindex(Vec::new());
+        //let object: TT = from_str(&xml).unwrap();
+
+        // log(&format!("object {}", object.body.div.p.len()));
+        // for (index, p) in object.body.div.p.iter().enumerate() {
+        //     let x = Vec::new();
+        //     for (index, child) in p.children.as_ref().unwrap_or(&x).iter().enumerate() {
+        //         if let Choice::Span(span) = child {
+        //             log(&format!(
+        //                 "Flipa {}",
+        //                 span.text.as_ref().unwrap_or(&"".to_string())
+        //             ));
+        //         } else if let Choice::Br(br) = child {
+        //             log("no Flipa");
+        //         }
+        //         // if let Choice::Span(span) = child {
+        //         //     Some(span.style.clone())
+        //         // } else {
+        //         //     None
+        //         // }
+        //     }
+        // }
+
+        //log(&format!("object {}", object.name_attr));
+
+        /*
+        let mut reader = Reader::from_str(xml);
+        reader.trim_text(true);
+        let mut count = 0;
+        let mut txt = Vec::new();
+        let mut buf = Vec::new();
+
+        // The `Reader` does not implement `Iterator` because it outputs borrowed data (`Cow`s)
+        loop {
+            // NOTE: this is the generic case when we don't know about the input BufRead.
+            // when the input is a &str or a &[u8], we don't actually need to use another
+            // buffer, we could directly call `reader.read_event()`
+            match reader.read_event_into(&mut buf) {
+                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+                // exits the loop when reaching end of file
+                Ok(Event::Eof) => break,
+
+                Ok(Event::Start(e)) => match e.name().as_ref() {
+                    b"tt:tt" => {
+                        let x = e
+                            .attributes()
+                            .map(|a| a.unwrap().value.as_ref())
+                            .collect::<Vec<_>>()
+                            .to_string();
+                        //let y = x.collect::<Vec<_>>();
+                        //log(&format!("attributes values: {}", y.join(","));
+                    }
+                    // b"tt:tt" => log(
+                    //     "attributes values: {:?}",
+                    //     e.attributes().map(|a| a.unwrap().value).collect::<Vec<_>>(),
+                    // ),
+                    b"tag2" => count += 1,
+                    _ => (),
+                },
+                Ok(Event::Text(e)) => txt.push(e.unescape().unwrap().into_owned()),
+
+                // There are several other `Event`s we do not consider here
+                _ => (),
+            }
+            // if we don't keep a borrow elsewhere, we can clear the buffer to keep memory usage low
+            buf.clear();
+        }
+        */
+        Ok(())
+    }
+
+    /// Hides the subtitles on screen and drops the document.
+    pub fn unload(&mut self) {
+        for index in self.visible_subtitles.clone() {
+            self.apply_subtitle_action(index, false);
+        }
+        self.tt = None;
+        self.source = String::new();
+        self.appended_documents = 0;
+        self.dvb_bitmaps.clear();
+        self.subtitle_refs.clear();
+        self.cuepoints = cuepoints::Cuepoints::new();
+        self.cuepoint_to_subtitles_action.clear();
+        self.styles_index.clear();
+        self.region_index.clear();
+    }
+    fn get_tt_root_config(&mut self) {
+        self.tt_root_config.cell_resolution = get_cell_resolution(
+            self.tt
+                .as_ref()
+                .and_then(|tt| tt.ttp_cell_resolution.as_ref()),
+        );
+        self.tt_root_config.extent = None;
+        if let Some(Ok(extent)) = self
+            .tt
+            .as_ref()
+            .and_then(|tt| tt.tts_extent.as_ref())
+            .map(|extent| Length::parse_list(extent))
+        {
+            if let [width, height] = extent[..] {
+                if width.unit == Unit::Pixel
+                    && height.unit == Unit::Pixel
+                    && width.value > 0.0
+                    && height.value > 0.0
+                {
+                    self.tt_root_config.extent = Some(RootExtent {
+                        width: width.value,
+                        height: height.value,
+                    });
+                }
+            }
+        }
+    }
+    /// Ids of the subtitles on screen and their animation states, taken
+    /// before the document changes so `reindex` can find them again.
+    fn take_visible_subtitles(&mut self) -> Vec<(String, Option<Vec<bool>>)> {
+        let visible_subtitles = std::mem::take(&mut self.visible_subtitles);
+        let mut animation_states = std::mem::take(&mut self.animation_states);
+        visible_subtitles
+            .into_iter()
+            .filter_map(|index| {
+                let id = self.get_subtitle_element(index)?.get_id();
+                Some((id, animation_states.remove(&index)))
+            })
+            .collect()
+    }
+
+    /// Rebuilds the indexes and the cuepoints after the document changed
+    /// (loaded, appended or evicted). The subtitles that were on screen keep
+    /// their place by id, the ones no longer in the document are hidden.
+    fn reindex(&mut self, visible_subtitles: Vec<(String, Option<Vec<bool>>)>) {
+        self.index_subtitles();
+        self.cuepoints = cuepoints::Cuepoints::new();
+        self.cuepoint_to_subtitles_action.clear();
+        self.add_cuepoints();
+        self.styles_index.clear();
+        self.get_styles();
+        self.region_index.clear();
+        self.get_regions();
+        self.get_default_styles();
+        self.check_regions();
+        let indexes: HashMap<String, usize> = (0..self.subtitle_refs.len())
+            .filter_map(|index| Some((self.get_subtitle_element(index)?.get_id(), index)))
+            .collect();
+        for (id, animation_state) in visible_subtitles {
+            match indexes.get(&id) {
+                Some(index) => {
+                    self.visible_subtitles.push(*index);
+                    if let Some(animation_state) = animation_state {
+                        self.animation_states.insert(*index, animation_state);
+                    }
+                }
+                None => {
+                    if let Some(view) = self.view.as_ref() {
+                        view.hide_subtitle(&id);
+                    }
+                }
+            }
+        }
+    }
+
+    fn get_styles(&mut self) {
+        for (index, s) in self
+            .tt
+            .as_ref()
+            .unwrap()
+            .head
+            .styling
+            .styles
+            .iter()
+            .enumerate()
+        {
+            self.styles_index.insert(s.id.clone(), index);
+        }
+    }
+    fn get_regions(&mut self) {
+        for (index, r) in self
+            .tt
+            .as_ref()
+            .unwrap()
+            .head
+            .layout
+            .regions
+            .iter()
+            .enumerate()
+        {
+            self.region_index.insert(r.id.clone(), index);
+        }
+    }
+    fn index_subtitles(&mut self) {
+        self.subtitle_refs.clear();
+        if let Some(tt) = self.tt.as_ref() {
+            for (div_index, div) in tt.body.divs.iter().enumerate() {
+                if div.is_image() {
+                    self.subtitle_refs.push(SubtitleRef::Image(div_index));
+                }
+                for p_index in 0..div.p.len() {
+                    self.subtitle_refs
+                        .push(SubtitleRef::Paragraph(div_index, p_index));
+                }
+            }
+        }
+    }
+
+    fn get_subtitle_element(&self, index: usize) -> Option<SubtitleElement<'_>> {
+        let divs = &self.tt.as_ref()?.body.divs;
+        match *self.subtitle_refs.get(index)? {
+            SubtitleRef::Paragraph(div_index, p_index) => {
+                let div = divs.get(div_index)?;
+                Some(SubtitleElement::Paragraph(div, div.p.get(p_index)?))
+            }
+            SubtitleRef::Image(div_index) => {
+                Some(SubtitleElement::Image(div_index, divs.get(div_index)?))
+            }
+        }
+    }
+
+    fn add_cuepoint(&mut self, id: String, ms: i64, action: SubtilesAction) {
+        self.cuepoints.add_cuepoint(cuepoints::Cuepoint {
+            id: id.clone(),
+            ms,
+            timestopass: 0,
+            //callback: None,
+            negativemargin: None,
+            positivemargin: None,
+            once: false,
+        });
+        self.cuepoint_to_subtitles_action.insert(id, action);
+    }
+
+    fn add_cuepoints(&mut self) {
+        let mut cuepoints: Vec<(String, i64, SubtilesAction)> = Vec::new();
+        if let Some(tt) = self.tt.as_ref() {
+            for region in tt.head.layout.regions.iter() {
+                for (set_index, set) in region.sets.iter().enumerate() {
+                    let id = format!("{}-set-{}", region.id, set_index);
+                    Self::push_set_cuepoints(&mut cuepoints, &id, set, 0);
+                }
+            }
+        }
+        for index in 0..self.subtitle_refs.len() {
+            let element = match self.get_subtitle_element(index) {
+                Some(element) => element,
+                None => continue,
+            };
+            let id = element.get_id();
+            let begin = element.get_begin();
+            cuepoints.push((format!("in-{}", id), begin, SubtilesAction::Show(index)));
+            cuepoints.push((
+                format!("out-{}", id),
+                element.get_end(),
+                SubtilesAction::Hide(index),
+            ));
+            if let SubtitleElement::Paragraph(_, p) = element {
+                for (set_index, set) in p.get_sets().into_iter().enumerate() {
+                    let id = format!("{}-set-{}", p.id, set_index);
+                    Self::push_set_cuepoints(&mut cuepoints, &id, set, begin);
+                }
+                for (span_index, span) in p.get_spans().into_iter().enumerate() {
+                    for (set_index, set) in span.get_sets().into_iter().enumerate() {
+                        let id = format!("{}-span-{}-set-{}", p.id, span_index, set_index);
+                        Self::push_set_cuepoints(&mut cuepoints, &id, set, begin);
+                    }
+                }
+            }
+        }
+        for (id, ms, action) in cuepoints {
+            self.add_cuepoint(id, ms, action);
+        }
+    }
+
+    fn push_set_cuepoints(
+        cuepoints: &mut Vec<(String, i64, SubtilesAction)>,
+        id: &str,
+        set: &Set,
+        parent_begin: i64,
+    ) {
+        if let Some((begin, end)) = Self::get_set_interval(set, parent_begin) {
+            cuepoints.push((format!("in-{}", id), begin, SubtilesAction::Animate));
+            if let Some(end) = end {
+                cuepoints.push((format!("out-{}", id), end, SubtilesAction::Animate));
+            }
+        }
+    }
+
+    /// Absolute interval of a `<set>`. Without `end` or `dur` it lasts until
+    /// the end of its parent (`None`).
+    fn get_set_interval(set: &Set, parent_begin: i64) -> Option<(i64, Option<i64>)> {
+        let begin = match set.begin.as_ref() {
+            Some(begin) => parent_begin + time_expression_to_ms(begin).ok()?,
+            None => parent_begin,
+        };
+        let end = if let Some(end) = set.end.as_ref() {
+            Some(parent_begin + time_expression_to_ms(end).ok()?)
+        } else if let Some(dur) = set.dur.as_ref() {
+            Some(begin + time_expression_to_ms(dur).ok()?)
+        } else {
+            None
+        };
+        Some((begin, end))
+    }
+
+    fn is_set_active(&self, set: &Set, parent_begin: i64) -> bool {
+        match Self::get_set_interval(set, parent_begin) {
+            Some((begin, end)) => {
+                self.current_ms >= begin && end.is_none_or(|end| self.current_ms < end)
+            }
+            None => false,
+        }
+    }
+
+    /// Style attributes of the `<set>` children active at the current time.
+    fn get_active_set_styles<'a>(&self, sets: &[&'a Set], parent_begin: i64) -> Vec<&'a Style> {
+        sets.iter()
+            .filter(|set| self.is_set_active(set, parent_begin))
+            .map(|set| &set.style)
+            .collect()
+    }
+
+    /// Activity of every `<set>` that affects a subtitle: its region, the
+    /// paragraph and its spans.
+    fn get_animation_state(&self, element: &SubtitleElement) -> Vec<bool> {
+        let mut state: Vec<bool> = element
+            .get_region_id()
+            .and_then(|region_id| self.get_region(region_id))
+            .map(|region| {
+                region
+                    .sets
+                    .iter()
+                    .map(|set| self.is_set_active(set, 0))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let SubtitleElement::Paragraph(_, p) = element {
+            let begin = element.get_begin();
+            state.extend(
+                p.get_sets()
+                    .iter()
+                    .map(|set| self.is_set_active(set, begin)),
+            );
+            for span in p.get_spans() {
+                state.extend(
+                    span.get_sets()
+                        .iter()
+                        .map(|set| self.is_set_active(set, begin)),
+                );
+            }
+        }
+        state
+    }
+
+    /// Updates the subtitles on screen for the media time `ms`.
+    pub fn update_subtitles_for_ms(&mut self, ms: i64) {
+        self.media_ms = ms;
+        self.current_ms = self.timeline.to_document(ms);
+        let mut is_animated = false;
+        let actions: Vec<(usize, bool)> = self
+            .cuepoints
+            .get_cuepoints_by_time(self.current_ms, self.timeline.get_margin_scale())
+            .iter()
+            .filter_map(|cue| self.cuepoint_to_subtitles_action.get(&cue.id))
+            .filter_map(|subtitle_action| match subtitle_action {
+                SubtilesAction::Show(index) => Some((*index, true)),
+                SubtilesAction::Hide(index) => Some((*index, false)),
+                SubtilesAction::Animate => {
+                    is_animated = true;
+                    None
+                }
+            })
+            .collect();
+        for (index, is_show_action) in actions {
+            self.apply_subtitle_action(index, is_show_action);
+        }
+        if is_animated {
+            self.update_animated_subtitles();
+        }
+    }
+    fn apply_subtitle_action(&mut self, index: usize, is_show_action: bool) {
+        let element = match self.get_subtitle_element(index) {
+            Some(element) => element,
+            None => return,
+        };
+        if is_show_action {
+            self.show_subtile(&element);
+            let animation_state = self.get_animation_state(&element);
+            self.animation_states.insert(index, animation_state);
+        } else {
+            self.hide_subtile(&element);
+            self.animation_states.remove(&index);
+        }
+        self.visible_subtitles
+            .retain(|visible_index| *visible_index != index);
+        if is_show_action {
+            self.visible_subtitles.push(index);
+        }
+    }
+    /// Redraws the visible subtitles whose `<set>` animations changed.
+    fn update_animated_subtitles(&mut self) {
+        let mut animation_states: Vec<(usize, Vec<bool>)> = Vec::new();
+        for index in self.visible_subtitles.iter() {
+            if let Some(element) = self.get_subtitle_element(*index) {
+                let animation_state = self.get_animation_state(&element);
+                if self.animation_states.get(index) != Some(&animation_state) {
+                    self.hide_subtile(&element);
+                    self.show_subtile(&element);
+                    animation_states.push((*index, animation_state));
+                }
+            }
+        }
+        self.animation_states.extend(animation_states);
+    }
+    /// Shows exactly the subtitles active at the media time of the last
+    /// update, after a change of the timeline moved the cues.
+    fn resync_visible_subtitles(&mut self) {
+        if self.tt.is_none() {
+            return;
+        }
+        self.current_ms = self.timeline.to_document(self.media_ms);
+        let ms = self.current_ms;
+        let active: Vec<usize> = (0..self.subtitle_refs.len())
+            .filter(|index| {
+                self.get_subtitle_element(*index)
+                    .is_some_and(|element| element.get_begin() <= ms && ms < element.get_end())
+            })
+            .collect();
+        for index in self.visible_subtitles.clone() {
+            if !active.contains(&index) {
+                self.apply_subtitle_action(index, false);
+            }
+        }
+        for index in active {
+            if !self.visible_subtitles.contains(&index) {
+                self.apply_subtitle_action(index, true);
+            }
+        }
+        self.update_animated_subtitles();
+    }
+
+    fn refresh_visible_subtitles(&self) {
+        for index in self.visible_subtitles.iter() {
+            if let Some(element) = self.get_subtitle_element(*index) {
+                self.hide_subtile(&element);
+                self.show_subtile(&element);
+            }
+        }
+    }
+    fn get_default_styles(&mut self) {
+        self.default_styles = format!(
+            "font-size:{}",
+            format_px(self.get_initial_font_size().height)
+        );
+    }
+
+    fn get_style(&self, style_id: &str) -> Option<&Style> {
+        let style_index = self.styles_index.get(style_id)?;
+        self.tt.as_ref()?.head.styling.styles.get(*style_index)
+    }
+
+    fn get_region(&self, region_id: &str) -> Option<&Region> {
+        let region_index = self.region_index.get(region_id)?;
+        self.tt.as_ref()?.head.layout.regions.get(*region_index)
+    }
+
+    fn get_styles_by_ref(&self, style_refs: &str) -> Vec<&Style> {
+        style_refs
+            .split_whitespace()
+            .filter_map(|style_id| self.get_style(style_id))
+            .collect()
+    }
+
+    /// Styles of a region followed by its active `<set>` animations.
+    fn get_region_styles<'a>(&'a self, region: &'a Region) -> Vec<&'a Style> {
+        let mut styles = region
+            .style
+            .as_ref()
+            .map(|style_refs| self.get_styles_by_ref(style_refs))
+            .unwrap_or_default();
+        let sets: Vec<&Set> = region.sets.iter().collect();
+        styles.extend(self.get_active_set_styles(&sets, 0));
+        styles
+    }
+
+    /// Times of the `<set>` of a span are relative to its paragraph.
+    fn get_span_styles<'a>(&'a self, p: &P, span: &'a Span) -> Vec<&'a Style> {
+        let mut styles = self.get_styles_by_ref(&span.style);
+        styles.extend(
+            self.get_active_set_styles(&span.get_sets(), time_to_ms(&p.begin).unwrap_or(-1)),
+        );
+        styles
+    }
+
+    /// Styles that apply to a subtitle grouped by element, from lowest to
+    /// highest priority: region style, body, div and the paragraph itself,
+    /// including the active `<set>` animations.
+    fn get_style_levels<'a>(&'a self, element: &SubtitleElement<'a>) -> Vec<Vec<&'a Style>> {
+        let mut levels = vec![element
+            .get_region_id()
+            .and_then(|region_id| self.get_region(region_id))
+            .map(|region| self.get_region_styles(region))
+            .unwrap_or_default()];
+        if let Some(tt) = self.tt.as_ref() {
+            for style_refs in [tt.body.style.as_ref(), element.get_div().style.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                levels.push(self.get_styles_by_ref(style_refs));
+            }
+        }
+        if let SubtitleElement::Paragraph(_, p) = element {
+            let mut p_styles = p
+                .style
+                .as_ref()
+                .map(|style_refs| self.get_styles_by_ref(style_refs))
+                .unwrap_or_default();
+            p_styles.extend(self.get_active_set_styles(&p.get_sets(), element.get_begin()));
+            levels.push(p_styles);
+        }
+        levels
+    }
+
+    /// `tts:display` is not inherited, but an element with `none` hides all
+    /// its descendants.
+    fn is_displayed(styles: &[&Style]) -> bool {
+        Self::get_inherited_attribute(styles, |style| style.display.as_ref())
+            .is_none_or(|display| display != "none")
+    }
+
+    fn get_inherited_attribute<'a, F>(style_chain: &[&'a Style], attribute: F) -> Option<&'a String>
+    where
+        F: Fn(&'a Style) -> Option<&'a String>,
+    {
+        style_chain.iter().rev().find_map(|style| attribute(style))
+    }
+
+    /// Origin and extent of a region converted to display pixels. Percentages
+    /// are relative to the root container, cells use the cell resolution and
+    /// pixels are scaled from the root `tts:extent`. A missing origin is the
+    /// top left corner and a missing extent covers the whole root container.
+    fn get_region_geometry(&self, region: &Region) -> Result<RegionGeometry, String> {
+        let root_width = self.element_size.width as f32;
+        let root_height = self.element_size.height as f32;
+        let (left, top) = match region.origin.as_ref() {
+            Some(origin) => self.get_region_pair_px(origin, root_width, root_height)?,
+            None => (0.0, 0.0),
+        };
+        let (width, height) = match region.extent.as_ref() {
+            Some(extent) => self.get_region_pair_px(extent, root_width, root_height)?,
+            None => (root_width, root_height),
+        };
+        if width < 0.0 || height < 0.0 {
+            return Err(format!("Region {} has a negative extent", region.id));
+        }
+        Ok(RegionGeometry {
+            left,
+            top,
+            width,
+            height,
+        })
+    }
+
+    fn get_region_pair_px(
+        &self,
+        value: &str,
+        reference_x: f32,
+        reference_y: f32,
+    ) -> Result<(f32, f32), String> {
+        match Length::parse_list(value)?[..] {
+            [x, y] => {
+                if x.unit == Unit::Em || y.unit == Unit::Em {
+                    return Err(format!("Length units not allowed in a region: {}", value));
+                }
+                Ok((
+                    self.length_to_px(&x, "x", reference_x),
+                    self.length_to_px(&y, "y", reference_y),
+                ))
+            }
+            _ => Err(format!("Expected two lengths: {}", value)),
+        }
+    }
+
+    /// `tts:padding` of a region in pixels (top, right, bottom, left),
+    /// following the 1 to 4 values shorthand of TTML for a `lrtb` writing
+    /// mode. Percentages are relative to the region size.
+    fn get_region_padding_px(
+        &self,
+        padding: &str,
+        geometry: &RegionGeometry,
+    ) -> Result<[f32; 4], String> {
+        let lengths = Length::parse_list(padding)?;
+        let to_px = |length: &Length, direction: &str| {
+            let reference = if direction == "x" {
+                geometry.width
+            } else {
+                geometry.height
+            };
+            self.length_to_px(length, direction, reference)
+        };
+        let (top, right, bottom, left) = match lengths[..] {
+            [all] => (all, all, all, all),
+            [vertical, horizontal] => (vertical, horizontal, vertical, horizontal),
+            [top, horizontal, bottom] => (top, horizontal, bottom, horizontal),
+            [top, right, bottom, left] => (top, right, bottom, left),
+            _ => return Err(format!("Invalid padding: {}", padding)),
+        };
+        Ok([
+            to_px(&top, "y"),
+            to_px(&right, "x"),
+            to_px(&bottom, "y"),
+            to_px(&left, "x"),
+        ])
+    }
+
+    /// Regions must be inside the root container. Overflowing regions are
+    /// reported in `warnings` and clipped when rendered.
+    fn check_regions(&mut self) {
+        let mut warnings: Vec<String> = Vec::new();
+        let has_element_size = self.element_size.width > 0 && self.element_size.height > 0;
+        if let Some(tt) = self.tt.as_ref().filter(|_| has_element_size) {
+            for region in tt.head.layout.regions.iter() {
+                match self.get_region_geometry(region) {
+                    Ok(geometry) => {
+                        if self.is_region_overflowing(&geometry) {
+                            warnings.push(format!(
+                                "Region {} overflows the root container: origin {}px {}px, extent {}px {}px, root {}px {}px",
+                                region.id,
+                                format_number(geometry.left),
+                                format_number(geometry.top),
+                                format_number(geometry.width),
+                                format_number(geometry.height),
+                                self.element_size.width,
+                                self.element_size.height
+                            ));
+                        }
+                    }
+                    Err(error) => warnings.push(format!("Region {}: {}", region.id, error)),
+                }
+            }
+        }
+        self.check_images(&mut warnings);
+        if let Some(view) = self.view.as_ref() {
+            for warning in warnings.iter() {
+                view.log(warning);
+            }
+        }
+        self.warnings = warnings;
+    }
+
+    /// Embedded images referenced by the image divisions. External images
+    /// are resolved when they are shown.
+    fn check_images(&self, warnings: &mut Vec<String>) {
+        let tt = match self.tt.as_ref() {
+            Some(tt) => tt,
+            None => return,
+        };
+        for subtitle_ref in self.subtitle_refs.iter() {
+            if let SubtitleRef::Image(div_index) = subtitle_ref {
+                let div = &tt.body.divs[*div_index];
+                if let Err(error) = images::resolve_image(tt, div, None) {
+                    warnings.push(format!(
+                        "Image {}: {}",
+                        SubtitleElement::Image(*div_index, div).get_id(),
+                        error
+                    ));
+                }
+            }
+        }
+    }
+
+    fn clip_region_geometry(&self, geometry: &RegionGeometry) -> RegionGeometry {
+        let root_width = self.element_size.width as f32;
+        let root_height = self.element_size.height as f32;
+        let left = geometry.left.clamp(0.0, root_width);
+        let top = geometry.top.clamp(0.0, root_height);
+        let right = (geometry.left + geometry.width).clamp(left, root_width);
+        let bottom = (geometry.top + geometry.height).clamp(top, root_height);
+        RegionGeometry {
+            left,
+            top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+
+    fn is_region_overflowing(&self, geometry: &RegionGeometry) -> bool {
+        // Half a pixel of tolerance for the rounding of percentages.
+        let clipped = self.clip_region_geometry(geometry);
+        (clipped.left - geometry.left).abs() > 0.5
+            || (clipped.top - geometry.top).abs() > 0.5
+            || (clipped.width - geometry.width).abs() > 0.5
+            || (clipped.height - geometry.height).abs() > 0.5
+    }
+
+    fn build_region(&self, region_id: &str) -> Option<RenderedRegion> {
+        let region = self.get_region(region_id)?;
+        let geometry = self.clip_region_geometry(&self.get_region_geometry(region).ok()?);
+        let padding = region
+            .padding
+            .as_ref()
+            .and_then(|padding| self.get_region_padding_px(padding, &geometry).ok())
+            .unwrap_or([0.0; 4]);
+        let region_styles = self.get_region_styles(region);
+        Some(RenderedRegion {
+            id: region.id.clone(),
+            geometry,
+            padding,
+            style: self.compute_text_style(&self.get_initial_text_style(), &region_styles),
+        })
+    }
+
+    fn get_cell_size(&self, direction: &str) -> f32 {
+        if direction == "x" {
+            self.element_size.width as f32 / self.tt_root_config.cell_resolution.columns as f32
+        } else {
+            self.element_size.height as f32 / self.tt_root_config.cell_resolution.rows as f32
+        }
+    }
+
+    /// Converts a length to display pixels. `reference` is the value that
+    /// percentages and `em` are relative to (usually the parent font size).
+    fn length_to_px(&self, length: &Length, direction: &str, reference: f32) -> f32 {
+        match length.unit {
+            Unit::Cell => length.value * self.get_cell_size(direction),
+            Unit::Percentage => length.value * reference / 100.0,
+            Unit::Em => length.value * reference,
+            Unit::Pixel => match self.tt_root_config.extent.as_ref() {
+                Some(extent) if direction == "x" => {
+                    length.value * self.element_size.width as f32 / extent.width
+                }
+                Some(extent) => length.value * self.element_size.height as f32 / extent.height,
+                None => length.value,
+            },
+        }
+    }
+
+    /// The initial value of `tts:fontSize` is `1c`, a square glyph of one
+    /// cell height.
+    fn get_initial_font_size(&self) -> FontSize {
+        let height = self.get_cell_size("y");
+        FontSize {
+            width: height,
+            height,
+        }
+    }
+
+    /// Applies the `tts:fontSize` of an element (its `styles`) over the font
+    /// size computed for its parent. With a single value both dimensions are
+    /// scaled together; with two values the first one is the horizontal size.
+    fn compute_font_size(&self, parent: FontSize, styles: &[&Style]) -> FontSize {
+        let font_size =
+            match Self::get_inherited_attribute(styles, |style| style.font_size.as_ref())
+                .map(|font_size| Length::parse_list(font_size))
+            {
+                Some(Ok(font_size)) => font_size,
+                _ => return parent,
+            };
+        match font_size[..] {
+            [size] => {
+                let height = self.length_to_px(&size, "y", parent.height);
+                let width = match size.unit {
+                    Unit::Percentage | Unit::Em => self.length_to_px(&size, "x", parent.width),
+                    Unit::Cell | Unit::Pixel => height,
+                };
+                FontSize { width, height }
+            }
+            [width, height] => FontSize {
+                width: self.length_to_px(&width, "x", parent.width),
+                height: self.length_to_px(&height, "y", parent.height),
+            },
+            _ => parent,
+        }
+    }
+
+    fn get_initial_text_style(&self) -> TextStyle {
+        TextStyle {
+            font_family: None,
+            font_size: self.get_initial_font_size(),
+            font_style: None,
+            font_weight: None,
+            text_decoration: None,
+            color: None,
+            background_color: None,
+            text_align: None,
+            line_height: None,
+            line_padding: 0.0,
+            multi_row_align: None,
+            text_outline: None,
+            text_shadows: Vec::new(),
+            opacity: 1.0,
+            visible: true,
+            ruby_position: None,
+            ruby_align: None,
+            ruby_reserve: None,
+            text_emphasis: None,
+            text_combine: false,
+            shear: 0.0,
+        }
+    }
+
+    /// Computed style of an element from the computed style of its parent and
+    /// its own `styles`. Invalid values are ignored and the inherited value is
+    /// kept.
+    fn compute_text_style(&self, parent: &TextStyle, styles: &[&Style]) -> TextStyle {
+        let attribute = |attribute: fn(&Style) -> Option<&String>| {
+            Self::get_inherited_attribute(styles, attribute)
+        };
+        let font_size = self.compute_font_size(parent.font_size, styles);
+        let color = match attribute(|style| style.color.as_ref()) {
+            Some(color) => Color::parse(color).ok().or(parent.color),
+            None => parent.color,
+        };
+        let line_height = match attribute(|style| style.line_height.as_ref()) {
+            Some(line_height) if line_height == "normal" => None,
+            Some(line_height) => match Length::parse(line_height) {
+                Ok(line_height) => Some(self.length_to_px(&line_height, "y", font_size.height)),
+                Err(_) => parent.line_height,
+            },
+            None => parent.line_height,
+        };
+        let line_padding = attribute(|style| style.line_padding.as_ref())
+            .and_then(|line_padding| Length::parse(line_padding).ok())
+            .map(|line_padding| self.length_to_px(&line_padding, "x", font_size.width))
+            .unwrap_or(parent.line_padding);
+        // The outline and the shadows are drawn with the text colour unless
+        // they specify their own.
+        let text_color = color.unwrap_or(Color::rgb(255, 255, 255));
+        let text_outline = match attribute(|style| style.text_outline.as_ref())
+            .map(|text_outline| outline::parse_text_outline(text_outline))
+        {
+            Some(Ok(text_outline)) => text_outline.map(|text_outline| TextOutline {
+                color: text_outline.color.unwrap_or(text_color),
+                thickness: self.length_to_px(&text_outline.thickness, "y", font_size.height),
+                blur_radius: text_outline
+                    .blur_radius
+                    .map(|blur_radius| self.length_to_px(&blur_radius, "y", font_size.height))
+                    .unwrap_or(0.0),
+            }),
+            _ => parent.text_outline,
+        };
+        let text_shadows = match attribute(|style| style.text_shadow.as_ref())
+            .map(|text_shadow| outline::parse_text_shadow(text_shadow))
+        {
+            Some(Ok(text_shadows)) => text_shadows
+                .iter()
+                .map(|text_shadow| TextShadow {
+                    offset_x: self.length_to_px(&text_shadow.offset_x, "x", font_size.width),
+                    offset_y: self.length_to_px(&text_shadow.offset_y, "y", font_size.height),
+                    blur_radius: text_shadow
+                        .blur_radius
+                        .map(|blur_radius| self.length_to_px(&blur_radius, "y", font_size.height))
+                        .unwrap_or(0.0),
+                    color: text_shadow.color.unwrap_or(text_color),
+                })
+                .collect(),
+            _ => parent.text_shadows.clone(),
+        };
+        let text_emphasis = match attribute(|style| style.text_emphasis.as_ref())
+            .map(|text_emphasis| emphasis::parse_text_emphasis(text_emphasis))
+        {
+            Some(Ok(text_emphasis)) => text_emphasis.map(|text_emphasis| TextEmphasis {
+                style: text_emphasis.style,
+                color: text_emphasis.color.unwrap_or(text_color),
+                position: text_emphasis.position,
+            }),
+            _ => parent.text_emphasis.clone(),
+        };
+        TextStyle {
+            font_family: attribute(|style| style.font_family.as_ref())
+                .cloned()
+                .or_else(|| parent.font_family.clone()),
+            font_size,
+            font_style: attribute(|style| style.font_style.as_ref())
+                .cloned()
+                .or_else(|| parent.font_style.clone()),
+            font_weight: attribute(|style| style.font_weight.as_ref())
+                .cloned()
+                .or_else(|| parent.font_weight.clone()),
+            text_decoration: attribute(|style| style.text_decoration.as_ref())
+                .cloned()
+                .or_else(|| parent.text_decoration.clone()),
+            color,
+            // The background colour is not inherited.
+            background_color: attribute(|style| style.background_color.as_ref())
+                .and_then(|background_color| Color::parse(background_color).ok()),
+            text_align: attribute(|style| style.text_align.as_ref())
+                .cloned()
+                .or_else(|| parent.text_align.clone()),
+            line_height,
+            line_padding,
+            multi_row_align: attribute(|style| style.multi_row_align.as_ref())
+                .cloned()
+                .or_else(|| parent.multi_row_align.clone()),
+            text_outline,
+            text_shadows,
+            // The opacity is not inherited, it is composed by the renderer.
+            opacity: attribute(|style| style.opacity.as_ref())
+                .and_then(|opacity| opacity.trim().parse::<f32>().ok())
+                .filter(|opacity| opacity.is_finite())
+                .map_or(1.0, |opacity| opacity.clamp(0.0, 1.0)),
+            visible: match attribute(|style| style.visibility.as_ref()).map(|v| v.as_str()) {
+                Some("hidden") => false,
+                Some("visible") => true,
+                _ => parent.visible,
+            },
+            ruby_position: attribute(|style| style.ruby_position.as_ref())
+                .cloned()
+                .or_else(|| parent.ruby_position.clone()),
+            ruby_align: attribute(|style| style.ruby_align.as_ref())
+                .cloned()
+                .or_else(|| parent.ruby_align.clone()),
+            ruby_reserve: match attribute(|style| style.ruby_reserve.as_ref()) {
+                Some(ruby_reserve) => self
+                    .parse_ruby_reserve(ruby_reserve, font_size)
+                    .unwrap_or_else(|_| parent.ruby_reserve.clone()),
+                None => parent.ruby_reserve.clone(),
+            },
+            text_emphasis,
+            text_combine: match attribute(|style| style.text_combine.as_ref()).map(|v| v.as_str()) {
+                Some("all") => true,
+                Some("none") => false,
+                _ => parent.text_combine,
+            },
+            shear: attribute(|style| style.shear.as_ref())
+                .and_then(|shear| Self::parse_shear(shear).ok())
+                .unwrap_or(parent.shear),
+        }
+    }
+
+    /// `tts:shear` percentage converted to an angle in degrees, 100% being a
+    /// quarter turn. Positive values lean the top of the glyphs forwards.
+    fn parse_shear(value: &str) -> Result<f32, String> {
+        match Length::parse(value)? {
+            Length {
+                value,
+                unit: Unit::Percentage,
+            } => Ok(value.clamp(-100.0, 100.0) * 0.9),
+            _ => Err(format!("Invalid shear: {}", value)),
+        }
+    }
+
+    /// `tts:rubyReserve`: "none" or a position ("both", "before", "after" or
+    /// "outside") and an optional length, by default half the font size.
+    fn parse_ruby_reserve(
+        &self,
+        value: &str,
+        font_size: FontSize,
+    ) -> Result<Option<RubyReserve>, String> {
+        let tokens: Vec<&str> = value.split_whitespace().collect();
+        let (position, length) = match tokens[..] {
+            ["none"] => return Ok(None),
+            [position] => (position, None),
+            [position, length] => (position, Some(Length::parse(length)?)),
+            _ => return Err(format!("Invalid ruby reserve: {}", value)),
+        };
+        if !["both", "before", "after", "outside"].contains(&position) {
+            return Err(format!("Invalid ruby reserve: {}", value));
+        }
+        Ok(Some(RubyReserve {
+            position: position.to_string(),
+            size: length
+                .map(|length| self.length_to_px(&length, "y", font_size.height))
+                .unwrap_or(font_size.height * 0.5),
+        }))
+    }
+
+    /// Computed style of a span. The ruby attributes specified inline on the
+    /// span override the referenced styles.
+    fn compute_span_style(&self, parent: &TextStyle, span: &Span, styles: &[&Style]) -> TextStyle {
+        let mut style = self.compute_text_style(parent, styles);
+        if let Some(ruby_position) = span.ruby_position.as_ref() {
+            style.ruby_position = Some(ruby_position.clone());
+        }
+        if let Some(ruby_align) = span.ruby_align.as_ref() {
+            style.ruby_align = Some(ruby_align.clone());
+        }
+        style
+    }
+
+    /// `tts:ruby` role of a span, specified inline or by its styles. It is not
+    /// inherited.
+    fn get_ruby_role<'a>(span: &'a Span, styles: &[&'a Style]) -> Option<&'a str> {
+        span.ruby.as_deref().or_else(|| {
+            Self::get_inherited_attribute(styles, |style| style.ruby.as_ref())
+                .map(|ruby| ruby.as_str())
+        })
+    }
+
+    fn push_text_run(lines: &mut [Vec<RenderedRun>], texts: &mut Vec<&str>, style: &TextStyle) {
+        if texts.is_empty() {
+            return;
+        }
+        if let Some(line) = lines.last_mut() {
+            line.push(RenderedRun {
+                text: texts.join(" "),
+                style: style.clone(),
+                ruby: None,
+            });
+        }
+        texts.clear();
+    }
+
+    /// Appends the runs of a span and its descendants to `lines`, starting a
+    /// new line for every `<br/>`.
+    fn push_span_runs(
+        &self,
+        p: &P,
+        span: &Span,
+        parent: &TextStyle,
+        lines: &mut Vec<Vec<RenderedRun>>,
+    ) {
+        let styles = self.get_span_styles(p, span);
+        if !Self::is_displayed(&styles) {
+            return;
+        }
+        let style = self.compute_span_style(parent, span, &styles);
+        if Self::get_ruby_role(span, &styles) == Some("container") {
+            if let Some(line) = lines.last_mut() {
+                line.extend(self.get_ruby_runs(p, span, &style));
+            }
+            return;
+        }
+        // The parser trims the text around the `<set>` elements.
+        let mut texts: Vec<&str> = Vec::new();
+        for child in span.children.iter().flatten() {
+            match child {
+                SpanChoice::Text(text) => texts.push(text),
+                SpanChoice::Set(_) => (),
+                SpanChoice::Span(child) => {
+                    Self::push_text_run(lines, &mut texts, &style);
+                    self.push_span_runs(p, child, &style, lines);
+                }
+                SpanChoice::Br(_) => {
+                    Self::push_text_run(lines, &mut texts, &style);
+                    lines.push(Vec::new());
+                }
+            }
+        }
+        Self::push_text_run(lines, &mut texts, &style);
+    }
+
+    /// Bases and annotations of a ruby container, looking inside the base and
+    /// text containers. Delimiters are only meant for renderers without ruby
+    /// support and are skipped.
+    fn collect_ruby_parts(
+        &self,
+        p: &P,
+        span: &Span,
+        parent: &TextStyle,
+        bases: &mut Vec<(String, TextStyle)>,
+        texts: &mut Vec<(String, TextStyle)>,
+    ) {
+        for child in span.get_child_spans() {
+            let styles = self.get_span_styles(p, child);
+            if !Self::is_displayed(&styles) {
+                continue;
+            }
+            let style = self.compute_span_style(parent, child, &styles);
+            match Self::get_ruby_role(child, &styles) {
+                Some("base") => bases.push((child.get_text().unwrap_or_default(), style)),
+                Some("text") => texts.push((child.get_text().unwrap_or_default(), style)),
+                Some("baseContainer") | Some("textContainer") => {
+                    self.collect_ruby_parts(p, child, &style, bases, texts)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Runs of a ruby container, every base with its annotation. When the
+    /// number of bases and annotations differ (group ruby) the bases are
+    /// joined in a single run annotated with all the text.
+    fn get_ruby_runs(&self, p: &P, container: &Span, style: &TextStyle) -> Vec<RenderedRun> {
+        let mut bases: Vec<(String, TextStyle)> = Vec::new();
+        let mut texts: Vec<(String, TextStyle)> = Vec::new();
+        self.collect_ruby_parts(p, container, style, &mut bases, &mut texts);
+        if bases.is_empty() {
+            return Vec::new();
+        }
+        if bases.len() == texts.len() {
+            return bases
+                .into_iter()
+                .zip(texts)
+                .map(|((text, style), (ruby_text, ruby_style))| RenderedRun {
+                    text,
+                    style,
+                    ruby: Some(RenderedRuby {
+                        text: ruby_text,
+                        style: ruby_style,
+                    }),
+                })
+                .collect();
+        }
+        let ruby = texts.first().map(|(_, ruby_style)| RenderedRuby {
+            text: texts.iter().map(|(text, _)| text.as_str()).collect(),
+            style: ruby_style.clone(),
+        });
+        vec![RenderedRun {
+            text: bases.iter().map(|(text, _)| text.as_str()).collect(),
+            style: bases[0].1.clone(),
+            ruby,
+        }]
+    }
+
+    /// Structured representation of a subtitle: region geometry and computed
+    /// styles in display pixels, with the user personalisation applied. `None`
+    /// when the subtitle, an ancestor or its region has `tts:display="none"`.
+    fn build_subtitle(&self, element: &SubtitleElement) -> Option<RenderedSubtitle> {
+        let style_levels = self.get_style_levels(element);
+        if !style_levels.iter().all(|styles| Self::is_displayed(styles)) {
+            return None;
+        }
+        let style = style_levels
+            .iter()
+            .fold(self.get_initial_text_style(), |parent, styles| {
+                self.compute_text_style(&parent, styles)
+            });
+        let mut lines: Vec<Vec<RenderedRun>> = vec![Vec::new()];
+        let mut image: Option<RenderedImage> = None;
+        match element {
+            SubtitleElement::Paragraph(_, p) => {
+                for child in p.children.iter().flatten() {
+                    match child {
+                        Choice::Span(span) => self.push_span_runs(p, span, &style, &mut lines),
+                        Choice::Br(_) => lines.push(Vec::new()),
+                        Choice::Set(_) => (),
+                    }
+                }
+            }
+            SubtitleElement::Image(_, div) => {
+                lines.clear();
+                // Images that can not be resolved are not shown, the errors
+                // of the document are reported when it is loaded.
+                image = images::resolve_image(self.tt.as_ref()?, div, self.image_resolver.as_ref())
+                    .ok()
+                    .map(|image| RenderedImage {
+                        bitmap: self.dvb_bitmaps.get(&element.get_id()).cloned(),
+                        ..image
+                    });
+            }
+        }
+        let mut subtitle = RenderedSubtitle {
+            id: element.get_id(),
+            region: element
+                .get_region_id()
+                .and_then(|region_id| self.build_region(region_id)),
+            style,
+            lines,
+            image,
+            background_removed: false,
+        };
+        self.personalization
+            .apply(&mut subtitle, self.element_size.height as f32);
+        Some(subtitle)
+    }
+
+    /// External image URLs of the subtitles on screen, which showing them
+    /// again would resolve.
+    pub fn get_visible_image_urls(&self) -> Vec<String> {
+        self.visible_subtitles
+            .iter()
+            .filter_map(|index| match self.get_subtitle_element(*index)? {
+                SubtitleElement::Image(_, div) => images::get_external_url(div),
+                SubtitleElement::Paragraph(..) => None,
+            })
+            .map(|url| url.to_string())
+            .collect()
+    }
+
+    /// Subtitles currently on screen, in display order.
+    pub fn get_active_subtitles(&self) -> Vec<RenderedSubtitle> {
+        self.visible_subtitles
+            .iter()
+            .filter_map(|index| self.get_subtitle_element(*index))
+            .filter_map(|element| self.build_subtitle(&element))
+            .collect()
+    }
+
+    fn show_subtile(&self, element: &SubtitleElement) {
+        let view = match self.view.as_ref() {
+            Some(view) => view,
+            None => return,
+        };
+        let id = element.get_id();
+        if self.tt.is_some() && !view.exist_subtitle(&id) {
+            if let Some(subtitle) = self.build_subtitle(element) {
+                view.show_subtitle(&id, &render::to_html(&subtitle, &self.default_styles));
+            }
+        }
+    }
+    fn hide_subtile(&self, element: &SubtitleElement) {
+        if let Some(view) = self.view.as_ref() {
+            view.hide_subtitle(&element.get_id());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::recording_view::RecordingView;
+    use super::*;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:timeBase="media" ttp:cellResolution="50 30" xml:lang="en">
+  <tt:head>
+    <tt:styling>
+      <tt:style xml:id="s1" tts:color="yellow" tts:fontSize="100%"/>
+      <tt:style xml:id="s2" tts:color="red"/>
+    </tt:styling>
+    <tt:layout>
+      <tt:region xml:id="r1" tts:origin="10% 80%" tts:extent="80% 15%"/>
+    </tt:layout>
+  </tt:head>
+  <tt:body>
+    <tt:div region="r1">
+      <tt:p xml:id="p1" begin="00:00:01.000" end="00:00:02.000" style="s1"><tt:span>First</tt:span></tt:p>
+      <tt:p xml:id="p2" begin="00:00:02.500" end="00:00:04.000"><tt:span>Second</tt:span><tt:br/><tt:span style="s2">line</tt:span></tt:p>
+    </tt:div>
+  </tt:body>
+</tt:tt>"#;
+
+    fn load(xml: &str) -> Subtitles {
+        let mut subtitles = Subtitles::new();
+        subtitles.set_element_size(1000, 600);
+        subtitles.load(xml).unwrap();
+        subtitles
+    }
+
+    #[test]
+    fn parses_clock_times() {
+        assert_eq!(time_to_ms("00:01:02.500"), Ok(62500));
+        assert_eq!(time_to_ms("01:00:00"), Ok(3600000));
+        assert_eq!(time_to_ms("00:00:01.5"), Ok(1500));
+        assert_eq!(time_to_ms("00:00:01.25"), Ok(1250));
+        assert_eq!(time_to_ms("00:00:01.005"), Ok(1005));
+        assert_eq!(time_to_ms("00:00:01.0059"), Ok(1005));
+        assert!(time_to_ms("00:00:01.").is_err());
+        assert!(time_to_ms("00:00:01.-5").is_err());
+        assert!(time_to_ms("01:00").is_err());
+        assert!(time_to_ms("aa:00:00").is_err());
+        // Wall clock times beyond the range of 32 bits.
+        assert_eq!(time_to_ms("480000:00:00.000"), Ok(1728000000000));
+        assert!(time_to_ms("9223372036854775807:00:00").is_err());
+    }
+
+    #[test]
+    fn parses_time_expressions() {
+        assert_eq!(time_expression_to_ms("1.5s"), Ok(1500));
+        assert_eq!(time_expression_to_ms("200ms"), Ok(200));
+        assert_eq!(time_expression_to_ms("2m"), Ok(120000));
+        assert_eq!(time_expression_to_ms("00:00:01.000"), Ok(1000));
+        assert!(time_expression_to_ms("xs").is_err());
+    }
+
+    #[test]
+    fn reports_invalid_documents() {
+        let mut subtitles = Subtitles::new();
+        assert!(subtitles.load("<tt:tt").is_err());
+        assert!(subtitles.tt.is_none());
+    }
+
+    #[test]
+    fn shows_and_hides_subtitles_on_the_view() {
+        let mut subtitles = load(DOCUMENT);
+        let view = RecordingView::default();
+        subtitles.set_view(Some(Box::new(view.clone())));
+        let mut screens: Vec<(i64, Vec<String>)> = Vec::new();
+        for ms in (0..5000).step_by(100) {
+            subtitles.update_subtitles_for_ms(ms);
+            if ms % 500 == 0 {
+                screens.push((ms, view.shown.lock().unwrap().clone()));
+            }
+        }
+        let on_screen = |ms: i64| -> Vec<String> {
+            screens
+                .iter()
+                .find(|(screen_ms, _)| *screen_ms == ms)
+                .map(|(_, shown)| shown.clone())
+                .unwrap()
+        };
+        assert!(on_screen(500).is_empty());
+        assert_eq!(on_screen(1500), vec!["p1"]);
+        assert!(on_screen(2000).is_empty());
+        assert_eq!(on_screen(3000), vec!["p2"]);
+        assert!(on_screen(4500).is_empty());
+        let shows = view
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| event.starts_with("show"))
+            .count();
+        assert_eq!(shows, 2);
+    }
+
+    #[test]
+    fn reloading_replaces_the_document() {
+        let mut subtitles = load(DOCUMENT);
+        let view = RecordingView::default();
+        subtitles.set_view(Some(Box::new(view.clone())));
+        subtitles.update_subtitles_for_ms(1200);
+        subtitles.load(DOCUMENT).unwrap();
+        assert!(view.shown.lock().unwrap().is_empty());
+        subtitles.update_subtitles_for_ms(1300);
+        subtitles.update_subtitles_for_ms(2100);
+        let events = view.events.lock().unwrap().clone();
+        assert_eq!(events, vec!["show p1", "hide p1", "show p1", "hide p1"]);
+    }
+
+    #[test]
+    fn renders_the_subtitles_at_a_time() {
+        let mut subtitles = load(DOCUMENT);
+        assert!(subtitles.get_subtitles_at(500).is_empty());
+        let active = subtitles.get_subtitles_at(3000);
+        assert_eq!(active.len(), 1);
+        let subtitle = &active[0];
+        assert_eq!(subtitle.id, "p2");
+        let region = subtitle.region.as_ref().unwrap();
+        assert_eq!(region.geometry.left, 100.0);
+        assert_eq!(region.geometry.top, 480.0);
+        assert_eq!(subtitle.lines.len(), 2);
+        assert_eq!(subtitle.lines[1][0].text, "line");
+        assert_eq!(
+            subtitle.lines[1][0].style.color,
+            Some(Color::rgb(255, 0, 0))
+        );
+        // 600px / 30 rows.
+        assert_eq!(subtitle.lines[0][0].style.font_size.height, 20.0);
+    }
+
+    #[test]
+    fn maps_media_time_to_the_document() {
+        let mut subtitles = load(DOCUMENT);
+        subtitles.set_time_offset(10000);
+        subtitles.set_subtitle_delay(500);
+        assert!(subtitles.get_subtitles_at(3000).is_empty());
+        assert_eq!(subtitles.get_subtitles_at(13500)[0].id, "p2");
+        let timed_subtitles = subtitles.get_timed_subtitles();
+        assert_eq!(timed_subtitles[0].begin, 11500);
+        assert_eq!(timed_subtitles[0].end, 12500);
+        subtitles.set_time_offset(0);
+        subtitles.set_subtitle_delay(0);
+        subtitles.set_time_scale(2.0).unwrap();
+        assert_eq!(subtitles.get_subtitles_at(5500)[0].id, "p2");
+        assert!(subtitles.set_time_scale(0.0).is_err());
+        assert!(subtitles.set_playback_rate(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn schedules_media_times_since_the_epoch() {
+        let mut subtitles = load(DOCUMENT);
+        let view = RecordingView::default();
+        subtitles.set_view(Some(Box::new(view.clone())));
+        let offset = 1_700_000_000_000;
+        subtitles.set_time_offset(offset);
+        subtitles.update_subtitles_for_ms(offset + 1200);
+        assert_eq!(*view.shown.lock().unwrap(), vec!["p1"]);
+    }
+
+    #[test]
+    fn resyncs_the_view_when_the_delay_changes() {
+        let mut subtitles = load(DOCUMENT);
+        let view = RecordingView::default();
+        subtitles.set_view(Some(Box::new(view.clone())));
+        subtitles.update_subtitles_for_ms(1200);
+        assert_eq!(*view.shown.lock().unwrap(), vec!["p1"]);
+        subtitles.set_subtitle_delay(-1500);
+        assert_eq!(*view.shown.lock().unwrap(), vec!["p2"]);
+        subtitles.set_subtitle_delay(1000);
+        assert!(view.shown.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn does_not_skip_subtitles_at_a_faster_rate() {
+        let mut subtitles = load(DOCUMENT);
+        let view = RecordingView::default();
+        subtitles.set_view(Some(Box::new(view.clone())));
+        subtitles.set_playback_rate(4.0).unwrap();
+        // Updates every 250ms of wall clock, 1s of media.
+        subtitles.update_subtitles_for_ms(600);
+        subtitles.update_subtitles_for_ms(1600);
+        assert_eq!(*view.shown.lock().unwrap(), vec!["p1"]);
+    }
+
+    #[test]
+    fn computes_the_duration() {
+        assert_eq!(load(DOCUMENT).get_duration_ms(), 4000);
+    }
+}
@@ -0,0 +1,632 @@
+// Fragmented MP4 (ISOBMFF) subtitle tracks, as delivered by DASH: the init
+// segment describes the track (`stpp` TTML or `wvtt` WebVTT samples) and each
+// media segment carries `moof` boxes with the timing (`tfdt`, `trun`) of the
+// samples in the following `mdat`. The samples are appended to the engine, so
+// subtitles can be handled without Media Source Extensions (e.g. HbbTV).
+//
+// The times inside `stpp` documents are on the timeline of the track, like
+// the decode times of their samples, so the documents are appended as they
+// are.
+use super::timeline::ticks_to_ms;
+use super::webvtt::{self, VttCue};
+use super::Subtitles;
+
+const TRACK_FRAGMENT_BASE_DATA_OFFSET: u32 = 0x1;
+const TRACK_FRAGMENT_SAMPLE_DESCRIPTION_INDEX: u32 = 0x2;
+const TRACK_FRAGMENT_DEFAULT_SAMPLE_DURATION: u32 = 0x8;
+const TRACK_FRAGMENT_DEFAULT_SAMPLE_SIZE: u32 = 0x10;
+const TRACK_FRAGMENT_DEFAULT_SAMPLE_FLAGS: u32 = 0x20;
+const TRACK_RUN_DATA_OFFSET: u32 = 0x1;
+const TRACK_RUN_FIRST_SAMPLE_FLAGS: u32 = 0x4;
+const TRACK_RUN_SAMPLE_DURATION: u32 = 0x100;
+const TRACK_RUN_SAMPLE_SIZE: u32 = 0x200;
+const TRACK_RUN_SAMPLE_FLAGS: u32 = 0x400;
+const TRACK_RUN_SAMPLE_COMPOSITION_TIME_OFFSET: u32 = 0x800;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleCodec {
+    /// TTML documents (ISO/IEC 14496-30).
+    Stpp,
+    /// WebVTT cues in boxes (ISO/IEC 14496-30).
+    Wvtt,
+}
+
+/// Subtitle track of an init segment.
+#[derive(Debug, Clone)]
+pub struct Mp4Track {
+    pub track_id: u32,
+    pub codec: SubtitleCodec,
+    /// Ticks per second of the sample times.
+    pub timescale: u32,
+    /// Defaults of the `trex` box for the fragments.
+    pub default_sample_duration: u32,
+    pub default_sample_size: u32,
+}
+
+/// A sample of a media segment with its presentation interval.
+pub struct Mp4Sample<'a> {
+    pub begin: i64,
+    pub end: i64,
+    pub data: &'a [u8],
+}
+
+#[derive(Clone)]
+struct Mp4Box<'a> {
+    kind: [u8; 4],
+    /// Position of the box in the segment.
+    offset: usize,
+    /// Position of the payload in the segment.
+    payload_offset: usize,
+    payload: &'a [u8],
+}
+
+fn get_kind_name(kind: &[u8; 4]) -> String {
+    String::from_utf8_lossy(kind).to_string()
+}
+
+/// Children boxes of `data`, which starts at `offset` of the segment.
+fn read_boxes(data: &[u8], offset: usize) -> Result<Vec<Mp4Box<'_>>, String> {
+    let mut boxes = Vec::new();
+    let mut reader = Reader::new(data);
+    while reader.remaining() > 0 {
+        let start = reader.position;
+        let size = reader.read_u32()? as u64;
+        let kind: [u8; 4] = reader.read_bytes(4)?.try_into().unwrap();
+        let size = match size {
+            0 => (data.len() - start) as u64,
+            1 => reader.read_u64()?,
+            _ => size,
+        };
+        let header_size = reader.position - start;
+        if size < header_size as u64 || size > (data.len() - start) as u64 {
+            return Err(format!("Invalid size of box {}", get_kind_name(&kind)));
+        }
+        let end = start + size as usize;
+        boxes.push(Mp4Box {
+            kind,
+            offset: offset + start,
+            payload_offset: offset + reader.position,
+            payload: &data[reader.position..end],
+        });
+        reader.position = end;
+    }
+    Ok(boxes)
+}
+
+fn find_box<'a, 'b>(boxes: &'b [Mp4Box<'a>], kind: &[u8; 4]) -> Option<&'b Mp4Box<'a>> {
+    boxes.iter().find(|mp4_box| &mp4_box.kind == kind)
+}
+
+fn get_children<'a>(mp4_box: &Mp4Box<'a>) -> Result<Vec<Mp4Box<'a>>, String> {
+    read_boxes(mp4_box.payload, mp4_box.payload_offset)
+}
+
+/// Box at `path` below `boxes`, e.g. `[b"mdia", b"minf"]`.
+fn find_path<'a>(boxes: &[Mp4Box<'a>], path: &[&[u8; 4]]) -> Result<Option<Mp4Box<'a>>, String> {
+    let (first, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return Ok(None),
+    };
+    let mp4_box = match find_box(boxes, first) {
+        Some(mp4_box) => mp4_box,
+        None => return Ok(None),
+    };
+    if rest.is_empty() {
+        return Ok(Some(mp4_box.clone()));
+    }
+    find_path(&get_children(mp4_box)?, rest)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, position: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], String> {
+        if self.remaining() < length {
+            return Err("Truncated box".to_string());
+        }
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_be_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Version and flags of a full box.
+    fn read_version_and_flags(&mut self) -> Result<(u8, u32), String> {
+        let value = self.read_u32()?;
+        Ok(((value >> 24) as u8, value & 0xffffff))
+    }
+
+    fn skip(&mut self, length: usize) -> Result<(), String> {
+        self.read_bytes(length).map(|_| ())
+    }
+}
+
+fn read_track_id(tkhd: &Mp4Box) -> Result<u32, String> {
+    let mut reader = Reader::new(tkhd.payload);
+    let (version, _) = reader.read_version_and_flags()?;
+    // Creation and modification times.
+    reader.skip(if version == 1 { 16 } else { 8 })?;
+    reader.read_u32()
+}
+
+fn read_timescale(mdhd: &Mp4Box) -> Result<u32, String> {
+    let mut reader = Reader::new(mdhd.payload);
+    let (version, _) = reader.read_version_and_flags()?;
+    reader.skip(if version == 1 { 16 } else { 8 })?;
+    reader.read_u32()
+}
+
+fn read_codec(stsd: &Mp4Box) -> Result<Option<SubtitleCodec>, String> {
+    let mut reader = Reader::new(stsd.payload);
+    reader.read_version_and_flags()?;
+    reader.read_u32()?;
+    let entries = read_boxes(
+        &stsd.payload[reader.position..],
+        stsd.payload_offset + reader.position,
+    )?;
+    Ok(entries.first().and_then(|entry| match &entry.kind {
+        b"stpp" => Some(SubtitleCodec::Stpp),
+        b"wvtt" => Some(SubtitleCodec::Wvtt),
+        _ => None,
+    }))
+}
+
+/// First `stpp` or `wvtt` track of an init segment.
+pub fn parse_init_segment(data: &[u8]) -> Result<Mp4Track, String> {
+    let boxes = read_boxes(data, 0)?;
+    let moov = find_box(&boxes, b"moov").ok_or("Missing moov box")?;
+    let moov_children = get_children(moov)?;
+    for trak in moov_children
+        .iter()
+        .filter(|mp4_box| &mp4_box.kind == b"trak")
+    {
+        let trak_children = get_children(trak)?;
+        let stsd = match find_path(&trak_children, &[b"mdia", b"minf", b"stbl", b"stsd"])? {
+            Some(stsd) => stsd,
+            None => continue,
+        };
+        let codec = match read_codec(&stsd)? {
+            Some(codec) => codec,
+            None => continue,
+        };
+        let tkhd = find_box(&trak_children, b"tkhd").ok_or("Missing tkhd box")?;
+        let mdhd = find_path(&trak_children, &[b"mdia", b"mdhd"])?.ok_or("Missing mdhd box")?;
+        let mut track = Mp4Track {
+            track_id: read_track_id(tkhd)?,
+            codec,
+            timescale: read_timescale(&mdhd)?,
+            default_sample_duration: 0,
+            default_sample_size: 0,
+        };
+        if track.timescale == 0 {
+            return Err("Invalid timescale: 0".to_string());
+        }
+        if let Some(mvex) = find_box(&moov_children, b"mvex") {
+            for trex in get_children(mvex)?
+                .iter()
+                .filter(|mp4_box| &mp4_box.kind == b"trex")
+            {
+                let mut reader = Reader::new(trex.payload);
+                reader.read_version_and_flags()?;
+                if reader.read_u32()? != track.track_id {
+                    continue;
+                }
+                // Default sample description index.
+                reader.read_u32()?;
+                track.default_sample_duration = reader.read_u32()?;
+                track.default_sample_size = reader.read_u32()?;
+            }
+        }
+        return Ok(track);
+    }
+    Err("No stpp or wvtt track".to_string())
+}
+
+/// Defaults of a track fragment, from `tfhd` over the ones of `trex`.
+struct FragmentDefaults {
+    base_data_offset: usize,
+    sample_duration: u32,
+    sample_size: u32,
+}
+
+fn read_tfhd(
+    tfhd: &Mp4Box,
+    track: &Mp4Track,
+    moof_offset: usize,
+) -> Result<Option<FragmentDefaults>, String> {
+    let mut reader = Reader::new(tfhd.payload);
+    let (_, flags) = reader.read_version_and_flags()?;
+    if reader.read_u32()? != track.track_id {
+        return Ok(None);
+    }
+    let mut defaults = FragmentDefaults {
+        // Fragments without base offset are relative to their `moof`.
+        base_data_offset: moof_offset,
+        sample_duration: track.default_sample_duration,
+        sample_size: track.default_sample_size,
+    };
+    if flags & TRACK_FRAGMENT_BASE_DATA_OFFSET != 0 {
+        defaults.base_data_offset = usize::try_from(reader.read_u64()?)
+            .map_err(|_| "Invalid base data offset".to_string())?;
+    }
+    if flags & TRACK_FRAGMENT_SAMPLE_DESCRIPTION_INDEX != 0 {
+        reader.read_u32()?;
+    }
+    if flags & TRACK_FRAGMENT_DEFAULT_SAMPLE_DURATION != 0 {
+        defaults.sample_duration = reader.read_u32()?;
+    }
+    if flags & TRACK_FRAGMENT_DEFAULT_SAMPLE_SIZE != 0 {
+        defaults.sample_size = reader.read_u32()?;
+    }
+    if flags & TRACK_FRAGMENT_DEFAULT_SAMPLE_FLAGS != 0 {
+        reader.read_u32()?;
+    }
+    Ok(Some(defaults))
+}
+
+fn read_tfdt(tfdt: &Mp4Box) -> Result<u64, String> {
+    let mut reader = Reader::new(tfdt.payload);
+    let (version, _) = reader.read_version_and_flags()?;
+    if version == 1 {
+        reader.read_u64()
+    } else {
+        reader.read_u32().map(u64::from)
+    }
+}
+
+/// Samples of the track in a media segment, in decode order.
+pub fn parse_media_segment<'a>(
+    data: &'a [u8],
+    track: &Mp4Track,
+) -> Result<Vec<Mp4Sample<'a>>, String> {
+    let mut samples = Vec::new();
+    for moof in read_boxes(data, 0)?
+        .iter()
+        .filter(|mp4_box| &mp4_box.kind == b"moof")
+    {
+        for traf in get_children(moof)?
+            .iter()
+            .filter(|mp4_box| &mp4_box.kind == b"traf")
+        {
+            let traf_children = get_children(traf)?;
+            let tfhd = find_box(&traf_children, b"tfhd").ok_or("Missing tfhd box")?;
+            let defaults = match read_tfhd(tfhd, track, moof.offset)? {
+                Some(defaults) => defaults,
+                None => continue,
+            };
+            let mut decode_time = match find_box(&traf_children, b"tfdt") {
+                Some(tfdt) => read_tfdt(tfdt)? as i128,
+                None => 0,
+            };
+            let mut data_offset = defaults.base_data_offset;
+            for trun in traf_children
+                .iter()
+                .filter(|mp4_box| &mp4_box.kind == b"trun")
+            {
+                let mut reader = Reader::new(trun.payload);
+                let (version, flags) = reader.read_version_and_flags()?;
+                let sample_count = reader.read_u32()? as usize;
+                if flags & TRACK_RUN_DATA_OFFSET != 0 {
+                    let offset = reader.read_u32()? as i32 as i64;
+                    data_offset = i64::try_from(defaults.base_data_offset)
+                        .ok()
+                        .and_then(|base| base.checked_add(offset))
+                        .and_then(|offset| usize::try_from(offset).ok())
+                        .ok_or("Invalid data offset")?;
+                }
+                if flags & TRACK_RUN_FIRST_SAMPLE_FLAGS != 0 {
+                    reader.read_u32()?;
+                }
+                // Samples without fields in the run take at least a byte of
+                // the segment, unless they are empty.
+                let sample_fields = [
+                    TRACK_RUN_SAMPLE_DURATION,
+                    TRACK_RUN_SAMPLE_SIZE,
+                    TRACK_RUN_SAMPLE_FLAGS,
+                    TRACK_RUN_SAMPLE_COMPOSITION_TIME_OFFSET,
+                ]
+                .iter()
+                .filter(|field| flags & **field != 0)
+                .count();
+                let max_sample_count = match sample_fields {
+                    0 => data.len(),
+                    _ => reader.remaining() / (sample_fields * 4),
+                };
+                if sample_count > max_sample_count {
+                    return Err(format!("Invalid sample count: {}", sample_count));
+                }
+                for _ in 0..sample_count {
+                    let duration = if flags & TRACK_RUN_SAMPLE_DURATION != 0 {
+                        reader.read_u32()?
+                    } else {
+                        defaults.sample_duration
+                    };
+                    let size = if flags & TRACK_RUN_SAMPLE_SIZE != 0 {
+                        reader.read_u32()?
+                    } else {
+                        defaults.sample_size
+                    } as usize;
+                    if flags & TRACK_RUN_SAMPLE_FLAGS != 0 {
+                        reader.read_u32()?;
+                    }
+                    let composition_offset =
+                        if flags & TRACK_RUN_SAMPLE_COMPOSITION_TIME_OFFSET != 0 {
+                            let offset = reader.read_u32()?;
+                            if version == 0 {
+                                offset as i128
+                            } else {
+                                offset as i32 as i128
+                            }
+                        } else {
+                            0
+                        };
+                    let sample_end = data_offset
+                        .checked_add(size)
+                        .ok_or("Sample data out of the segment")?;
+                    let sample_data = data
+                        .get(data_offset..sample_end)
+                        .ok_or("Sample data out of the segment")?;
+                    let begin = decode_time + composition_offset;
+                    samples.push(Mp4Sample {
+                        begin: ticks_to_ms(begin, track.timescale.into()),
+                        end: ticks_to_ms(begin + duration as i128, track.timescale.into()),
+                        data: sample_data,
+                    });
+                    data_offset = sample_end;
+                    decode_time += duration as i128;
+                }
+            }
+        }
+    }
+    Ok(samples)
+}
+
+/// Cues of a `wvtt` sample: `vttc` boxes with the payload (`payl`) and the
+/// identifier (`iden`). An empty sample is a `vtte` box. The settings
+/// (`sttg`) are skipped, like the ones of WebVTT files: the cues are shown
+/// centred at the bottom.
+fn read_wvtt_cues(sample: &Mp4Sample) -> Result<Vec<VttCue>, String> {
+    let mut cues = Vec::new();
+    for vttc in read_boxes(sample.data, 0)?
+        .iter()
+        .filter(|mp4_box| &mp4_box.kind == b"vttc")
+    {
+        let children = get_children(vttc)?;
+        let text = |kind: &[u8; 4]| {
+            find_box(&children, kind)
+                .map(|mp4_box| String::from_utf8_lossy(mp4_box.payload).to_string())
+        };
+        cues.push(VttCue {
+            id: text(b"iden"),
+            begin: sample.begin,
+            end: sample.end,
+            payload: text(b"payl").unwrap_or_default(),
+        });
+    }
+    Ok(cues)
+}
+
+impl Subtitles {
+    /// Reads the subtitle track of a DASH init segment, for the following
+    /// media segments.
+    pub fn load_init_segment(&mut self, data: &[u8]) -> Result<(), String> {
+        self.mp4_track = Some(parse_init_segment(data)?);
+        Ok(())
+    }
+
+    /// Appends the samples of a media segment of the track of the init
+    /// segment.
+    pub fn append_media_segment(&mut self, data: &[u8]) -> Result<(), String> {
+        let track = self.mp4_track.clone().ok_or("No init segment loaded")?;
+        let samples = parse_media_segment(data, &track)?;
+        match track.codec {
+            SubtitleCodec::Stpp => {
+                for sample in samples.iter().filter(|sample| !sample.data.is_empty()) {
+                    let xml = std::str::from_utf8(sample.data)
+                        .map_err(|_| "Invalid UTF-8 in stpp sample".to_string())?;
+                    self.append_document(xml)?;
+                }
+            }
+            SubtitleCodec::Wvtt => {
+                let mut cues = Vec::new();
+                for sample in samples.iter() {
+                    cues.extend(read_wvtt_cues(sample)?);
+                }
+                if !cues.is_empty() {
+                    self.append_document(&webvtt::to_ttml(&cues))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn full_box(kind: &[u8; 4], version: u8, flags: u32, payload: &[u8]) -> Vec<u8> {
+        let mut data = (((version as u32) << 24) | flags).to_be_bytes().to_vec();
+        data.extend_from_slice(payload);
+        mp4_box(kind, &data)
+    }
+
+    fn u32s(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect()
+    }
+
+    fn init_segment(codec: &[u8; 4]) -> Vec<u8> {
+        let tkhd = full_box(b"tkhd", 0, 0, &u32s(&[0, 0, 7, 0, 0]));
+        let mdhd = full_box(b"mdhd", 0, 0, &u32s(&[0, 0, 1000, 0]));
+        let stsd = full_box(
+            b"stsd",
+            0,
+            0,
+            &[u32s(&[1]), mp4_box(codec, &[0; 8])].concat(),
+        );
+        let stbl = mp4_box(b"stbl", &stsd);
+        let minf = mp4_box(b"minf", &stbl);
+        let mdia = mp4_box(b"mdia", &[mdhd, minf].concat());
+        let trak = mp4_box(b"trak", &[tkhd, mdia].concat());
+        let trex = full_box(b"trex", 0, 0, &u32s(&[7, 1, 2000, 0, 0]));
+        let mvex = mp4_box(b"mvex", &trex);
+        [
+            mp4_box(b"ftyp", b"iso6"),
+            mp4_box(b"moov", &[trak, mvex].concat()),
+        ]
+        .concat()
+    }
+
+    /// One fragment with a sample per entry, `(duration, data)`, decoded from
+    /// `decode_time`.
+    fn media_segment(decode_time: u64, samples: &[(Option<u32>, Vec<u8>)]) -> Vec<u8> {
+        let tfhd = full_box(b"tfhd", 0, 0x020000, &u32s(&[7]));
+        let tfdt = full_box(b"tfdt", 1, 0, &decode_time.to_be_bytes());
+        let has_durations = samples.iter().all(|(duration, _)| duration.is_some());
+        let flags = TRACK_RUN_DATA_OFFSET
+            | TRACK_RUN_SAMPLE_SIZE
+            | if has_durations {
+                TRACK_RUN_SAMPLE_DURATION
+            } else {
+                0
+            };
+        let build = |data_offset: u32| {
+            let mut entries = u32s(&[samples.len() as u32, data_offset]);
+            for (duration, data) in samples {
+                if has_durations {
+                    entries.extend(u32s(&[duration.unwrap()]));
+                }
+                entries.extend(u32s(&[data.len() as u32]));
+            }
+            let trun = full_box(b"trun", 0, flags, &entries);
+            let traf = mp4_box(b"traf", &[tfhd.clone(), tfdt.clone(), trun].concat());
+            mp4_box(
+                b"moof",
+                &[full_box(b"mfhd", 0, 0, &u32s(&[1])), traf].concat(),
+            )
+        };
+        let moof_size = build(0).len() as u32;
+        let mdat: Vec<u8> = samples.iter().flat_map(|(_, data)| data.clone()).collect();
+        [build(moof_size + 8), mp4_box(b"mdat", &mdat)].concat()
+    }
+
+    fn vttc(payload: &str) -> Vec<u8> {
+        mp4_box(b"vttc", &mp4_box(b"payl", payload.as_bytes()))
+    }
+
+    #[test]
+    fn parses_the_track_of_the_init_segment() {
+        let track = parse_init_segment(&init_segment(b"wvtt")).unwrap();
+        assert_eq!(track.track_id, 7);
+        assert_eq!(track.codec, SubtitleCodec::Wvtt);
+        assert_eq!(track.timescale, 1000);
+        assert_eq!(track.default_sample_duration, 2000);
+        assert!(parse_init_segment(&init_segment(b"avc1")).is_err());
+        assert!(parse_init_segment(&[0, 0, 0, 20, b'm', b'o', b'o', b'v']).is_err());
+    }
+
+    #[test]
+    fn appends_wvtt_samples() {
+        let mut subtitles = Subtitles::new();
+        assert!(subtitles.append_media_segment(&[]).is_err());
+        subtitles.load_init_segment(&init_segment(b"wvtt")).unwrap();
+        let segment = media_segment(
+            60000,
+            &[
+                (Some(1500), vttc("Hello <i>world</i>")),
+                (Some(500), mp4_box(b"vtte", &[])),
+                (Some(2000), [vttc("One"), vttc("Two")].concat()),
+            ],
+        );
+        subtitles.append_media_segment(&segment).unwrap();
+        let timed_subtitles = subtitles.get_timed_subtitles();
+        assert_eq!(timed_subtitles.len(), 2);
+        assert_eq!(
+            (timed_subtitles[0].begin, timed_subtitles[0].end),
+            (60000, 61500)
+        );
+        assert_eq!(timed_subtitles[0].subtitle.lines[0][1].text, "world");
+        assert_eq!(
+            (timed_subtitles[1].begin, timed_subtitles[1].end),
+            (62000, 64000)
+        );
+        assert_eq!(timed_subtitles[1].subtitle.lines.len(), 2);
+    }
+
+    #[test]
+    fn appends_stpp_samples() {
+        let document = |id: &str, begin: &str, end: &str| {
+            format!(
+                r#"<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" ttp:timeBase="media" xmlns:ttp="http://www.w3.org/ns/ttml#parameter"><tt:head/><tt:body><tt:div><tt:p xml:id="{}" begin="{}" end="{}"><tt:span>{}</tt:span></tt:p></tt:div></tt:body></tt:tt>"#,
+                id, begin, end, id
+            )
+            .into_bytes()
+        };
+        let mut subtitles = Subtitles::new();
+        subtitles.load_init_segment(&init_segment(b"stpp")).unwrap();
+        // Durations from the defaults of `trex`.
+        let segment = media_segment(
+            0,
+            &[
+                (None, document("a", "00:00:00.500", "00:00:01.500")),
+                (None, document("b", "00:00:02.500", "00:00:03.500")),
+            ],
+        );
+        subtitles.append_media_segment(&segment).unwrap();
+        let samples = parse_media_segment(&segment, subtitles.mp4_track.as_ref().unwrap()).unwrap();
+        assert_eq!((samples[1].begin, samples[1].end), (2000, 4000));
+        let ids: Vec<String> = subtitles
+            .get_timed_subtitles()
+            .iter()
+            .map(|timed_subtitle| timed_subtitle.subtitle.id.clone())
+            .collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn rejects_invalid_runs() {
+        let track = parse_init_segment(&init_segment(b"wvtt")).unwrap();
+        let segment = |flags: u32, entries: &[u32]| {
+            let tfhd = full_box(b"tfhd", 0, 0x020000, &u32s(&[7]));
+            let trun = full_box(b"trun", 0, flags, &u32s(entries));
+            mp4_box(b"moof", &mp4_box(b"traf", &[tfhd, trun].concat()))
+        };
+        // More samples than entries in the run.
+        let data = segment(TRACK_RUN_SAMPLE_SIZE, &[u32::MAX, 0]);
+        assert!(parse_media_segment(&data, &track).is_err());
+        // Data offset before the start of the segment.
+        let data = segment(TRACK_RUN_DATA_OFFSET, &[1, -100i32 as u32]);
+        assert!(parse_media_segment(&data, &track).is_err());
+        // Sample that ends past the end of the segment.
+        let data = segment(TRACK_RUN_SAMPLE_SIZE, &[1, u32::MAX]);
+        assert!(parse_media_segment(&data, &track).is_err());
+    }
+}
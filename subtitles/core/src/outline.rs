@@ -0,0 +1,160 @@
+// Values of tts:textOutline ("none" | [<color>] <thickness> [<blur-radius>])
+// and tts:textShadow ("none" | <shadow> [, <shadow>]*, where <shadow> is
+// <offset-x> <offset-y> [<blur-radius>] [<color>]). The lengths are resolved
+// to pixels by `Subtitles`, the colour defaults to the colour of the text.
+use super::color::Color;
+use super::units::Length;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextOutlineValue {
+    pub color: Option<Color>,
+    pub thickness: Length,
+    pub blur_radius: Option<Length>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextShadowValue {
+    pub offset_x: Length,
+    pub offset_y: Length,
+    pub blur_radius: Option<Length>,
+    pub color: Option<Color>,
+}
+
+/// Splits on `separator` except inside parentheses, so "rgba(0, 0, 0, 255)"
+/// stays a single token.
+pub fn split_outside_parentheses(value: &str, is_separator: fn(char) -> bool) -> Vec<&str> {
+    let mut tokens: Vec<&str> = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, character) in value.char_indices() {
+        match character {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            character if depth == 0 && is_separator(character) => {
+                tokens.push(&value[start..index]);
+                start = index + character.len_utf8();
+            }
+            _ => (),
+        }
+    }
+    tokens.push(&value[start..]);
+    tokens
+        .into_iter()
+        .map(|token| token.trim())
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+/// Separates the optional colour (first or last token) from the lengths.
+fn parse_color_and_lengths(value: &str) -> Result<(Option<Color>, Vec<Length>), String> {
+    let mut tokens = split_outside_parentheses(value, char::is_whitespace);
+    let mut color = None;
+    if let Some(first) = tokens.first() {
+        if Length::parse(first).is_err() {
+            color = Some(Color::parse(first)?);
+            tokens.remove(0);
+        }
+    }
+    if color.is_none() {
+        if let Some(last) = tokens.last() {
+            if Length::parse(last).is_err() {
+                color = Some(Color::parse(last)?);
+                tokens.pop();
+            }
+        }
+    }
+    let lengths = tokens
+        .iter()
+        .map(|token| Length::parse(token))
+        .collect::<Result<Vec<Length>, String>>()?;
+    Ok((color, lengths))
+}
+
+/// `Ok(None)` is an explicit "none".
+pub fn parse_text_outline(value: &str) -> Result<Option<TextOutlineValue>, String> {
+    if value.trim() == "none" {
+        return Ok(None);
+    }
+    let (color, lengths) = parse_color_and_lengths(value)?;
+    match lengths[..] {
+        [thickness] => Ok(Some(TextOutlineValue {
+            color,
+            thickness,
+            blur_radius: None,
+        })),
+        [thickness, blur_radius] => Ok(Some(TextOutlineValue {
+            color,
+            thickness,
+            blur_radius: Some(blur_radius),
+        })),
+        _ => Err(format!("Invalid text outline: {}", value)),
+    }
+}
+
+pub fn parse_text_shadow(value: &str) -> Result<Vec<TextShadowValue>, String> {
+    if value.trim() == "none" {
+        return Ok(Vec::new());
+    }
+    split_outside_parentheses(value, |character| character == ',')
+        .into_iter()
+        .map(|shadow| {
+            let (color, lengths) = parse_color_and_lengths(shadow)?;
+            match lengths[..] {
+                [offset_x, offset_y] => Ok(TextShadowValue {
+                    offset_x,
+                    offset_y,
+                    blur_radius: None,
+                    color,
+                }),
+                [offset_x, offset_y, blur_radius] => Ok(TextShadowValue {
+                    offset_x,
+                    offset_y,
+                    blur_radius: Some(blur_radius),
+                    color,
+                }),
+                _ => Err(format!("Invalid text shadow: {}", shadow)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Unit;
+
+    #[test]
+    fn splits_outside_parentheses() {
+        assert_eq!(
+            split_outside_parentheses("rgba(0, 0, 0, 255) 1px", char::is_whitespace),
+            vec!["rgba(0, 0, 0, 255)", "1px"]
+        );
+        assert_eq!(
+            split_outside_parentheses("1px 1px red, 2px 2px", |character| character == ','),
+            vec!["1px 1px red", "2px 2px"]
+        );
+    }
+
+    #[test]
+    fn parses_text_outline() {
+        assert_eq!(parse_text_outline("none"), Ok(None));
+        let outline = parse_text_outline("black 0.1c").unwrap().unwrap();
+        assert_eq!(outline.color, Some(Color::rgb(0, 0, 0)));
+        assert_eq!(outline.thickness.unit, Unit::Cell);
+        assert_eq!(outline.blur_radius, None);
+        let outline = parse_text_outline("2px 1px #ff0000").unwrap().unwrap();
+        assert_eq!(outline.color, Some(Color::rgb(255, 0, 0)));
+        assert!(outline.blur_radius.is_some());
+        assert!(parse_text_outline("red").is_err());
+    }
+
+    #[test]
+    fn parses_text_shadows() {
+        assert_eq!(parse_text_shadow("none"), Ok(Vec::new()));
+        let shadows = parse_text_shadow("1px 2px red, 3px 4px 5px").unwrap();
+        assert_eq!(shadows.len(), 2);
+        assert_eq!(shadows[0].color, Some(Color::rgb(255, 0, 0)));
+        assert_eq!(shadows[1].blur_radius.unwrap().value, 5.0);
+        assert!(parse_text_shadow("1px").is_err());
+    }
+}
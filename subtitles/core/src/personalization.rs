@@ -0,0 +1,94 @@
+// User subtitle preferences (accessibility). They are merged over the styles
+// computed from the TTML document when a subtitle is rendered, so they can be
+// changed while subtitles are on screen.
+use super::color::Color;
+use super::render::{RenderedSubtitle, TextStyle};
+use super::units::FontSize;
+
+pub struct Personalization {
+    pub enabled: bool,
+    /// Multiplies every computed font size.
+    pub font_scale: f32,
+    /// Replaces the text colour of the document.
+    pub text_color: Option<Color>,
+    /// Opacity (0 to 1) of the text background. `None` keeps the background of
+    /// the document and 0 removes it.
+    pub background_opacity: Option<f32>,
+    /// Moves the regions up (positive) or down (negative), in percentage of
+    /// the element height.
+    pub vertical_offset: f32,
+}
+
+impl Personalization {
+    pub fn new() -> Personalization {
+        Personalization {
+            enabled: false,
+            font_scale: 1.0,
+            text_color: None,
+            background_opacity: None,
+            vertical_offset: 0.0,
+        }
+    }
+
+    fn scale_font_size(&self, style: &mut TextStyle) {
+        style.font_size = FontSize {
+            width: style.font_size.width * self.font_scale,
+            height: style.font_size.height * self.font_scale,
+        };
+        style.line_height = style
+            .line_height
+            .map(|line_height| line_height * self.font_scale);
+        if let Some(ruby_reserve) = style.ruby_reserve.as_mut() {
+            ruby_reserve.size *= self.font_scale;
+        }
+    }
+
+    /// Background for a run whose document background is `document_color`.
+    /// The colour of the document is kept (black if it has none) and only the
+    /// opacity is replaced.
+    fn get_background_color(&self, document_color: Option<Color>) -> Option<Color> {
+        let opacity = self.background_opacity?;
+        let color = document_color
+            .filter(|color| !color.is_transparent())
+            .unwrap_or(Color::rgb(0, 0, 0));
+        Some(Color::rgba(
+            color.r,
+            color.g,
+            color.b,
+            (opacity.clamp(0.0, 1.0) * 255.0).round() as u8,
+        ))
+    }
+
+    /// Merges the preferences over a subtitle computed from the document.
+    pub fn apply(&self, subtitle: &mut RenderedSubtitle, element_height: f32) {
+        if !self.enabled {
+            return;
+        }
+        self.scale_font_size(&mut subtitle.style);
+        for run in subtitle.lines.iter_mut().flatten() {
+            self.scale_font_size(&mut run.style);
+            if self.text_color.is_some() {
+                run.style.color = self.text_color;
+            }
+            if let Some(ruby) = run.ruby.as_mut() {
+                self.scale_font_size(&mut ruby.style);
+                if self.text_color.is_some() {
+                    ruby.style.color = self.text_color;
+                }
+            }
+            if let Some(background_color) = self.get_background_color(run.style.background_color) {
+                run.style.background_color = Some(background_color);
+            }
+        }
+        subtitle.background_removed = self.background_opacity == Some(0.0);
+        if let Some(region) = subtitle.region.as_mut() {
+            self.scale_font_size(&mut region.style);
+            // The vertical offset keeps the region inside the element.
+            if self.vertical_offset != 0.0 {
+                let max_top = (element_height - region.geometry.height).max(0.0);
+                let top = region.geometry.top - self.vertical_offset * element_height / 100.0;
+                region.geometry.top = top.clamp(0.0, max_top);
+            }
+        }
+    }
+}
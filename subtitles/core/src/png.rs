@@ -0,0 +1,237 @@
+// PNG encoding of the decoded bitmaps (DVB subtitles), embedded in the
+// document as base64 IMSC images. Subtitle bitmaps are mostly transparent
+// runs, so the deflate stream only looks for repeats of the previous pixel
+// and of the previous row, with the fixed Huffman codes.
+
+const LENGTH_BASES: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const MAX_MATCH: usize = 258;
+const MAX_DISTANCE: usize = 32768;
+
+/// Bits of a deflate stream, least significant first.
+struct BitWriter {
+    data: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= value << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.data.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are written from their most significant bit.
+    fn write_code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn write_symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASES
+            .iter()
+            .rposition(|base| *base <= length)
+            .unwrap();
+        self.write_symbol(257 + code as u32);
+        self.write(
+            (length - LENGTH_BASES[code]) as u32,
+            LENGTH_EXTRA_BITS[code],
+        );
+        let code = DISTANCE_BASES
+            .iter()
+            .rposition(|base| *base <= distance)
+            .unwrap();
+        self.write_code(code as u32, 5);
+        self.write(
+            (distance - DISTANCE_BASES[code]) as u32,
+            DISTANCE_EXTRA_BITS[code],
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.data.push(self.buffer as u8);
+        }
+        self.data
+    }
+}
+
+fn get_match_length(data: &[u8], position: usize, distance: usize) -> usize {
+    if distance > position || distance > MAX_DISTANCE {
+        return 0;
+    }
+    data[position..]
+        .iter()
+        .take(MAX_MATCH)
+        .zip(data[position - distance..].iter())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+/// zlib stream of a single fixed Huffman block.
+fn deflate(data: &[u8], row_length: usize) -> Vec<u8> {
+    let mut writer = BitWriter {
+        data: vec![0x78, 0x01],
+        buffer: 0,
+        count: 0,
+    };
+    writer.write(1, 1);
+    writer.write(1, 2);
+    let mut position = 0;
+    while position < data.len() {
+        let (length, distance) = [4, row_length]
+            .iter()
+            .map(|distance| (get_match_length(data, position, *distance), *distance))
+            .max_by_key(|(length, _)| *length)
+            .unwrap();
+        if length >= 3 {
+            writer.write_match(length, distance);
+            position += length;
+        } else {
+            writer.write_symbol(data[position] as u32);
+            position += 1;
+        }
+    }
+    writer.write_symbol(256);
+    let mut stream = writer.finish();
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn push_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// PNG of an 8-bit RGBA bitmap, `width * height * 4` bytes in rows.
+pub fn encode_png(width: usize, height: usize, rgba: &[u8]) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut header = (width as u32).to_be_bytes().to_vec();
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, adaptive filters, no interlace.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    push_chunk(&mut png, b"IHDR", &header);
+    // Each row starts with the filter type, none.
+    let row_length = width * 4 + 1;
+    let mut rows = Vec::with_capacity(row_length * height);
+    for row in rgba.chunks(width * 4).take(height) {
+        rows.push(0);
+        rows.extend_from_slice(row);
+    }
+    push_chunk(&mut png, b"IDAT", &deflate(&rows, row_length));
+    push_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+pub fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (index, byte)| {
+            value | ((*byte as u32) << (16 - 8 * index))
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                text.push(ALPHABET[((value >> (18 - 6 * index)) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn encodes_png() {
+        let rgba: Vec<u8> = (0..8 * 4)
+            .flat_map(|index| {
+                if index % 8 < 4 {
+                    [0, 0, 0, 0]
+                } else {
+                    [255, 255, 255, 255]
+                }
+            })
+            .collect();
+        let png = encode_png(8, 4, &rgba);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR"));
+        assert!(png.ends_with(b"IEND\xae\x42\x60\x82"));
+        // Repeated pixels and rows are encoded as back-references.
+        assert!(png.len() < 8 * 4 * 4);
+    }
+}
@@ -0,0 +1,34 @@
+// View of the tests: records the calls of the engine, like the page would
+// draw them.
+use super::SubtitleView;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+pub struct RecordingView {
+    pub events: Arc<Mutex<Vec<String>>>,
+    /// Ids of the subtitles on screen.
+    pub shown: Arc<Mutex<Vec<String>>>,
+    /// Last HTML shown for each id.
+    pub html: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl SubtitleView for RecordingView {
+    fn show_subtitle(&self, id: &str, html: &str) {
+        self.events.lock().unwrap().push(format!("show {}", id));
+        self.shown.lock().unwrap().push(id.to_string());
+        self.html
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), html.to_string());
+    }
+
+    fn hide_subtitle(&self, id: &str) {
+        self.events.lock().unwrap().push(format!("hide {}", id));
+        self.shown.lock().unwrap().retain(|shown| shown != id);
+    }
+
+    fn exist_subtitle(&self, id: &str) -> bool {
+        self.shown.lock().unwrap().iter().any(|shown| shown == id)
+    }
+}
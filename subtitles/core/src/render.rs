@@ -0,0 +1,681 @@
+// Structured output of the engine: a subtitle with its region geometry and
+// the computed style of every run of text, in display pixels. The HTML that is
+// sent to the page is generated from it, and it is also exposed as JSON so the
+// app can draw subtitles by other means (e.g. on a canvas).
+use super::color::Color;
+use super::convert::escape_attribute;
+use super::dvb::DvbBitmap;
+use super::units::{format_number, format_px, FontSize};
+use super::RegionGeometry;
+use serde::Serialize;
+
+/// Outline around the glyphs (`tts:textOutline`), in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextOutline {
+    pub color: Color,
+    pub thickness: f32,
+    pub blur_radius: f32,
+}
+
+/// One shadow of `tts:textShadow`, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextShadow {
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub blur_radius: f32,
+    pub color: Color,
+}
+
+/// Emphasis marks drawn next to every character (`tts:textEmphasis`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextEmphasis {
+    /// CSS `text-emphasis-style`, e.g. "filled circle", "open sesame" or a
+    /// quoted character.
+    pub style: String,
+    pub color: Color,
+    /// "before", "after" or "outside".
+    pub position: String,
+}
+
+/// Space reserved for ruby annotations on every line (`tts:rubyReserve`),
+/// in pixels.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RubyReserve {
+    pub position: String,
+    pub size: f32,
+}
+
+/// Computed style of an element. Everything is inherited from the parent
+/// element except the background colour and the opacity.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextStyle {
+    pub font_family: Option<String>,
+    pub font_size: FontSize,
+    pub font_style: Option<String>,
+    pub font_weight: Option<String>,
+    pub text_decoration: Option<String>,
+    pub color: Option<Color>,
+    pub background_color: Option<Color>,
+    pub text_align: Option<String>,
+    /// `None` is `tts:lineHeight="normal"`.
+    pub line_height: Option<f32>,
+    pub line_padding: f32,
+    pub multi_row_align: Option<String>,
+    pub text_outline: Option<TextOutline>,
+    pub text_shadows: Vec<TextShadow>,
+    /// Opacity of the element itself, multiplied by the opacity of its
+    /// ancestors when composited.
+    pub opacity: f32,
+    pub visible: bool,
+    pub ruby_position: Option<String>,
+    pub ruby_align: Option<String>,
+    pub ruby_reserve: Option<RubyReserve>,
+    pub text_emphasis: Option<TextEmphasis>,
+    /// Horizontal in vertical text (tate-chu-yoko). It has no effect on the
+    /// horizontal writing modes supported by the renderer.
+    pub text_combine: bool,
+    /// Shear of the lines in degrees, positive values lean them forwards like
+    /// italics.
+    pub shear: f32,
+}
+
+/// Ruby annotation of a run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedRuby {
+    pub text: String,
+    pub style: TextStyle,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedRun {
+    pub text: String,
+    pub style: TextStyle,
+    pub ruby: Option<RenderedRuby>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedRegion {
+    pub id: String,
+    pub geometry: RegionGeometry,
+    /// Top, right, bottom and left padding.
+    pub padding: [f32; 4],
+    pub style: TextStyle,
+}
+
+/// Image of the IMSC image profile, drawn to fill its region.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedImage {
+    /// URL for an `<img>`: the resolved URL or a data URL.
+    pub src: String,
+    pub mime_type: String,
+    /// Base64 data of the embedded images.
+    pub data: Option<String>,
+    /// Decoded pixels of the DVB subtitles, for drawing on a canvas.
+    pub bitmap: Option<DvbBitmap>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedSubtitle {
+    pub id: String,
+    pub region: Option<RenderedRegion>,
+    pub style: TextStyle,
+    pub lines: Vec<Vec<RenderedRun>>,
+    pub image: Option<RenderedImage>,
+    /// The user has removed the background of the text.
+    pub background_removed: bool,
+}
+
+/// Text or attribute value of the HTML. Single quotes are escaped too, the
+/// attributes are quoted with them.
+fn escape_html(text: &str) -> String {
+    escape_attribute(text).replace('\'', "&#39;")
+}
+
+/// Declaration of a keyword of the document (e.g. `tts:fontWeight`), dropped
+/// if the value could end the declaration or load a resource.
+fn push_keyword(styles: &mut Vec<String>, property: &str, value: Option<&String>) {
+    let Some(value) = value else {
+        return;
+    };
+    let is_safe = !value.is_empty()
+        && value
+            .chars()
+            .all(|character| character.is_alphanumeric() || " -_.%#".contains(character));
+    if is_safe {
+        styles.push(format!("{}:{}", property, value));
+    }
+}
+
+/// `tts:fontFamily` as CSS: the names with other characters than letters,
+/// digits, spaces, hyphens and underscores are dropped, and the ones that are
+/// not identifiers are quoted.
+fn push_font_family(styles: &mut Vec<String>, font_family: Option<&String>) {
+    let Some(font_family) = font_family else {
+        return;
+    };
+    let families: Vec<String> = font_family
+        .split(',')
+        .map(|family| {
+            family
+                .trim()
+                .trim_matches(|character| character == '"' || character == '\'')
+        })
+        .filter(|family| {
+            !family.is_empty()
+                && family
+                    .chars()
+                    .all(|character| character.is_alphanumeric() || " -_".contains(character))
+        })
+        .map(|family| {
+            let is_identifier = family.split_whitespace().all(|word| {
+                word.starts_with(|character: char| character.is_alphabetic() || character == '_')
+            });
+            if is_identifier {
+                family.split_whitespace().collect::<Vec<&str>>().join(" ")
+            } else {
+                format!("\"{}\"", family)
+            }
+        })
+        .collect();
+    if !families.is_empty() {
+        styles.push(format!("font-family:{}", families.join(",")));
+    }
+}
+
+fn push_color(styles: &mut Vec<String>, property: &str, color: Option<Color>) {
+    if let Some(color) = color {
+        styles.extend(color.to_css_declarations(property));
+    }
+}
+
+/// Text outline and shadows as CSS `text-shadow`, which unlike
+/// `-webkit-text-stroke` is supported by every HbbTV browser. The outline is
+/// drawn with eight shadows around the glyphs.
+fn push_text_shadow(styles: &mut Vec<String>, style: &TextStyle) {
+    let mut shadows: Vec<(f32, f32, f32, Color)> = Vec::new();
+    if let Some(outline) = style.text_outline {
+        let t = outline.thickness;
+        for (x, y) in [
+            (-t, -t),
+            (0.0, -t),
+            (t, -t),
+            (t, 0.0),
+            (t, t),
+            (0.0, t),
+            (-t, t),
+            (-t, 0.0),
+        ] {
+            shadows.push((x, y, outline.blur_radius, outline.color));
+        }
+    }
+    for shadow in style.text_shadows.iter() {
+        shadows.push((
+            shadow.offset_x,
+            shadow.offset_y,
+            shadow.blur_radius,
+            shadow.color,
+        ));
+    }
+    if shadows.is_empty() {
+        return;
+    }
+    let to_css = |color_to_css: &dyn Fn(Color) -> String| {
+        shadows
+            .iter()
+            .map(|(x, y, blur, color)| {
+                format!(
+                    "{} {} {} {}",
+                    format_px(*x),
+                    format_px(*y),
+                    format_px(*blur),
+                    color_to_css(*color)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    };
+    styles.push(format!(
+        "text-shadow:{}",
+        to_css(&|color| color.to_css_rgb())
+    ));
+    if shadows
+        .iter()
+        .any(|(_, _, _, color)| color.a != 255 && !color.is_transparent())
+    {
+        styles.push(format!("text-shadow:{}", to_css(&|color| color.to_css())));
+    }
+}
+
+/// `opacity` and `visibility` declarations. Visibility is only written when
+/// it differs from the parent, since CSS inherits it as well.
+fn push_visibility(styles: &mut Vec<String>, style: &TextStyle, parent_visible: bool) {
+    if style.opacity < 1.0 {
+        styles.push(format!("opacity:{}", format_number(style.opacity)));
+    }
+    if style.visible != parent_visible {
+        styles.push(format!(
+            "visibility:{}",
+            if style.visible { "visible" } else { "hidden" }
+        ));
+    }
+}
+
+fn push_text_emphasis(styles: &mut Vec<String>, style: &TextStyle) {
+    let text_emphasis = match style.text_emphasis.as_ref() {
+        Some(text_emphasis) => text_emphasis,
+        None => return,
+    };
+    let position = if text_emphasis.position == "after" {
+        "under right"
+    } else {
+        "over right"
+    };
+    for prefix in ["-webkit-", ""] {
+        styles.push(format!(
+            "{}text-emphasis-style:{}",
+            prefix, text_emphasis.style
+        ));
+        styles.push(format!(
+            "{}text-emphasis-color:{}",
+            prefix,
+            text_emphasis.color.to_css()
+        ));
+        styles.push(format!("{}text-emphasis-position:{}", prefix, position));
+    }
+}
+
+fn get_horizontal_scale_styles(scale: f32) -> Vec<String> {
+    get_transform_styles(scale, 0.0)
+}
+
+/// Horizontal scale (anamorphic font sizes) and shear as a CSS transform, for
+/// an inline block.
+fn get_transform_styles(scale: f32, shear: f32) -> Vec<String> {
+    let mut transforms: Vec<String> = Vec::new();
+    if (scale - 1.0).abs() >= 0.01 {
+        transforms.push(format!("scaleX({})", format_number(scale)));
+    }
+    if shear.abs() >= 0.01 {
+        transforms.push(format!("skewX({}deg)", format_number(-shear)));
+    }
+    if transforms.is_empty() {
+        return Vec::new();
+    }
+    let transform = transforms.join(" ");
+    vec![
+        format!("-webkit-transform:{}", transform),
+        format!("-o-transform:{}", transform),
+        format!("transform:{}", transform),
+    ]
+}
+
+fn get_text_align_css(text_align: &str) -> &str {
+    // start and end are not understood by old browsers, writing mode is lrtb.
+    match text_align {
+        "start" => "left",
+        "end" => "right",
+        text_align => text_align,
+    }
+}
+
+/// CSS class for the `multiRowAlign` wrapper. With `auto` (the default) the
+/// lines follow the paragraph `textAlign`, so no class is added.
+fn get_multi_row_align_class(style: &TextStyle) -> &str {
+    match style.multi_row_align.as_deref() {
+        Some("start") => " multiRowAlign-start",
+        Some("center") => " multiRowAlign-center",
+        Some("end") => " multiRowAlign-end",
+        _ => "",
+    }
+}
+
+/// `tts:rubyPosition` as CSS. `outside` is above the text for horizontal
+/// writing modes.
+fn get_ruby_position_css(ruby_position: Option<&str>) -> [&str; 2] {
+    match ruby_position {
+        Some("after") => ["under", "after"],
+        _ => ["over", "before"],
+    }
+}
+
+fn get_ruby_styles(ruby: &RenderedRuby) -> String {
+    let [ruby_position, webkit_ruby_position] =
+        get_ruby_position_css(ruby.style.ruby_position.as_deref());
+    let mut styles: Vec<String> = vec![
+        format!("-webkit-ruby-position:{}", webkit_ruby_position),
+        format!("ruby-position:{}", ruby_position),
+    ];
+    match ruby.style.ruby_align.as_deref() {
+        Some("center") => styles.push("ruby-align:center".to_string()),
+        Some("start") => styles.push("ruby-align:start".to_string()),
+        Some("spaceAround") => styles.push("ruby-align:space-around".to_string()),
+        Some("spaceBetween") => styles.push("ruby-align:space-between".to_string()),
+        _ => (),
+    }
+    styles.join(";")
+}
+
+/// Styles of the `<rt>` element. The font size is always written because the
+/// browser reduces it by default, while TTML computes it like any other span.
+fn get_ruby_text_styles(ruby: &RenderedRuby, base_style: &TextStyle) -> String {
+    let style = &ruby.style;
+    let mut styles: Vec<String> = vec![format!("font-size:{}", format_px(style.font_size.height))];
+    // Old browsers ignore ruby-align, the annotation box can be aligned instead.
+    match style.ruby_align.as_deref() {
+        Some("start") => styles.push("text-align:left".to_string()),
+        Some("center") => styles.push("text-align:center".to_string()),
+        Some("end") => styles.push("text-align:right".to_string()),
+        _ => (),
+    }
+    if style.font_family != base_style.font_family {
+        push_font_family(&mut styles, style.font_family.as_ref());
+    }
+    push_keyword(&mut styles, "font-style", style.font_style.as_ref());
+    push_keyword(&mut styles, "font-weight", style.font_weight.as_ref());
+    push_color(&mut styles, "background-color", style.background_color);
+    push_color(&mut styles, "color", style.color);
+    push_text_shadow(&mut styles, style);
+    push_visibility(&mut styles, style, base_style.visible);
+    styles.join(";")
+}
+
+/// Line height of the paragraph, increased by the space reserved for ruby
+/// annotations since CSS can not reserve it on lines without ruby.
+fn get_line_height_css(style: &TextStyle) -> String {
+    let reserve = match style.ruby_reserve.as_ref() {
+        Some(ruby_reserve) if ruby_reserve.position == "both" => 2.0 * ruby_reserve.size,
+        Some(ruby_reserve) => ruby_reserve.size,
+        None => 0.0,
+    };
+    match style.line_height {
+        Some(line_height) => format_px(line_height + reserve),
+        // 1.25 is the usual "normal" line height of subtitle fonts.
+        None if reserve > 0.0 => format_px(style.font_size.height * 1.25 + reserve),
+        None => "normal".to_string(),
+    }
+}
+
+fn get_region_styles(region: &RenderedRegion) -> String {
+    let mut styles: Vec<String> = vec![
+        format!("left:{}", format_px(region.geometry.left)),
+        format!("top:{}", format_px(region.geometry.top)),
+        format!("width:{}", format_px(region.geometry.width)),
+        format!("height:{}", format_px(region.geometry.height)),
+    ];
+    push_color(
+        &mut styles,
+        "background-color",
+        region.style.background_color,
+    );
+    push_font_family(&mut styles, region.style.font_family.as_ref());
+    styles.push(format!(
+        "font-size:{}",
+        format_px(region.style.font_size.height)
+    ));
+    push_visibility(&mut styles, &region.style, true);
+    styles.join(";")
+}
+
+fn get_region_padding_styles(region: &RenderedRegion) -> String {
+    format!(
+        "padding:{} {} {} {}",
+        format_px(region.padding[0]),
+        format_px(region.padding[1]),
+        format_px(region.padding[2]),
+        format_px(region.padding[3])
+    )
+}
+
+/// Styles for the paragraph container: the computed font size and the line
+/// height are applied to the whole paragraph so every line box gets the same
+/// height.
+fn get_paragraph_styles(style: &TextStyle, parent_visible: bool) -> String {
+    let mut styles: Vec<String> = vec![
+        format!("font-size:{}", format_px(style.font_size.height)),
+        format!("line-height:{}", get_line_height_css(style)),
+    ];
+    if let Some(text_align) = style.text_align.as_ref() {
+        let text_align = get_text_align_css(text_align).to_string();
+        push_keyword(&mut styles, "text-align", Some(&text_align));
+    }
+    push_color(&mut styles, "background-color", style.background_color);
+    push_visibility(&mut styles, style, parent_visible);
+    styles.join(";")
+}
+
+fn get_run_styles(
+    run: &RenderedRun,
+    paragraph_style: &TextStyle,
+    is_first: bool,
+    is_last: bool,
+) -> String {
+    let style = &run.style;
+    let mut styles: Vec<String> = Vec::new();
+    push_color(&mut styles, "background-color", style.background_color);
+    push_font_family(&mut styles, style.font_family.as_ref());
+    if style.font_size != paragraph_style.font_size {
+        styles.push(format!("font-size:{}", format_px(style.font_size.height)));
+        let scale_styles = get_horizontal_scale_styles(
+            style.font_size.horizontal_scale() / paragraph_style.font_size.horizontal_scale(),
+        );
+        if !scale_styles.is_empty() {
+            styles.push("display:inline-block".to_string());
+            styles.extend(scale_styles);
+        }
+    }
+    push_keyword(&mut styles, "font-style", style.font_style.as_ref());
+    push_keyword(&mut styles, "font-weight", style.font_weight.as_ref());
+    push_keyword(
+        &mut styles,
+        "text-decoration",
+        style.text_decoration.as_ref(),
+    );
+    push_color(&mut styles, "color", style.color);
+    push_text_shadow(&mut styles, style);
+    push_text_emphasis(&mut styles, style);
+    if style.text_combine {
+        styles.push("-webkit-text-combine:horizontal".to_string());
+        styles.push("text-combine-upright:all".to_string());
+    }
+    push_visibility(&mut styles, style, paragraph_style.visible);
+    // ebutts:linePadding adds horizontal space at the start and the end of
+    // every line, painted with the background of the span it touches.
+    if paragraph_style.line_padding > 0.0 {
+        if is_first {
+            styles.push(format!(
+                "padding-left:{}",
+                format_px(paragraph_style.line_padding)
+            ));
+        }
+        if is_last {
+            styles.push(format!(
+                "padding-right:{}",
+                format_px(paragraph_style.line_padding)
+            ));
+        }
+    }
+    styles.join(";")
+}
+
+fn get_rows(subtitle: &RenderedSubtitle) -> String {
+    let br_string = "<br/>".to_string();
+    let mut texts: Vec<String> = Vec::new();
+    for (line_index, line) in subtitle.lines.iter().enumerate() {
+        if line_index > 0 {
+            texts.push(br_string.clone());
+        }
+        for (run_index, run) in line.iter().enumerate() {
+            let text = match run.ruby.as_ref() {
+                Some(ruby) => format!(
+                    "<ruby style='{}'>{}<rp>(</rp><rt style='{}'>{}</rt><rp>)</rp></ruby>",
+                    escape_html(&get_ruby_styles(ruby)),
+                    escape_html(&run.text),
+                    escape_html(&get_ruby_text_styles(ruby, &run.style)),
+                    escape_html(&ruby.text)
+                ),
+                None => escape_html(&run.text),
+            };
+            texts.push(format!(
+                "<span class='span-subtitle' style='{}'>{}</span>",
+                escape_html(&get_run_styles(
+                    run,
+                    &subtitle.style,
+                    run_index == 0,
+                    run_index == line.len() - 1
+                )),
+                text
+            ));
+        }
+    }
+    texts.concat()
+}
+
+/// The image covers the region, or the whole element without region.
+fn get_image_html(subtitle: &RenderedSubtitle, image: &RenderedImage) -> String {
+    let mut styles: Vec<String> = vec!["display:block".to_string()];
+    match subtitle.region.as_ref() {
+        Some(region) => {
+            styles.push(format!("width:{}", format_px(region.geometry.width)));
+            styles.push(format!("height:{}", format_px(region.geometry.height)));
+        }
+        None => {
+            styles.push("width:100%".to_string());
+            styles.push("height:100%".to_string());
+        }
+    }
+    push_visibility(&mut styles, &subtitle.style, true);
+    format!(
+        "<img class='image-subtitle' alt='' src='{}' style='{}'/>",
+        escape_html(&image.src),
+        escape_html(&styles.join(";"))
+    )
+}
+
+pub fn to_html(subtitle: &RenderedSubtitle, default_styles: &str) -> String {
+    if let Some(image) = subtitle.image.as_ref() {
+        let region_styles = subtitle
+            .region
+            .as_ref()
+            .map(get_region_styles)
+            .unwrap_or_default();
+        return format!(
+            "\
+            <div data-test-id='default-style-wrapper' style='{}'>\
+                <div class='regionContainer' data-test-id='region-style' style='{}' id='{}'>\
+                {}\
+                </div>\
+            </div>\
+            ",
+            escape_html(default_styles),
+            escape_html(&region_styles),
+            escape_html(&subtitle.id),
+            get_image_html(subtitle, image)
+        );
+    }
+    let region_class = if subtitle.background_removed {
+        " user-no-background"
+    } else {
+        ""
+    };
+    let (region_styles, region_padding_styles) = match subtitle.region.as_ref() {
+        Some(region) => (get_region_styles(region), get_region_padding_styles(region)),
+        None => ("".to_string(), "".to_string()),
+    };
+    // An inline block, so an anamorphic font size can be rendered scaling it
+    // horizontally and the shear applied to all the lines.
+    let multi_row_align_styles = get_transform_styles(
+        subtitle.style.font_size.horizontal_scale(),
+        subtitle.style.shear,
+    )
+    .join(";");
+    format!(
+        "\
+        <div data-test-id='default-style-wrapper' style='{}'>\
+            <div class='regionContainer{}' data-test-id='region-style' style='{}' id='{}'>\
+                <div class='displayAlign regionPadding' style='{}'>\
+                    <div class='paragraphContainer' data-test-id='paragraphContainer' style='{}'>\
+                        <div class='multiRowAlign{}' style='{}'>\
+                        {}\
+                        </div>\
+                    </div>\
+                </div>\
+            </div>\
+        </div>\
+        ",
+        escape_html(default_styles),
+        region_class,
+        escape_html(&region_styles),
+        escape_html(&subtitle.id),
+        escape_html(&region_padding_styles),
+        escape_html(&get_paragraph_styles(
+            &subtitle.style,
+            subtitle
+                .region
+                .as_ref()
+                .is_none_or(|region| region.style.visible)
+        )),
+        get_multi_row_align_class(&subtitle.style),
+        escape_html(&multi_row_align_styles),
+        get_rows(subtitle)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Subtitles;
+
+    fn render(font_family: &str, text: &str) -> String {
+        let xml = format!(
+            r#"<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling">
+  <tt:head>
+    <tt:styling>
+      <tt:style xml:id="s1" tts:fontFamily="{}" tts:fontWeight="bold;color:red"/>
+    </tt:styling>
+    <tt:layout>
+      <tt:region xml:id="r1" tts:origin="10% 80%" tts:extent="80% 15%"/>
+    </tt:layout>
+  </tt:head>
+  <tt:body><tt:div region="r1">
+    <tt:p xml:id="p1" begin="00:00:01.000" end="00:00:02.000"><tt:span style="s1">{}</tt:span></tt:p>
+  </tt:div></tt:body>
+</tt:tt>"#,
+            font_family, text
+        );
+        let mut subtitles = Subtitles::new();
+        subtitles.set_element_size(1000, 600);
+        subtitles.load(&xml).unwrap();
+        to_html(&subtitles.get_subtitles_at(1500)[0], "")
+    }
+
+    #[test]
+    fn escapes_the_text_and_the_styles() {
+        let html = render(
+            "a' onmouseover='x",
+            "&lt;img src=x onerror=alert(1)&gt; &amp; 'q'",
+        );
+        assert!(html.contains("&lt;img src=x onerror=alert(1)&gt; &amp; &#39;q&#39;"));
+        assert!(!html.contains("<img"));
+        assert!(!html.contains("onmouseover"));
+        assert!(!html.contains("color:red"));
+    }
+
+    #[test]
+    fn quotes_font_families() {
+        let html = render(
+            "&quot;Times  New Roman&quot;, 'Noto Sans', 3D, monospace",
+            "Text",
+        );
+        assert!(html.contains("font-family:Times New Roman,Noto Sans,&quot;3D&quot;,monospace"));
+    }
+}
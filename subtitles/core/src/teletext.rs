@@ -0,0 +1,633 @@
+// EBU Teletext subtitles (EN 300 472, ETS 300 706) of broadcast and TS
+// streams, usually page 888. The packets of the selected page are assembled
+// into rows, decoded with their national character set, colours, boxes and
+// double height, and every update of the page is appended as a cue shown
+// until the next one.
+use super::append::UNTIL_NEXT_UPDATE_MS;
+use super::convert::{escape_attribute, format_clock_time};
+use super::webvtt::escape_run_text;
+use super::Subtitles;
+
+const ROWS: usize = 24;
+const COLUMNS: usize = 40;
+
+/// Characters of the G0 Latin set replaced by the national option subsets.
+const NATIONAL_POSITIONS: [u8; 13] = [
+    0x23, 0x24, 0x40, 0x5B, 0x5C, 0x5D, 0x5E, 0x5F, 0x60, 0x7B, 0x7C, 0x7D, 0x7E,
+];
+
+/// National option subsets of the default (Western European) group, by the
+/// C12 C13 C14 bits of the page header.
+const NATIONAL_SUBSETS: [[char; 13]; 8] = [
+    // English
+    [
+        '£', '$', '@', '←', '½', '→', '↑', '#', '–', '¼', '‖', '¾', '÷',
+    ],
+    // German
+    [
+        '#', '$', '§', 'Ä', 'Ö', 'Ü', '^', '_', '°', 'ä', 'ö', 'ü', 'ß',
+    ],
+    // Swedish, Finnish, Hungarian
+    [
+        '#', '¤', 'É', 'Ä', 'Ö', 'Å', 'Ü', '_', 'é', 'ä', 'ö', 'å', 'ü',
+    ],
+    // Italian
+    [
+        '£', '$', 'é', '°', 'ç', '→', '↑', '#', 'ù', 'à', 'ò', 'è', 'ì',
+    ],
+    // French
+    [
+        'é', 'ï', 'à', 'ë', 'ê', 'ù', 'î', '#', 'è', 'â', 'ô', 'û', 'ç',
+    ],
+    // Portuguese, Spanish (and Catalan)
+    [
+        'ç', '$', '¡', 'á', 'é', 'í', 'ó', 'ú', '¿', 'ü', 'ñ', 'è', 'à',
+    ],
+    // Czech, Slovak
+    [
+        '#', 'ů', 'č', 'ť', 'ž', 'ý', 'í', 'ř', 'é', 'á', 'ě', 'ú', 'š',
+    ],
+    // Not defined, English
+    [
+        '£', '$', '@', '←', '½', '→', '↑', '#', '–', '¼', '‖', '¾', '÷',
+    ],
+];
+
+/// Black, red, green, yellow, blue, magenta, cyan and white.
+const COLORS: [&str; 8] = [
+    "#000000", "#ff0000", "#00ff00", "#ffff00", "#0000ff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+fn encode_hamming_8_4(nibble: u8) -> u8 {
+    let bit = |index: u8| (nibble >> index) & 1;
+    let p1 = 1 ^ bit(0) ^ bit(2) ^ bit(3);
+    let p2 = 1 ^ bit(0) ^ bit(1) ^ bit(3);
+    let p3 = 1 ^ bit(0) ^ bit(1) ^ bit(2);
+    let p4 = 1 ^ p1 ^ p2 ^ p3 ^ bit(0) ^ bit(1) ^ bit(2) ^ bit(3);
+    p1 | (bit(0) << 1)
+        | (p2 << 2)
+        | (bit(1) << 3)
+        | (p3 << 4)
+        | (bit(2) << 5)
+        | (p4 << 6)
+        | (bit(3) << 7)
+}
+
+/// Hamming 8/4 protected nibble, with single bit errors corrected.
+fn decode_hamming_8_4(byte: u8) -> Option<u8> {
+    (0..16).find(|nibble| (encode_hamming_8_4(*nibble) ^ byte).count_ones() <= 1)
+}
+
+/// 7-bit character with odd parity.
+fn decode_odd_parity(byte: u8) -> Option<u8> {
+    (byte.count_ones() % 2 == 1).then_some(byte & 0x7F)
+}
+
+/// A shown cell: character, foreground and background colours. Cells
+/// outside of the boxes of subtitle pages are not shown.
+type Cell = Option<(char, u8, u8)>;
+
+/// Characters of a line with the same colours.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeletextRun {
+    pub text: String,
+    /// Index of the foreground colour, 0 (black) to 7 (white).
+    pub color: u8,
+    /// Index of the background colour, none outside of the boxes.
+    pub background: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeletextLine {
+    pub row: usize,
+    /// The line also covers the next row.
+    pub double_height: bool,
+    pub runs: Vec<TeletextRun>,
+}
+
+/// The page as shown from `begin`, no lines clears the screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeletextPage {
+    pub begin: i64,
+    pub lines: Vec<TeletextLine>,
+}
+
+/// Decoder of the Teletext PES packets of one page.
+pub struct TeletextDecoder {
+    /// Magazine, 1 to 8.
+    magazine: u8,
+    /// Page number in the magazine, BCD.
+    page: u8,
+    /// National option subset used instead of the one of the page header,
+    /// for broadcasters that do not signal it.
+    national_option: Option<u8>,
+    /// Rows 1 to 23, row 0 (the header) is not shown.
+    rows: [[u8; COLUMNS]; ROWS],
+    national_subset: u8,
+    /// Subtitle and newsflash pages only show their boxed characters.
+    boxed: bool,
+    /// Time of the header of the page being received, until another header
+    /// of the magazine ends it.
+    begin: Option<i64>,
+    is_changed: bool,
+    last_lines: Vec<TeletextLine>,
+}
+
+impl Default for TeletextDecoder {
+    fn default() -> Self {
+        TeletextDecoder {
+            magazine: 8,
+            page: 0x88,
+            national_option: None,
+            rows: [[b' '; COLUMNS]; ROWS],
+            national_subset: 0,
+            boxed: false,
+            begin: None,
+            is_changed: false,
+            last_lines: Vec::new(),
+        }
+    }
+}
+
+impl TeletextDecoder {
+    /// Selects the page, 100 to 899.
+    pub fn set_page(&mut self, page: u16) -> Result<(), String> {
+        if !(100..=899).contains(&page) {
+            return Err(format!("Invalid Teletext page: {}", page));
+        }
+        let number = (page % 100) as u8;
+        *self = TeletextDecoder {
+            magazine: (page / 100) as u8,
+            page: ((number / 10) << 4) | (number % 10),
+            national_option: self.national_option,
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    pub fn set_national_option(&mut self, national_option: Option<u8>) -> Result<(), String> {
+        if let Some(option) = national_option.filter(|option| *option > 7) {
+            return Err(format!("Invalid national option: {}", option));
+        }
+        self.national_option = national_option;
+        Ok(())
+    }
+
+    /// Decodes the payload of a PES packet with presentation time `ms`,
+    /// returning the updates of the page.
+    pub fn decode_pes(&mut self, data: &[u8], ms: i64) -> Result<Vec<TeletextPage>, String> {
+        if !(0x10..=0x1F).contains(data.first().ok_or("Empty Teletext PES packet")?) {
+            return Err("Not a Teletext PES packet".to_string());
+        }
+        let mut position = 1;
+        while position + 2 <= data.len() {
+            let (data_unit_id, length) = (data[position], data[position + 1] as usize);
+            let unit = data
+                .get(position + 2..position + 2 + length)
+                .ok_or("Truncated Teletext data unit")?;
+            position += 2 + length;
+            // EBU Teletext non-subtitle and subtitle data, with the framing
+            // code. The bits of the packet are sent least significant first.
+            if (data_unit_id == 0x02 || data_unit_id == 0x03) && length == 44 && unit[1] == 0xE4 {
+                let packet: Vec<u8> = unit[2..].iter().map(|byte| byte.reverse_bits()).collect();
+                self.read_packet(&packet, ms);
+            }
+        }
+        let mut pages = Vec::new();
+        if self.is_changed {
+            self.is_changed = false;
+            let lines = self.get_lines();
+            if lines != self.last_lines {
+                pages.push(TeletextPage {
+                    begin: self.begin.unwrap_or(ms),
+                    lines: lines.clone(),
+                });
+                self.last_lines = lines;
+            }
+        }
+        Ok(pages)
+    }
+
+    fn read_packet(&mut self, packet: &[u8], ms: i64) {
+        let (Some(address), Some(row_high)) =
+            (decode_hamming_8_4(packet[0]), decode_hamming_8_4(packet[1]))
+        else {
+            return;
+        };
+        let magazine = match address & 7 {
+            0 => 8,
+            magazine => magazine,
+        };
+        let row = ((address >> 3) | (row_high << 1)) as usize;
+        if row == 0 {
+            let header: Option<Vec<u8>> = packet[2..10]
+                .iter()
+                .map(|byte| decode_hamming_8_4(*byte))
+                .collect();
+            let Some(header) = header else {
+                return;
+            };
+            let page = (header[1] << 4) | header[0];
+            let serial = header[7] & 1 == 1;
+            // In serial mode the header of any magazine ends the page.
+            if magazine == self.magazine || serial {
+                self.begin = None;
+            }
+            if magazine == self.magazine && page == self.page {
+                if header[3] & 8 != 0 {
+                    self.rows = [[b' '; COLUMNS]; ROWS];
+                }
+                self.boxed = header[5] & 0b1100 != 0;
+                // C12 is the most significant bit of the option.
+                let bits = header[7] >> 1;
+                self.national_subset = ((bits & 1) << 2) | (bits & 2) | ((bits >> 2) & 1);
+                self.begin = Some(ms);
+                self.is_changed = true;
+            }
+        } else if row < ROWS && magazine == self.magazine && self.begin.is_some() {
+            for (cell, byte) in self.rows[row].iter_mut().zip(packet[2..].iter()) {
+                *cell = decode_odd_parity(*byte).unwrap_or(b' ');
+            }
+            self.is_changed = true;
+        }
+    }
+
+    fn get_char(&self, code: u8) -> char {
+        let subset = self.national_option.unwrap_or(self.national_subset) as usize;
+        match NATIONAL_POSITIONS
+            .iter()
+            .position(|position| *position == code)
+        {
+            Some(index) => NATIONAL_SUBSETS[subset][index],
+            None if code == 0x7F => '■',
+            None => code as char,
+        }
+    }
+
+    /// Cells of a row as shown, with their colours, and whether it is double
+    /// height. Spacing attributes are shown as spaces; the set-after ones
+    /// apply from the next cell.
+    fn get_cells(&self, row: &[u8; COLUMNS]) -> (Vec<Cell>, bool) {
+        let (mut color, mut background) = (7, 0);
+        let (mut boxed, mut mosaic, mut conceal, mut double_height) = (false, false, false, false);
+        let mut cells = Vec::with_capacity(COLUMNS);
+        for code in row.iter().copied() {
+            match code {
+                0x18 => conceal = true,
+                0x1C => background = 0,
+                0x1D => background = color,
+                _ => (),
+            }
+            let character = if code < 0x20 || conceal {
+                ' '
+            } else if mosaic && !(0x40..0x60).contains(&code) {
+                // Block mosaics are not shown, capitals blast through.
+                ' '
+            } else {
+                self.get_char(code)
+            };
+            cells.push((!self.boxed || boxed).then_some((character, color, background)));
+            match code {
+                0x00..=0x07 => (color, mosaic, conceal) = (code, false, false),
+                0x10..=0x17 => (color, mosaic, conceal) = (code - 0x10, true, false),
+                0x0A => boxed = false,
+                0x0B => boxed = true,
+                0x0D | 0x0F => double_height = true,
+                _ => (),
+            }
+        }
+        (cells, double_height)
+    }
+
+    fn get_lines(&self) -> Vec<TeletextLine> {
+        let mut lines = Vec::new();
+        let mut row = 1;
+        while row < ROWS {
+            let (cells, double_height) = self.get_cells(&self.rows[row]);
+            let is_text = |cell: &Cell| cell.is_some_and(|(c, _, _)| c != ' ');
+            if let (Some(first), Some(last)) = (
+                cells.iter().position(is_text),
+                cells.iter().rposition(is_text),
+            ) {
+                let mut runs: Vec<TeletextRun> = Vec::new();
+                for cell in cells[first..=last].iter() {
+                    // Between boxes nothing is shown.
+                    let (character, color, background) = match cell {
+                        Some((character, color, background)) => {
+                            (*character, *color, Some(*background))
+                        }
+                        None => (' ', 7, None),
+                    };
+                    match runs.last_mut() {
+                        Some(run) if run.color == color && run.background == background => {
+                            run.text.push(character)
+                        }
+                        _ => runs.push(TeletextRun {
+                            text: character.to_string(),
+                            color,
+                            background,
+                        }),
+                    }
+                }
+                lines.push(TeletextLine {
+                    row,
+                    double_height,
+                    runs,
+                });
+            }
+            // The row after a double height one is hidden.
+            row += if double_height { 2 } else { 1 };
+        }
+        lines
+    }
+}
+
+/// Document of a page update: a paragraph over its rows, in a display of
+/// 25 rows.
+fn to_ttml(page: &TeletextPage) -> String {
+    let first_row = page.lines[0].row;
+    let last_line = &page.lines[page.lines.len() - 1];
+    let last_row = last_line.row + last_line.double_height as usize;
+    let region_id = format!("txt-{}-{}", first_row, last_row);
+    let content = page
+        .lines
+        .iter()
+        .map(|line| {
+            line.runs
+                .iter()
+                .map(|run| {
+                    let mut styles = format!("txt-{}", run.color);
+                    if let Some(background) = run.background {
+                        styles.push_str(&format!(" txt-bg-{}", background));
+                    }
+                    if line.double_height {
+                        styles.push_str(" txt-double-height");
+                    }
+                    format!(
+                        "<tt:span style=\"{}\">{}</tt:span>",
+                        styles,
+                        escape_run_text(&run.text)
+                    )
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("<tt:br/>");
+    let styles: String = COLORS
+        .iter()
+        .enumerate()
+        .map(|(index, color)| {
+            format!(
+                "      <tt:style xml:id=\"txt-{0}\" tts:color=\"{1}\"/>\n      <tt:style xml:id=\"txt-bg-{0}\" tts:backgroundColor=\"{1}\"/>\n",
+                index, color
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" xmlns:tts="http://www.w3.org/ns/ttml#styling" ttp:timeBase="media" ttp:cellResolution="40 25">
+  <tt:head>
+    <tt:styling>
+      <tt:style xml:id="txt" tts:textAlign="center" tts:fontFamily="monospaceSansSerif"/>
+      <tt:style xml:id="txt-double-height" tts:fontSize="100% 200%"/>
+{}    </tt:styling>
+    <tt:layout>
+      <tt:region xml:id="{}" tts:origin="0% {}%" tts:extent="100% {}%"/>
+    </tt:layout>
+  </tt:head>
+  <tt:body>
+    <tt:div>
+      <tt:p xml:id="txt-{}" begin="{}" end="{}" region="{}" style="txt">{}</tt:p>
+    </tt:div>
+  </tt:body>
+</tt:tt>
+"#,
+        styles,
+        region_id,
+        first_row * 4,
+        (last_row - first_row + 1) * 4,
+        page.begin,
+        format_clock_time(page.begin, '.'),
+        format_clock_time(page.begin + UNTIL_NEXT_UPDATE_MS, '.'),
+        escape_attribute(&region_id),
+        content
+    )
+}
+
+impl Subtitles {
+    /// Selects the Teletext subtitle page, 888 if not set.
+    pub fn set_teletext_page(&mut self, page: u16) -> Result<(), String> {
+        self.teletext_decoder
+            .get_or_insert_with(TeletextDecoder::default)
+            .set_page(page)
+    }
+
+    /// National option subset (0 to 7) of the Teletext characters, instead
+    /// of the one signalled by the page.
+    pub fn set_teletext_national_option(&mut self, option: Option<u8>) -> Result<(), String> {
+        self.teletext_decoder
+            .get_or_insert_with(TeletextDecoder::default)
+            .set_national_option(option)
+    }
+
+    /// Appends the updates of the Teletext page in the payload of a PES
+    /// packet whose presentation time is `ms`.
+    pub fn append_teletext_pes(&mut self, data: &[u8], ms: i64) -> Result<(), String> {
+        let pages = self
+            .teletext_decoder
+            .get_or_insert_with(TeletextDecoder::default)
+            .decode_pes(data, ms)?;
+        for page in pages.iter() {
+            self.replace_stream_cues("txt-", page.begin);
+            if !page.lines.is_empty() {
+                self.append_document(&to_ttml(page))?;
+            }
+        }
+        if !pages.is_empty() {
+            self.resync_visible_subtitles();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_odd_parity(code: u8) -> u8 {
+        if code.count_ones() % 2 == 1 {
+            code
+        } else {
+            code | 0x80
+        }
+    }
+
+    /// Data unit of a packet, with its bits in transmission order.
+    fn data_unit(magazine: u8, row: u8, payload: &[u8]) -> Vec<u8> {
+        let address = (magazine & 7) | ((row & 1) << 3);
+        let mut packet = vec![encode_hamming_8_4(address), encode_hamming_8_4(row >> 1)];
+        packet.extend_from_slice(payload);
+        packet.resize(42, with_odd_parity(b' '));
+        let mut unit = vec![0x03, 44, 0x02, 0xE4];
+        unit.extend(packet.iter().map(|byte| byte.reverse_bits()));
+        unit
+    }
+
+    /// Header of a page erased with subtitle flag and a national option.
+    fn header(magazine: u8, page: u8, national_option: u8) -> Vec<u8> {
+        let c12_c14 =
+            ((national_option >> 2) & 1) | (national_option & 2) | ((national_option & 1) << 2);
+        let nibbles = [page & 15, page >> 4, 0, 8, 0, 8, 0, c12_c14 << 1];
+        let payload: Vec<u8> = nibbles
+            .iter()
+            .map(|nibble| encode_hamming_8_4(*nibble))
+            .collect();
+        data_unit(magazine, 0, &payload)
+    }
+
+    fn row(magazine: u8, row: u8, text: &[u8]) -> Vec<u8> {
+        let payload: Vec<u8> = text.iter().map(|code| with_odd_parity(*code)).collect();
+        data_unit(magazine, row, &payload)
+    }
+
+    fn pes(units: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0x10];
+        for unit in units {
+            data.extend_from_slice(unit);
+        }
+        data
+    }
+
+    fn subtitle_pes(magazine: u8, page: u8) -> Vec<u8> {
+        pes(&[
+            header(magazine, page, 5),
+            row(magazine, 20, b"hidden\x03\x0d\x0b\x0b\x60Qu\x5c?\x0a\x0a"),
+            row(magazine, 21, b"\x0b\x0bbottom half"),
+            row(magazine, 22, b"\x07\x0b\x0bS\x5d \x01no\x0a\x0a"),
+        ])
+    }
+
+    #[test]
+    fn decodes_hamming_and_parity() {
+        assert_eq!(encode_hamming_8_4(0), 0x15);
+        assert_eq!(encode_hamming_8_4(1), 0x02);
+        for nibble in 0..16 {
+            let byte = encode_hamming_8_4(nibble);
+            assert_eq!(decode_hamming_8_4(byte), Some(nibble));
+            assert_eq!(decode_hamming_8_4(byte ^ 0x10), Some(nibble));
+            assert_eq!(decode_hamming_8_4(byte ^ 0x11), None);
+        }
+        assert_eq!(decode_odd_parity(b'A'), None);
+        assert_eq!(decode_odd_parity(with_odd_parity(b'A')), Some(b'A'));
+    }
+
+    #[test]
+    fn decodes_page_updates() {
+        let mut decoder = TeletextDecoder::default();
+        let pages = decoder.decode_pes(&subtitle_pes(0, 0x88), 1000).unwrap();
+        assert_eq!(
+            pages,
+            [TeletextPage {
+                begin: 1000,
+                lines: vec![
+                    TeletextLine {
+                        row: 20,
+                        double_height: true,
+                        runs: vec![TeletextRun {
+                            text: "¿Qué?".to_string(),
+                            color: 3,
+                            background: Some(0),
+                        }],
+                    },
+                    TeletextLine {
+                        row: 22,
+                        double_height: false,
+                        runs: vec![
+                            TeletextRun {
+                                text: "Sí  ".to_string(),
+                                color: 7,
+                                background: Some(0),
+                            },
+                            TeletextRun {
+                                text: "no".to_string(),
+                                color: 1,
+                                background: Some(0),
+                            },
+                        ],
+                    },
+                ],
+            }]
+        );
+        // A retransmission is not a new update, other pages are skipped.
+        assert!(decoder
+            .decode_pes(&subtitle_pes(0, 0x88), 2000)
+            .unwrap()
+            .is_empty());
+        assert!(decoder
+            .decode_pes(&subtitle_pes(0, 0x89), 2000)
+            .unwrap()
+            .is_empty());
+        decoder.set_national_option(Some(1)).unwrap();
+        let pages = decoder
+            .decode_pes(
+                &pes(&[header(0, 0x88, 5), row(0, 1, b"\x0b\x0b\x5b\x7e")]),
+                3000,
+            )
+            .unwrap();
+        assert_eq!(pages[0].lines[0].runs[0].text, "Äß");
+        assert!(decoder.set_page(999).is_err());
+        assert!(decoder.decode_pes(&[0x20], 0).is_err());
+    }
+
+    #[test]
+    fn shows_updates_until_the_next_one() {
+        let mut subtitles = Subtitles::new();
+        subtitles.set_teletext_page(801).unwrap();
+        subtitles
+            .append_teletext_pes(&subtitle_pes(0, 0x01), 1000)
+            .unwrap();
+        let shown = subtitles.get_subtitles_at(1500);
+        assert_eq!(shown.len(), 1);
+        let text: Vec<String> = shown[0]
+            .lines
+            .iter()
+            .map(|line| line.iter().map(|run| run.text.as_str()).collect())
+            .collect();
+        assert_eq!(text, ["¿Qué?", "Sí  no"]);
+        // An erased page clears the screen.
+        subtitles
+            .append_teletext_pes(&pes(&[header(0, 0x01, 5)]), 4000)
+            .unwrap();
+        assert_eq!(subtitles.get_subtitles_at(3999).len(), 1);
+        assert!(subtitles.get_subtitles_at(4000).is_empty());
+    }
+
+    #[test]
+    fn keeps_the_teletext_grid_when_appended_to_a_document() {
+        // Font sizes of the region and the runs, rounded to hundredths.
+        let font_sizes = |subtitles: &mut Subtitles| {
+            subtitles.set_element_size(1000, 750);
+            subtitles
+                .append_teletext_pes(&subtitle_pes(0, 0x88), 1000)
+                .unwrap();
+            let shown = subtitles.get_subtitles_at(1500);
+            let region = shown[0].region.as_ref().unwrap();
+            std::iter::once(region.style.font_size)
+                .chain(shown[0].lines.iter().map(|line| line[0].style.font_size))
+                .map(|size| ((size.width * 100.0).round(), (size.height * 100.0).round()))
+                .collect::<Vec<(f32, f32)>>()
+        };
+        let alone = font_sizes(&mut Subtitles::new());
+        assert_eq!(
+            alone,
+            [(3000.0, 3000.0), (3000.0, 6000.0), (3000.0, 3000.0)]
+        );
+        let mut subtitles = Subtitles::new();
+        subtitles
+            .load(
+                r#"<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:cellResolution="32 15"><tt:head/><tt:body/></tt:tt>"#,
+            )
+            .unwrap();
+        assert_eq!(font_sizes(&mut subtitles), alone);
+    }
+}
@@ -0,0 +1,88 @@
+// Mapping between the media time of the player and the times of the
+// document. The updates of the page receive the media time, the cues keep the
+// times of the document:
+//
+//     media = document * scale + offset + delay
+//
+// `offset` places the start of the document on the media timeline (live to
+// VOD, stitched ads, DASH periods), `scale` corrects documents authored for
+// another frame rate (e.g. 25 / 23.976) and `delay` is the user adjustment.
+
+pub struct Timeline {
+    /// Media time of the document time 0, in milliseconds.
+    pub offset: i64,
+    /// Media milliseconds per document millisecond.
+    pub scale: f64,
+    /// User adjustment in milliseconds, positive shows the subtitles later.
+    pub delay: i64,
+    /// Playback rate of the player. The page updates at the same wall clock
+    /// rate, so the media time advances more between updates when faster.
+    pub playback_rate: f64,
+}
+
+impl Timeline {
+    pub fn new() -> Timeline {
+        Timeline {
+            offset: 0,
+            scale: 1.0,
+            delay: 0,
+            playback_rate: 1.0,
+        }
+    }
+
+    pub fn to_document(&self, media_ms: i64) -> i64 {
+        let ms = media_ms as f64 - self.offset as f64 - self.delay as f64;
+        (ms / self.scale).round() as i64
+    }
+
+    pub fn to_media(&self, document_ms: i64) -> i64 {
+        let ms = document_ms as f64 * self.scale + self.offset as f64 + self.delay as f64;
+        ms.round() as i64
+    }
+
+    /// Widening of the cuepoint window, in document time, so that the
+    /// updates of a faster playback do not step over the cues.
+    pub fn get_margin_scale(&self) -> f64 {
+        self.playback_rate.max(1.0) / self.scale
+    }
+}
+
+/// Milliseconds of `ticks` of a media timescale (ticks per second), like the
+/// ones of MP4 tracks and DASH templates.
+pub fn ticks_to_ms(ticks: i128, timescale: u64) -> i64 {
+    (ticks * 1000 / timescale as i128) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_by_default() {
+        let timeline = Timeline::new();
+        assert_eq!(timeline.to_document(1500), 1500);
+        assert_eq!(timeline.to_media(1500), 1500);
+        assert_eq!(timeline.get_margin_scale(), 1.0);
+    }
+
+    #[test]
+    fn offset_scale_and_delay() {
+        let timeline = Timeline {
+            offset: 10000,
+            scale: 25.0 / 24.0,
+            delay: 500,
+            playback_rate: 2.0,
+        };
+        assert_eq!(timeline.to_media(2400), 13000);
+        assert_eq!(timeline.to_document(13000), 2400);
+        assert_eq!(timeline.to_document(10500), 0);
+        assert_eq!(timeline.to_document(0), -10080);
+        assert!((timeline.get_margin_scale() - 1.92).abs() < 1e-9);
+    }
+
+    #[test]
+    fn converts_ticks() {
+        assert_eq!(ticks_to_ms(180000, 90000), 2000);
+        assert_eq!(ticks_to_ms(-45, 90), -500);
+    }
+}
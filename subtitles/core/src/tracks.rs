@@ -0,0 +1,349 @@
+// Subtitle tracks of a title (languages, captions for the hard of hearing,
+// forced narratives), registered with their documents. The selected track is
+// loaded into the engine, which keeps its media time, timeline, view and
+// personalization, so switching tracks during playback shows the cues of the
+// current time at once.
+use super::{Subtitles, TT};
+use quick_xml::de::from_str;
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum TrackKind {
+    Subtitles,
+    /// Captions for the deaf and hard of hearing.
+    Captions,
+    /// Forced narratives: translation of foreign dialogue and on-screen text,
+    /// shown with subtitles off.
+    Forced,
+}
+
+/// Kind of a name: "subtitles", "captions" or "forced".
+pub fn parse_track_kind(name: &str) -> Result<TrackKind, String> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "subtitles" => Ok(TrackKind::Subtitles),
+        "captions" => Ok(TrackKind::Captions),
+        "forced" => Ok(TrackKind::Forced),
+        _ => Err(format!("Invalid track kind: {}", name)),
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SubtitleTrack {
+    pub id: String,
+    /// `xml:lang` of the document, empty when not set.
+    pub language: String,
+    pub kind: TrackKind,
+    /// Role of the manifest, e.g. "main" or "commentary".
+    pub role: Option<String>,
+    #[serde(skip)]
+    pub document: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackPreferences {
+    /// Subtitle languages by preference, empty for subtitles off.
+    pub languages: Vec<String>,
+    /// Captions for the hard of hearing over subtitles.
+    pub captions: bool,
+    /// Language of the audio, whose forced narratives are shown when no
+    /// subtitles are.
+    pub audio_language: Option<String>,
+}
+
+/// How well a track language matches a preferred one: 2 if equal, 1 if
+/// only the primary language is, as "es-ES" and "es".
+fn get_language_match(language: &str, preferred: &str) -> u8 {
+    let primary = |tag: &str| {
+        tag.split(['-', '_'])
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase()
+    };
+    if language.eq_ignore_ascii_case(preferred) {
+        2
+    } else if !language.is_empty() && primary(language) == primary(preferred) {
+        1
+    } else {
+        0
+    }
+}
+
+impl Subtitles {
+    /// Registers a track, or replaces the one with the same id. Its language
+    /// is the `xml:lang` of the document.
+    pub fn add_track(
+        &mut self,
+        id: &str,
+        xml: &str,
+        kind: TrackKind,
+        role: Option<&str>,
+    ) -> Result<(), String> {
+        let tt: TT = from_str(xml).map_err(|error| format!("Invalid document: {}", error))?;
+        let track = SubtitleTrack {
+            id: id.to_string(),
+            language: tt.xml_lang,
+            kind,
+            role: role.map(|role| role.to_string()),
+            document: xml.to_string(),
+        };
+        match self.tracks.iter_mut().find(|track| track.id == id) {
+            Some(existing) => *existing = track,
+            None => self.tracks.push(track),
+        }
+        if self.track_preferences.is_some() {
+            self.select_preferred_track()?;
+        } else if self.selected_track.as_deref() == Some(id) {
+            self.load_track(Some(id))?;
+        }
+        Ok(())
+    }
+
+    /// Unregisters a track, which is deselected if it was selected.
+    pub fn remove_track(&mut self, id: &str) -> Result<(), String> {
+        if !self.tracks.iter().any(|track| track.id == id) {
+            return Err(format!("Unknown track: {}", id));
+        }
+        self.tracks.retain(|track| track.id != id);
+        if self.track_preferences.is_some() {
+            self.select_preferred_track()
+        } else if self.selected_track.as_deref() == Some(id) {
+            self.load_track(None)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn get_tracks(&self) -> &[SubtitleTrack] {
+        &self.tracks
+    }
+
+    pub fn get_selected_track(&self) -> Option<&SubtitleTrack> {
+        let id = self.selected_track.as_deref()?;
+        self.tracks.iter().find(|track| track.id == id)
+    }
+
+    /// Shows a track, or none. The user choice stops the automatic selection
+    /// until preferences are set again.
+    pub fn select_track(&mut self, id: Option<&str>) -> Result<(), String> {
+        self.track_preferences = None;
+        self.load_track(id)
+    }
+
+    /// Selects the track that best matches the preferences, now and whenever
+    /// tracks are added or removed.
+    pub fn set_track_preferences(&mut self, preferences: TrackPreferences) -> Result<(), String> {
+        self.track_preferences = Some(preferences);
+        self.select_preferred_track()
+    }
+
+    /// Track of the first preferred language with a match: captions or
+    /// subtitles as preferred, then main roles, then the exact language.
+    /// Else the forced narratives of the audio language.
+    pub fn get_preferred_track(&self, preferences: &TrackPreferences) -> Option<&SubtitleTrack> {
+        let preferred_kind = if preferences.captions {
+            TrackKind::Captions
+        } else {
+            TrackKind::Subtitles
+        };
+        let get_score = |track: &SubtitleTrack, language: &str| {
+            let is_main = track.role.as_deref().is_none_or(|role| role == "main");
+            (
+                track.kind == preferred_kind,
+                is_main,
+                get_language_match(&track.language, language),
+            )
+        };
+        let find_best = |language: &str, is_forced: bool| {
+            self.tracks
+                .iter()
+                .filter(|track| (track.kind == TrackKind::Forced) == is_forced)
+                .filter(|track| get_language_match(&track.language, language) > 0)
+                // The first registered wins a tie.
+                .rev()
+                .max_by_key(|track| get_score(track, language))
+        };
+        preferences
+            .languages
+            .iter()
+            .find_map(|language| find_best(language, false))
+            .or_else(|| find_best(preferences.audio_language.as_deref()?, true))
+    }
+
+    fn select_preferred_track(&mut self) -> Result<(), String> {
+        let Some(preferences) = self.track_preferences.as_ref() else {
+            return Ok(());
+        };
+        let id = self
+            .get_preferred_track(preferences)
+            .map(|track| track.id.clone());
+        self.load_track(id.as_deref())
+    }
+
+    /// Loads the document of a track, or unloads the one shown, and shows
+    /// the cues of the current media time.
+    fn load_track(&mut self, id: Option<&str>) -> Result<(), String> {
+        match id {
+            Some(id) => {
+                let document = self
+                    .tracks
+                    .iter()
+                    .find(|track| track.id == id)
+                    .ok_or_else(|| format!("Unknown track: {}", id))?
+                    .document
+                    .clone();
+                if self.selected_track.as_deref() == Some(id) && self.source == document {
+                    return Ok(());
+                }
+                self.load(&document)?;
+                self.selected_track = Some(id.to_string());
+            }
+            None => {
+                if self.selected_track.is_none() {
+                    return Ok(());
+                }
+                self.unload();
+                self.selected_track = None;
+            }
+        }
+        self.resync_visible_subtitles();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_view::RecordingView;
+
+    fn document(language: &str, text: &str) -> String {
+        format!(
+            r#"<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling" xml:lang="{}">
+  <tt:head>
+    <tt:layout>
+      <tt:region xml:id="r1" tts:origin="10% 80%" tts:extent="80% 15%"/>
+    </tt:layout>
+  </tt:head>
+  <tt:body><tt:div region="r1">
+    <tt:p xml:id="a" begin="00:00:01.000" end="00:00:05.000"><tt:span>{} 1</tt:span></tt:p>
+    <tt:p xml:id="b" begin="00:00:05.000" end="00:00:09.000"><tt:span>{} 2</tt:span></tt:p>
+  </tt:div></tt:body>
+</tt:tt>"#,
+            language, text, text
+        )
+    }
+
+    /// The one subtitle on screen includes `text`.
+    fn is_shown(view: &RecordingView, text: &str) -> bool {
+        let shown = view.shown.lock().unwrap();
+        shown.len() == 1 && view.html.lock().unwrap()[&shown[0]].contains(text)
+    }
+
+    fn add_tracks(subtitles: &mut Subtitles) {
+        let tracks = [
+            ("ca", "ca", "Hola", TrackKind::Subtitles, None),
+            ("es", "es", "Hola", TrackKind::Subtitles, Some("main")),
+            ("es-cc", "es", "[Música] Hola", TrackKind::Captions, None),
+            (
+                "es-commentary",
+                "es-ES",
+                "Director",
+                TrackKind::Subtitles,
+                Some("commentary"),
+            ),
+            ("en-forced", "en", "Forced", TrackKind::Forced, None),
+        ];
+        for (id, language, text, kind, role) in tracks {
+            subtitles
+                .add_track(id, &document(language, text), kind, role)
+                .unwrap();
+        }
+    }
+
+    fn get_preferred(subtitles: &Subtitles, languages: &[&str], captions: bool) -> Option<String> {
+        let preferences = TrackPreferences {
+            languages: languages
+                .iter()
+                .map(|language| language.to_string())
+                .collect(),
+            captions,
+            audio_language: Some("en".to_string()),
+        };
+        subtitles
+            .get_preferred_track(&preferences)
+            .map(|track| track.id.clone())
+    }
+
+    #[test]
+    fn prefers_tracks() {
+        let mut subtitles = Subtitles::new();
+        add_tracks(&mut subtitles);
+        assert_eq!(subtitles.get_tracks()[3].language, "es-ES");
+        assert_eq!(
+            get_preferred(&subtitles, &["es-ES"], false).as_deref(),
+            Some("es")
+        );
+        assert_eq!(
+            get_preferred(&subtitles, &["es-MX"], true).as_deref(),
+            Some("es-cc")
+        );
+        assert_eq!(
+            get_preferred(&subtitles, &["fr", "CA"], true).as_deref(),
+            Some("ca")
+        );
+        assert_eq!(
+            get_preferred(&subtitles, &["fr"], false).as_deref(),
+            Some("en-forced")
+        );
+        assert_eq!(
+            get_preferred(&subtitles, &[], false).as_deref(),
+            Some("en-forced")
+        );
+        assert!(parse_track_kind("forced").is_ok());
+        assert!(parse_track_kind("chapters").is_err());
+    }
+
+    #[test]
+    fn switches_tracks_during_playback() {
+        let view = RecordingView::default();
+        let mut subtitles = Subtitles::new();
+        subtitles.set_element_size(1000, 600);
+        subtitles.set_view(Some(Box::new(view.clone())));
+        add_tracks(&mut subtitles);
+        assert!(subtitles.get_selected_track().is_none());
+        subtitles
+            .set_track_preferences(TrackPreferences {
+                languages: vec!["es".to_string()],
+                captions: true,
+                audio_language: None,
+            })
+            .unwrap();
+        subtitles.update_subtitles_for_ms(5000);
+        assert!(is_shown(&view, "[Música] Hola 2"));
+        // The new track shows its cue of the current time at once.
+        subtitles.select_track(Some("ca")).unwrap();
+        assert!(is_shown(&view, ">Hola 2"));
+        assert_eq!(subtitles.get_selected_track().unwrap().id, "ca");
+        // Manual choices are kept when tracks are added.
+        subtitles
+            .add_track("en", &document("en", "Hello"), TrackKind::Subtitles, None)
+            .unwrap();
+        assert_eq!(subtitles.get_selected_track().unwrap().id, "ca");
+        subtitles.select_track(None).unwrap();
+        assert!(view.shown.lock().unwrap().is_empty());
+        assert!(subtitles.get_subtitles_at(6000).is_empty());
+        assert!(subtitles.select_track(Some("fr")).is_err());
+        // Removing the selected track of the preferences selects the next best.
+        subtitles
+            .set_track_preferences(TrackPreferences {
+                languages: vec!["en".to_string(), "es".to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(is_shown(&view, "Hello 2"));
+        subtitles.remove_track("en").unwrap();
+        assert!(is_shown(&view, ">Hola 2"));
+        assert_eq!(subtitles.get_selected_track().unwrap().id, "es");
+    }
+}
@@ -0,0 +1,570 @@
+// MPEG-2 transport streams (ISO/IEC 13818-1) of recordings and fetched
+// chunks. The PAT and PMT list the DVB subtitle and Teletext streams with
+// their languages, and the PES packets of those streams are reassembled and
+// handed to their decoders at their presentation time.
+use super::Subtitles;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// MPEG-2 TS presentation times are 33 bits at 90 kHz and wrap around.
+pub const PTS_ROLLOVER: u64 = 1 << 33;
+
+const PACKET_SIZE: usize = 188;
+const SYNC_BYTE: u8 = 0x47;
+const PAT_PID: u16 = 0;
+
+const SUBTITLING_DESCRIPTOR: u8 = 0x59;
+const TELETEXT_DESCRIPTOR: u8 = 0x56;
+
+/// Teletext types of the subtitle pages, for the hard of hearing too.
+const TELETEXT_SUBTITLE_TYPES: [u8; 2] = [2, 5];
+
+/// Milliseconds from the presentation time `initial_pts` to `pts`.
+/// Presentation times that wrapped around after the start are unwrapped.
+pub fn get_pts_ms(pts: u64, initial_pts: u64) -> i64 {
+    let difference =
+        (pts % PTS_ROLLOVER + PTS_ROLLOVER - initial_pts % PTS_ROLLOVER) % PTS_ROLLOVER;
+    // A difference of more than half the range is a time before the start.
+    let difference = if difference > PTS_ROLLOVER / 2 {
+        difference as i64 - PTS_ROLLOVER as i64
+    } else {
+        difference as i64
+    };
+    difference * 1000 / 90000
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TsStreamKind {
+    DvbSubtitles,
+    Teletext,
+}
+
+/// A subtitle service of the PMT. A stream may carry several, one per
+/// language.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TsSubtitleStream {
+    pub pid: u16,
+    pub kind: TsStreamKind,
+    /// ISO 639-2 code, e.g. "cat".
+    pub language: String,
+    /// `subtitling_type` of DVB subtitles (0x20 and up for the hard of
+    /// hearing) or `teletext_type` (2, or 5 for the hard of hearing).
+    pub subtitle_type: u8,
+    pub composition_page_id: Option<u16>,
+    pub ancillary_page_id: Option<u16>,
+    /// Teletext page, e.g. 888.
+    pub page: Option<u16>,
+}
+
+/// A PES packet of a subtitle stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TsPes {
+    pub pid: u16,
+    /// Presentation time, 90 kHz.
+    pub pts: Option<u64>,
+    pub payload: Vec<u8>,
+}
+
+fn read_pts(data: &[u8]) -> u64 {
+    (((data[0] >> 1) & 7) as u64) << 30
+        | (data[1] as u64) << 22
+        | ((data[2] >> 1) as u64) << 15
+        | (data[3] as u64) << 7
+        | (data[4] >> 1) as u64
+}
+
+/// Presentation time and payload of a PES packet.
+fn parse_pes(data: &[u8]) -> Option<(Option<u64>, &[u8])> {
+    if data.len() < 9 || data[..3] != [0, 0, 1] {
+        return None;
+    }
+    let length = u16::from_be_bytes([data[4], data[5]]) as usize;
+    let end = if length == 0 {
+        data.len()
+    } else {
+        (6 + length).min(data.len())
+    };
+    let payload_start = 9 + data[8] as usize;
+    let pts = match data.get(9..14) {
+        Some(pts) if data[7] & 0x80 != 0 => Some(read_pts(pts)),
+        _ => None,
+    };
+    Some((pts, data.get(payload_start..end)?))
+}
+
+fn read_language(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| *byte as char)
+        .filter(|c| c.is_ascii_alphabetic())
+        .collect()
+}
+
+/// Subtitle services of the descriptors of an elementary stream.
+fn read_descriptors(pid: u16, mut descriptors: &[u8]) -> Vec<TsSubtitleStream> {
+    let mut streams = Vec::new();
+    while descriptors.len() >= 2 {
+        let (tag, length) = (descriptors[0], descriptors[1] as usize);
+        let Some(body) = descriptors.get(2..2 + length) else {
+            break;
+        };
+        descriptors = &descriptors[2 + length..];
+        match tag {
+            SUBTITLING_DESCRIPTOR => {
+                for entry in body.chunks_exact(8) {
+                    streams.push(TsSubtitleStream {
+                        pid,
+                        kind: TsStreamKind::DvbSubtitles,
+                        language: read_language(&entry[..3]),
+                        subtitle_type: entry[3],
+                        composition_page_id: Some(u16::from_be_bytes([entry[4], entry[5]])),
+                        ancillary_page_id: Some(u16::from_be_bytes([entry[6], entry[7]])),
+                        page: None,
+                    });
+                }
+            }
+            TELETEXT_DESCRIPTOR => {
+                for entry in body.chunks_exact(5) {
+                    let teletext_type = entry[3] >> 3;
+                    let (tens, units) = (entry[4] >> 4, entry[4] & 15);
+                    if !TELETEXT_SUBTITLE_TYPES.contains(&teletext_type) || tens > 9 || units > 9 {
+                        continue;
+                    }
+                    let magazine = match entry[3] & 7 {
+                        0 => 8,
+                        magazine => magazine as u16,
+                    };
+                    streams.push(TsSubtitleStream {
+                        pid,
+                        kind: TsStreamKind::Teletext,
+                        language: read_language(&entry[..3]),
+                        subtitle_type: teletext_type,
+                        composition_page_id: None,
+                        ancillary_page_id: None,
+                        page: Some(magazine * 100 + tens as u16 * 10 + units as u16),
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+    streams
+}
+
+/// Demuxer of the subtitle streams of a transport stream, fed with chunks
+/// that do not need to be aligned to packets.
+#[derive(Default)]
+pub struct TsDemuxer {
+    /// Bytes of a packet split between chunks.
+    remainder: Vec<u8>,
+    pmt_pids: HashSet<u16>,
+    pmt_versions: HashMap<u16, u8>,
+    /// Sections of the PAT and PMTs being received, by PID.
+    sections: HashMap<u16, Vec<u8>>,
+    /// Elementary streams of the programs and the subtitle services.
+    elementary_pids: HashSet<u16>,
+    streams: Vec<TsSubtitleStream>,
+    /// PES packets of the subtitle streams being received, by PID.
+    pes: HashMap<u16, Vec<u8>>,
+    first_pts: Option<u64>,
+}
+
+impl TsDemuxer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subtitle services of the PMTs received so far.
+    pub fn get_streams(&self) -> &[TsSubtitleStream] {
+        &self.streams
+    }
+
+    /// Presentation time of the first PES packet of any stream of the
+    /// programs, the start of the media.
+    pub fn get_first_pts(&self) -> Option<u64> {
+        self.first_pts
+    }
+
+    /// Reads a chunk, returning the PES packets of the subtitle streams it
+    /// completes. DVB subtitle and Teletext PES packets have their length, so
+    /// they are returned with their last byte.
+    pub fn push(&mut self, data: &[u8]) -> Vec<TsPes> {
+        let mut data_with_remainder = std::mem::take(&mut self.remainder);
+        data_with_remainder.extend_from_slice(data);
+        let data = data_with_remainder;
+        let mut pes_packets = Vec::new();
+        let mut position = 0;
+        while position + PACKET_SIZE <= data.len() {
+            // Resynchronizes on a sync byte followed by another one.
+            if data[position] != SYNC_BYTE
+                || data
+                    .get(position + PACKET_SIZE)
+                    .is_some_and(|byte| *byte != SYNC_BYTE)
+            {
+                position += 1;
+                continue;
+            }
+            let packet = &data[position..position + PACKET_SIZE];
+            self.read_packet(packet, &mut pes_packets);
+            position += PACKET_SIZE;
+        }
+        self.remainder = data[position..].to_vec();
+        pes_packets
+    }
+
+    fn read_packet(&mut self, packet: &[u8], pes_packets: &mut Vec<TsPes>) {
+        // Packets with transport errors are dropped.
+        if packet[1] & 0x80 != 0 {
+            return;
+        }
+        let unit_start = packet[1] & 0x40 != 0;
+        let pid = u16::from_be_bytes([packet[1] & 0x1F, packet[2]]);
+        let adaptation_field_control = (packet[3] >> 4) & 3;
+        if adaptation_field_control & 1 == 0 {
+            return;
+        }
+        let payload_start = if adaptation_field_control & 2 != 0 {
+            5 + packet[4] as usize
+        } else {
+            4
+        };
+        let Some(payload) = packet.get(payload_start..) else {
+            return;
+        };
+        if pid == PAT_PID || self.pmt_pids.contains(&pid) {
+            self.read_section_data(pid, unit_start, payload);
+        } else if self.streams.iter().any(|stream| stream.pid == pid) {
+            if unit_start {
+                if let Some(pes) = self.pes.remove(&pid) {
+                    pes_packets.extend(Self::get_pes(pid, &pes));
+                }
+                self.read_first_pts(payload);
+                self.pes.insert(pid, payload.to_vec());
+            } else if let Some(pes) = self.pes.get_mut(&pid) {
+                pes.extend_from_slice(payload);
+            }
+            let is_complete = self.pes.get(&pid).is_some_and(|pes| {
+                let length = match pes.get(4..6) {
+                    Some(length) => u16::from_be_bytes([length[0], length[1]]) as usize,
+                    None => 0,
+                };
+                length > 0 && pes.len() >= 6 + length
+            });
+            if is_complete {
+                let pes = self.pes.remove(&pid).unwrap();
+                pes_packets.extend(Self::get_pes(pid, &pes));
+            }
+        } else if unit_start && self.elementary_pids.contains(&pid) {
+            self.read_first_pts(payload);
+        }
+    }
+
+    fn get_pes(pid: u16, data: &[u8]) -> Option<TsPes> {
+        let (pts, payload) = parse_pes(data)?;
+        Some(TsPes {
+            pid,
+            pts,
+            payload: payload.to_vec(),
+        })
+    }
+
+    fn read_first_pts(&mut self, payload: &[u8]) {
+        if self.first_pts.is_none() {
+            self.first_pts = parse_pes(payload).and_then(|(pts, _)| pts);
+        }
+    }
+
+    /// Reassembles the PSI sections of a PID. A section starts after the
+    /// pointer field of a packet with the unit start indicator.
+    fn read_section_data(&mut self, pid: u16, unit_start: bool, payload: &[u8]) {
+        if unit_start {
+            let pointer = *payload.first().unwrap_or(&0) as usize;
+            if let Some(mut section) = self.sections.remove(&pid) {
+                section.extend_from_slice(payload.get(1..1 + pointer).unwrap_or_default());
+                self.read_section(pid, &section);
+            }
+            self.sections
+                .insert(pid, payload.get(1 + pointer..).unwrap_or_default().to_vec());
+        } else if let Some(section) = self.sections.get_mut(&pid) {
+            section.extend_from_slice(payload);
+        } else {
+            return;
+        }
+        let section = &self.sections[&pid];
+        if section.len() >= 3 {
+            let length = 3 + (u16::from_be_bytes([section[1] & 0x0F, section[2]]) as usize);
+            if section.len() >= length {
+                let section = self.sections.remove(&pid).unwrap();
+                self.read_section(pid, &section[..length]);
+            }
+        }
+    }
+
+    fn read_section(&mut self, pid: u16, section: &[u8]) {
+        // Header, and the CRC at the end.
+        if section.len() < 12 {
+            return;
+        }
+        let body = &section[8..section.len() - 4];
+        match section[0] {
+            0x00 if pid == PAT_PID => {
+                for entry in body.chunks_exact(4) {
+                    let program_number = u16::from_be_bytes([entry[0], entry[1]]);
+                    if program_number != 0 {
+                        self.pmt_pids
+                            .insert(u16::from_be_bytes([entry[2] & 0x1F, entry[3]]));
+                    }
+                }
+            }
+            0x02 if self.pmt_pids.contains(&pid) && body.len() >= 4 => {
+                let version = (section[5] >> 1) & 0x1F;
+                if self.pmt_versions.insert(pid, version) == Some(version) {
+                    return;
+                }
+                let program_info_length =
+                    (u16::from_be_bytes([body[2] & 0x0F, body[3]]) as usize).min(body.len() - 4);
+                let mut entries = &body[4 + program_info_length..];
+                let mut streams = Vec::new();
+                while entries.len() >= 5 {
+                    let pid = u16::from_be_bytes([entries[1] & 0x1F, entries[2]]);
+                    let length = u16::from_be_bytes([entries[3] & 0x0F, entries[4]]) as usize;
+                    let descriptors = entries.get(5..5 + length).unwrap_or_default();
+                    self.elementary_pids.insert(pid);
+                    streams.extend(read_descriptors(pid, descriptors));
+                    entries = entries.get(5 + length..).unwrap_or_default();
+                }
+                // A new version of the PMT replaces the services of its streams.
+                let pids: HashSet<u16> = streams.iter().map(|stream| stream.pid).collect();
+                self.streams.retain(|stream| !pids.contains(&stream.pid));
+                self.streams.extend(streams);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Subtitles {
+    /// Subtitle services of the transport stream appended so far.
+    pub fn get_ts_streams(&self) -> Vec<TsSubtitleStream> {
+        self.ts_demuxer
+            .as_ref()
+            .map(|demuxer| demuxer.get_streams().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Shows the subtitle service `index` of `get_ts_streams`. Without it the
+    /// first service is shown.
+    pub fn select_ts_stream(&mut self, index: usize) -> Result<(), String> {
+        let stream = self
+            .get_ts_streams()
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("Invalid subtitle stream: {}", index))?;
+        match stream.kind {
+            TsStreamKind::DvbSubtitles => self.set_dvb_pages(
+                stream.composition_page_id.unwrap_or_default(),
+                stream.ancillary_page_id,
+            ),
+            TsStreamKind::Teletext => self.set_teletext_page(stream.page.unwrap_or(888))?,
+        }
+        self.ts_stream = Some(stream);
+        Ok(())
+    }
+
+    /// Appends a chunk of a transport stream. `initial_pts` is the
+    /// presentation time (90 kHz) of the start of the media timeline, by
+    /// default the first one of the stream.
+    pub fn append_ts(&mut self, data: &[u8], initial_pts: Option<u64>) -> Result<(), String> {
+        let demuxer = self.ts_demuxer.get_or_insert_with(TsDemuxer::new);
+        let pes_packets = demuxer.push(data);
+        let first_pts = demuxer.get_first_pts();
+        if self.ts_stream.is_none() && !self.get_ts_streams().is_empty() {
+            self.select_ts_stream(0)?;
+        }
+        let Some(stream) = self.ts_stream.clone() else {
+            return Ok(());
+        };
+        for pes in pes_packets.iter().filter(|pes| pes.pid == stream.pid) {
+            // Subtitles without presentation time can not be placed.
+            let Some(pts) = pes.pts else {
+                continue;
+            };
+            let ms = get_pts_ms(pts, initial_pts.or(first_pts).unwrap_or(pts));
+            match stream.kind {
+                TsStreamKind::DvbSubtitles => self.append_dvb_pes(&pes.payload, ms)?,
+                TsStreamKind::Teletext => self.append_teletext_pes(&pes.payload, ms)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packets of a payload, with the unit start indicator on the first one
+    /// and stuffing in the adaptation field of the last one.
+    fn packets(pid: u16, payload: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (index, chunk) in payload.chunks(PACKET_SIZE - 4).enumerate() {
+            let unit_start = if index == 0 { 0x40 } else { 0 };
+            data.extend_from_slice(&[SYNC_BYTE, unit_start | (pid >> 8) as u8, pid as u8]);
+            if chunk.len() == PACKET_SIZE - 4 {
+                data.push(0x10 | (index as u8 & 15));
+            } else {
+                let stuffing = PACKET_SIZE - 5 - chunk.len();
+                data.push(0x30 | (index as u8 & 15));
+                data.push(stuffing as u8);
+                if stuffing > 0 {
+                    data.push(0);
+                    data.resize(data.len() + stuffing - 1, 0xFF);
+                }
+            }
+            data.extend_from_slice(chunk);
+        }
+        data
+    }
+
+    /// A section with its header; the CRC is not checked.
+    fn section(table_id: u8, version: u8, body: &[u8]) -> Vec<u8> {
+        let length = 5 + body.len() + 4;
+        let mut section = vec![0, table_id, 0xB0 | (length >> 8) as u8, length as u8];
+        section.extend_from_slice(&[0, 1, 0xC1 | (version << 1), 0, 0]);
+        section.extend_from_slice(body);
+        section.extend_from_slice(&[0, 0, 0, 0]);
+        section
+    }
+
+    fn pmt() -> Vec<u8> {
+        let mut body = vec![0xE1, 0x00, 0xF0, 0x00];
+        // Video, then DVB subtitles and Teletext.
+        body.extend_from_slice(&[0x02, 0xE1, 0x00, 0xF0, 0x00]);
+        body.extend_from_slice(&[0x06, 0xE1, 0x01, 0xF0, 10, SUBTITLING_DESCRIPTOR, 8]);
+        body.extend_from_slice(b"cat\x10\x00\x01\x00\x02");
+        body.extend_from_slice(&[0x06, 0xE1, 0x02, 0xF0, 12, TELETEXT_DESCRIPTOR, 10]);
+        body.extend_from_slice(b"spa\x08\x88cat\x28\x88");
+        section(0x02, 0, &body)
+    }
+
+    fn pes(pts: u64, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0, 0, 1, 0xBD];
+        data.extend_from_slice(&((payload.len() + 8) as u16).to_be_bytes());
+        data.extend_from_slice(&[0x80, 0x80, 5]);
+        data.extend_from_slice(&[
+            0x21 | ((pts >> 29) & 0x0E) as u8,
+            (pts >> 22) as u8,
+            0x01 | (pts >> 14) as u8 & 0xFE,
+            (pts >> 7) as u8,
+            0x01 | (pts << 1) as u8,
+        ]);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn stream() -> Vec<u8> {
+        let mut data = packets(PAT_PID, &section(0x00, 0, &[0, 1, 0xF0, 0x20]));
+        data.extend(packets(0x1020, &pmt()));
+        data.extend(packets(0x100, &pes(900000, &[0; 10])));
+        data
+    }
+
+    #[test]
+    fn lists_the_subtitle_streams() {
+        let mut demuxer = TsDemuxer::new();
+        let data = stream();
+        // Chunks are not aligned to packets.
+        for chunk in data.chunks(100) {
+            assert!(demuxer.push(chunk).is_empty());
+        }
+        let streams = demuxer.get_streams();
+        assert_eq!(
+            streams
+                .iter()
+                .map(|stream| (stream.pid, stream.kind, stream.language.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (0x101, TsStreamKind::DvbSubtitles, "cat"),
+                (0x102, TsStreamKind::Teletext, "cat"),
+            ]
+        );
+        assert_eq!(
+            (streams[0].composition_page_id, streams[0].ancillary_page_id),
+            (Some(1), Some(2))
+        );
+        // The Spanish Teletext page is not a subtitle page, the Catalan one is
+        // for the hard of hearing.
+        assert_eq!((streams[1].page, streams[1].subtitle_type), (Some(888), 5));
+        assert_eq!(demuxer.get_first_pts(), Some(900000));
+    }
+
+    #[test]
+    fn reassembles_pes_packets() {
+        let mut demuxer = TsDemuxer::new();
+        demuxer.push(&stream());
+        let payload: Vec<u8> = (0..400).map(|index| index as u8).collect();
+        let mut data = vec![0x12; 7];
+        data.extend(packets(0x102, &pes(PTS_ROLLOVER - 9000, &payload)));
+        let pes_packets = demuxer.push(&data);
+        assert_eq!(
+            pes_packets,
+            [TsPes {
+                pid: 0x102,
+                pts: Some(PTS_ROLLOVER - 9000),
+                payload,
+            }]
+        );
+        assert_eq!(get_pts_ms(PTS_ROLLOVER - 9000, 900000), -10100);
+        assert_eq!(get_pts_ms(9000, PTS_ROLLOVER - 9000), 200);
+    }
+
+    /// Teletext data unit of a packet of magazine 8, with the bits of the
+    /// Hamming coded address in transmission order.
+    fn teletext_unit(address: [u8; 2], payload: &[u8]) -> Vec<u8> {
+        let mut unit = vec![0x03, 44, 0x02, 0xE4];
+        unit.extend(
+            address
+                .iter()
+                .chain(payload)
+                .map(|byte| byte.reverse_bits()),
+        );
+        unit.resize(46, 0x20u8.reverse_bits());
+        unit
+    }
+
+    #[test]
+    fn shows_the_teletext_subtitles_of_a_recording() {
+        let mut subtitles = Subtitles::new();
+        subtitles.append_ts(&stream(), None).unwrap();
+        // The first service is shown until another one is selected.
+        assert_eq!(
+            subtitles.ts_stream.as_ref().map(|stream| stream.kind),
+            Some(TsStreamKind::DvbSubtitles)
+        );
+        subtitles.select_ts_stream(1).unwrap();
+        assert!(subtitles.select_ts_stream(2).is_err());
+        // Page 888 erased with the subtitle flag, then row 20 with boxed text.
+        let header = [0xD0, 0xD0, 0x15, 0xD0, 0x15, 0xD0, 0x15, 0x15];
+        let text: Vec<u8> = b"\x0b\x0bHola"
+            .iter()
+            .map(|code| {
+                if code.count_ones() % 2 == 1 {
+                    *code
+                } else {
+                    code | 0x80
+                }
+            })
+            .collect();
+        let mut data = vec![0x10];
+        data.extend(teletext_unit([0x15, 0x15], &header));
+        data.extend(teletext_unit([0x15, 0x8C], &text));
+        subtitles
+            .append_ts(&packets(0x102, &pes(900000 + 90000, &data)), None)
+            .unwrap();
+        let shown = subtitles.get_subtitles_at(1000);
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].lines[0][0].text, "Hola");
+        assert!(subtitles.get_subtitles_at(999).is_empty());
+    }
+}
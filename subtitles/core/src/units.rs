@@ -0,0 +1,126 @@
+// TTML <length> values: "12px", "0.5c", "180%" and "1.2em". The conversion
+// to display pixels depends on the document (cell resolution, root extent)
+// and on the element (parent font size), so it is done by `Subtitles`.
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Pixel,
+    Cell,
+    Percentage,
+    Em,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Length {
+    pub value: f32,
+    pub unit: Unit,
+}
+
+impl Length {
+    pub fn parse(value: &str) -> Result<Length, String> {
+        let value = value.trim();
+        let (number, unit) = if let Some(number) = value.strip_suffix("px") {
+            (number, Unit::Pixel)
+        } else if let Some(number) = value.strip_suffix("em") {
+            (number, Unit::Em)
+        } else if let Some(number) = value.strip_suffix('c') {
+            (number, Unit::Cell)
+        } else if let Some(number) = value.strip_suffix('%') {
+            (number, Unit::Percentage)
+        } else {
+            return Err(format!("Unknown length unit: {}", value));
+        };
+        let number = number
+            .parse::<f32>()
+            .map_err(|_| format!("Invalid length: {}", value))?;
+        if !number.is_finite() {
+            return Err(format!("Invalid length: {}", value));
+        }
+        Ok(Length {
+            value: number,
+            unit,
+        })
+    }
+
+    /// Parses a whitespace separated list of lengths, like "1c 2c" or "10% 80%".
+    pub fn parse_list(value: &str) -> Result<Vec<Length>, String> {
+        value.split_whitespace().map(Length::parse).collect()
+    }
+}
+
+/// Font size in display pixels. Both values are equal unless the document
+/// uses an anamorphic two-dimensional `tts:fontSize`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FontSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl FontSize {
+    pub fn horizontal_scale(&self) -> f32 {
+        if self.height > 0.0 {
+            self.width / self.height
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Formats a pixel value for CSS without long float tails (14.400001 -> 14.4).
+pub fn format_px(value: f32) -> String {
+    format!("{}px", format_number(value))
+}
+
+pub fn format_number(value: f32) -> String {
+    let number = format!("{:.2}", value);
+    let number = number.trim_end_matches('0').trim_end_matches('.');
+    if number == "-0" {
+        "0".to_string()
+    } else {
+        number.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_unit() {
+        assert_eq!(
+            Length::parse("12px"),
+            Ok(Length {
+                value: 12.0,
+                unit: Unit::Pixel
+            })
+        );
+        assert_eq!(Length::parse("0.5c").unwrap().unit, Unit::Cell);
+        assert_eq!(Length::parse(" 80% ").unwrap().value, 80.0);
+        assert_eq!(Length::parse("1.2em").unwrap().unit, Unit::Em);
+        assert_eq!(Length::parse("-1c").unwrap().value, -1.0);
+    }
+
+    #[test]
+    fn rejects_invalid_lengths() {
+        assert!(Length::parse("12").is_err());
+        assert!(Length::parse("px").is_err());
+        assert!(Length::parse("1.2.3%").is_err());
+        assert!(Length::parse("infpx").is_err());
+    }
+
+    #[test]
+    fn parses_lists() {
+        let lengths = Length::parse_list("10% 80%").unwrap();
+        assert_eq!(lengths.len(), 2);
+        assert!(Length::parse_list("1c 2").is_err());
+    }
+
+    #[test]
+    fn formats_numbers_without_float_tails() {
+        assert_eq!(format_number(14.400001), "14.4");
+        assert_eq!(format_number(2.0), "2");
+        assert_eq!(format_number(-0.001), "0");
+        assert_eq!(format_px(12.345), "12.35px");
+    }
+}
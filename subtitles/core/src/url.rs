@@ -0,0 +1,115 @@
+// Resolution of the relative URLs of streaming manifests (BaseURL, segment
+// templates, playlist entries) against the URL of the manifest.
+
+/// `url` has a scheme, like "https:" or "data:".
+fn has_scheme(url: &str) -> bool {
+    match url.find(':') {
+        Some(index) => {
+            let scheme = &url[..index];
+            !scheme.is_empty()
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        }
+        None => false,
+    }
+}
+
+/// Removes the "." and ".." segments of a path.
+fn normalize_path(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    let mut parts = path.split('/').peekable();
+    while let Some(segment) = parts.next() {
+        let is_last = parts.peek().is_none();
+        match segment {
+            "." => {
+                if is_last {
+                    segments.push("");
+                }
+            }
+            ".." => {
+                if segments.len() > 1 {
+                    segments.pop();
+                }
+                if is_last {
+                    segments.push("");
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+pub fn resolve_url(base: &str, url: &str) -> String {
+    if base.is_empty() || has_scheme(url) {
+        return url.to_string();
+    }
+    let base = base.split(['?', '#']).next().unwrap_or(base);
+    let (scheme, rest) = match base.split_once("://") {
+        Some(split) => split,
+        None => ("", base),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let origin = if scheme.is_empty() {
+        authority.to_string()
+    } else {
+        format!("{}://{}", scheme, authority)
+    };
+    if let Some(url) = url.strip_prefix("//") {
+        return format!("{}://{}", scheme, url);
+    }
+    if url.is_empty() {
+        return base.to_string();
+    }
+    let (url, suffix) = match url.find(['?', '#']) {
+        Some(index) => url.split_at(index),
+        None => (url, ""),
+    };
+    let path = if url.starts_with('/') {
+        url.to_string()
+    } else {
+        let directory = &path[..path.rfind('/').map(|index| index + 1).unwrap_or(0)];
+        format!("{}{}", directory, url)
+    };
+    format!("{}{}{}", origin, normalize_path(&path), suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_relative_urls() {
+        let base = "https://cdn.example.com/live/stream.mpd?token=1";
+        assert_eq!(
+            resolve_url(base, "text/seg-1.mp4"),
+            "https://cdn.example.com/live/text/seg-1.mp4"
+        );
+        assert_eq!(
+            resolve_url(base, "../vod/seg.mp4?a=b"),
+            "https://cdn.example.com/vod/seg.mp4?a=b"
+        );
+        assert_eq!(
+            resolve_url(base, "/root.vtt"),
+            "https://cdn.example.com/root.vtt"
+        );
+        assert_eq!(
+            resolve_url(base, "//other.example.com/a.vtt"),
+            "https://other.example.com/a.vtt"
+        );
+        assert_eq!(
+            resolve_url(base, "http://other.example.com/"),
+            "http://other.example.com/"
+        );
+        assert_eq!(resolve_url("", "seg.mp4"), "seg.mp4");
+        assert_eq!(
+            resolve_url("https://cdn.example.com", "a/./b.vtt"),
+            "https://cdn.example.com/a/b.vtt"
+        );
+    }
+}
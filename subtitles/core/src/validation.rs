@@ -0,0 +1,824 @@
+// Conformance checks for the EBU-TT-D and IMSC1 profiles. The deserialized
+// document has no positions nor namespaces, so the attributes are checked on
+// a scan of the source with the line and path of every element, and the
+// timing and layout on the document loaded by `Subtitles`.
+use super::units::{Length, Unit};
+use super::{time_to_ms, Region, SubtitleElement, Subtitles};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::ResolveResult;
+use quick_xml::reader::NsReader;
+use serde::Serialize;
+use std::collections::HashMap;
+
+const TTS: &str = "http://www.w3.org/ns/ttml#styling";
+const TTP: &str = "http://www.w3.org/ns/ttml#parameter";
+const SMPTE: &str = "http://www.smpte-ra.org/schemas/2052-1/2010/smpte-tt";
+const EBUTTS: &str = "urn:ebu:tt:style";
+
+/// Shorter subtitles can not be read.
+const MINIMUM_DURATION_MS: i64 = 500;
+/// Two frames at 25 fps, shorter gaps between subtitles flicker.
+const MINIMUM_GAP_MS: i64 = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Path of the element, e.g. "/tt:tt[1]/tt:body[1]/tt:div[1]/tt:p[3]".
+    pub path: String,
+    /// Line of the element in the source, starting at 1.
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Profiles {
+    ebu_tt_d: bool,
+    imsc_text: bool,
+    imsc_image: bool,
+}
+
+impl Profiles {
+    fn is_imsc(&self) -> bool {
+        self.imsc_text || self.imsc_image
+    }
+
+    fn add(&mut self, designator: &str) {
+        let designator = designator.trim();
+        if designator.starts_with("urn:ebu:tt:distribution") {
+            self.ebu_tt_d = true;
+        }
+        // IMSC 1.0 uses "imsc1/text", IMSC 1.1 "imsc1#text".
+        if designator.contains("ttml/profile/imsc1") {
+            if designator.ends_with("text") {
+                self.imsc_text = true;
+            } else if designator.ends_with("image") {
+                self.imsc_image = true;
+            }
+        }
+    }
+}
+
+struct Attribute {
+    namespace: Option<String>,
+    name: String,
+    /// Name as written in the source, e.g. "tts:fontSize".
+    qualified_name: String,
+    value: String,
+}
+
+struct Element {
+    name: String,
+    path: String,
+    line: usize,
+    attributes: Vec<Attribute>,
+}
+
+impl Element {
+    fn get_attribute(&self, namespace: Option<&str>, name: &str) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.namespace.as_deref() == namespace && attribute.name == name)
+    }
+
+    fn get_id(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.qualified_name == "xml:id")
+            .map(|attribute| attribute.value.as_str())
+    }
+
+    fn is_content(&self) -> bool {
+        matches!(self.name.as_str(), "body" | "div" | "p" | "span" | "br")
+    }
+}
+
+struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    fn push(&mut self, severity: Severity, element: Option<&Element>, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            message,
+            path: element
+                .map(|element| element.path.clone())
+                .unwrap_or_default(),
+            line: element.map(|element| element.line),
+        });
+    }
+}
+
+fn get_attributes(reader: &NsReader<&[u8]>, start: &BytesStart) -> Result<Vec<Attribute>, String> {
+    let mut attributes: Vec<Attribute> = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|error| error.to_string())?;
+        let qualified_name = String::from_utf8_lossy(attribute.key.as_ref()).to_string();
+        if qualified_name == "xmlns" || qualified_name.starts_with("xmlns:") {
+            continue;
+        }
+        let (namespace, name) = reader.resolve_attribute(attribute.key);
+        let namespace = match namespace {
+            ResolveResult::Bound(namespace) => {
+                Some(String::from_utf8_lossy(namespace.as_ref()).to_string())
+            }
+            _ => None,
+        };
+        attributes.push(Attribute {
+            namespace,
+            name: String::from_utf8_lossy(name.as_ref()).to_string(),
+            qualified_name,
+            value: attribute
+                .unescape_value()
+                .map_err(|error| error.to_string())?
+                .to_string(),
+        });
+    }
+    Ok(attributes)
+}
+
+fn to_error(path: &str, line: usize, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        message,
+        path: path.to_string(),
+        line: Some(line),
+    }
+}
+
+/// Elements of the source in document order. A malformed document stops the
+/// scan with an error at the line where it was found.
+fn scan_elements(xml: &str) -> Result<Vec<Element>, Diagnostic> {
+    let mut reader = NsReader::from_str(xml);
+    let mut elements: Vec<Element> = Vec::new();
+    // Path of the open elements and the number of children of each name.
+    let mut stack: Vec<(String, HashMap<String, usize>)> = vec![(String::new(), HashMap::new())];
+    // Lines are counted as the reader advances.
+    let mut line = 1;
+    let mut line_position = 0;
+    loop {
+        let position = reader.buffer_position();
+        line += xml.as_bytes()[line_position..position]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count();
+        line_position = position;
+        let (start, is_empty) = match reader.read_event() {
+            Ok(Event::Start(start)) => (start, false),
+            Ok(Event::Empty(start)) => (start, true),
+            Ok(Event::End(_)) => {
+                stack.pop();
+                continue;
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => continue,
+            Err(error) => {
+                let path = stack.last().map(|(path, _)| path.as_str()).unwrap_or("");
+                return Err(to_error(
+                    path,
+                    line,
+                    format!("Malformed document: {}", error),
+                ));
+            }
+        };
+        let qualified_name = String::from_utf8_lossy(start.name().as_ref()).to_string();
+        let (parent_path, children) = match stack.last_mut() {
+            Some(parent) => parent,
+            None => return Err(to_error("", line, "Element after the root".to_string())),
+        };
+        let count = children.entry(qualified_name.clone()).or_insert(0);
+        *count += 1;
+        let path = format!("{}/{}[{}]", parent_path, qualified_name, count);
+        let attributes = get_attributes(&reader, &start)
+            .map_err(|error| to_error(&path, line, format!("Malformed attribute: {}", error)))?;
+        elements.push(Element {
+            name: String::from_utf8_lossy(start.local_name().as_ref()).to_string(),
+            path: path.clone(),
+            line,
+            attributes,
+        });
+        if !is_empty {
+            stack.push((path, HashMap::new()));
+        }
+    }
+    Ok(elements)
+}
+
+/// Units allowed for a length attribute by the profile, `None` for any.
+fn get_allowed_units(profiles: &Profiles, element: &Element, name: &str) -> Option<Vec<Unit>> {
+    if !profiles.ebu_tt_d {
+        return None;
+    }
+    match name {
+        "fontSize" | "lineHeight" | "padding" => Some(vec![Unit::Percentage]),
+        "origin" | "extent" if element.name == "tt" => Some(vec![Unit::Pixel]),
+        "origin" | "extent" => Some(vec![Unit::Percentage, Unit::Pixel]),
+        "linePadding" => Some(vec![Unit::Cell]),
+        _ => None,
+    }
+}
+
+fn get_unit_name(unit: Unit) -> &'static str {
+    match unit {
+        Unit::Pixel => "px",
+        Unit::Cell => "c",
+        Unit::Percentage => "%",
+        Unit::Em => "em",
+    }
+}
+
+/// Length attributes: syntax, number of values, sign and units.
+fn check_lengths(
+    diagnostics: &mut Diagnostics,
+    profiles: &Profiles,
+    element: &Element,
+    has_root_extent: bool,
+) {
+    for attribute in element.attributes.iter() {
+        let (min_count, max_count, allow_negative) =
+            match (attribute.namespace.as_deref(), attribute.name.as_str()) {
+                (Some(TTS), "fontSize") => (1, 2, false),
+                (Some(TTS), "lineHeight") => (1, 1, false),
+                (Some(TTS), "origin") => (2, 2, true),
+                (Some(TTS), "extent") => (2, 2, false),
+                (Some(TTS), "padding") => (1, 4, false),
+                (Some(EBUTTS), "linePadding") => (1, 1, false),
+                _ => continue,
+            };
+        let value = attribute.value.trim();
+        if value == "auto" || (value == "normal" && attribute.name == "lineHeight") {
+            continue;
+        }
+        let lengths = match Length::parse_list(value) {
+            Ok(lengths) => lengths,
+            Err(error) => {
+                diagnostics.push(
+                    Severity::Error,
+                    Some(element),
+                    format!("Invalid {}: {}", attribute.qualified_name, error),
+                );
+                continue;
+            }
+        };
+        if lengths.len() < min_count || lengths.len() > max_count {
+            diagnostics.push(
+                Severity::Error,
+                Some(element),
+                format!(
+                    "Invalid number of lengths in {}: {}",
+                    attribute.qualified_name, value
+                ),
+            );
+        }
+        if !allow_negative && lengths.iter().any(|length| length.value < 0.0) {
+            diagnostics.push(
+                Severity::Error,
+                Some(element),
+                format!("Negative length in {}: {}", attribute.qualified_name, value),
+            );
+        }
+        let uses_pixels = lengths.iter().any(|length| length.unit == Unit::Pixel);
+        if uses_pixels
+            && !has_root_extent
+            && element.name != "tt"
+            && (profiles.ebu_tt_d || profiles.is_imsc())
+        {
+            diagnostics.push(
+                Severity::Error,
+                Some(element),
+                format!(
+                    "Pixel lengths require a tts:extent on the root element: {}",
+                    attribute.qualified_name
+                ),
+            );
+        }
+        if let Some(units) = get_allowed_units(profiles, element, &attribute.name) {
+            if let Some(length) = lengths.iter().find(|length| !units.contains(&length.unit)) {
+                diagnostics.push(
+                    Severity::Error,
+                    Some(element),
+                    format!(
+                        "Unit {} not allowed in {} by EBU-TT-D: {}",
+                        get_unit_name(length.unit),
+                        attribute.qualified_name,
+                        value
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Elements and attributes excluded by the profiles.
+fn check_features(diagnostics: &mut Diagnostics, profiles: &Profiles, element: &Element) {
+    if profiles.ebu_tt_d {
+        if element.is_content() {
+            if let Some(attribute) = element
+                .attributes
+                .iter()
+                .find(|attribute| attribute.namespace.as_deref() == Some(TTS))
+            {
+                diagnostics.push(
+                    Severity::Error,
+                    Some(element),
+                    format!(
+                        "Inline styling is not allowed by EBU-TT-D: {}",
+                        attribute.qualified_name
+                    ),
+                );
+            }
+        }
+        if let Some(attribute) = element.get_attribute(None, "dur") {
+            diagnostics.push(
+                Severity::Error,
+                Some(element),
+                format!(
+                    "Attribute not allowed by EBU-TT-D: {}",
+                    attribute.qualified_name
+                ),
+            );
+        }
+        if element.name == "set" {
+            diagnostics.push(
+                Severity::Error,
+                Some(element),
+                "Animations are not allowed by EBU-TT-D".to_string(),
+            );
+        }
+    }
+    if profiles.ebu_tt_d || profiles.is_imsc() {
+        if let Some(attribute) = element
+            .get_attribute(Some(TTP), "timeBase")
+            .filter(|attribute| attribute.value != "media")
+        {
+            diagnostics.push(
+                Severity::Error,
+                Some(element),
+                format!("Time base not allowed by the profile: {}", attribute.value),
+            );
+        }
+    }
+    if profiles.imsc_text {
+        if let Some(attribute) = element.get_attribute(Some(SMPTE), "backgroundImage") {
+            diagnostics.push(
+                Severity::Error,
+                Some(element),
+                format!(
+                    "Images are not allowed by the IMSC1 text profile: {}",
+                    attribute.qualified_name
+                ),
+            );
+        }
+    }
+    if profiles.imsc_image && matches!(element.name.as_str(), "p" | "span") {
+        diagnostics.push(
+            Severity::Error,
+            Some(element),
+            format!(
+                "Text is not allowed by the IMSC1 image profile: {}",
+                element.name
+            ),
+        );
+    }
+    if profiles.is_imsc() {
+        if let Some(attribute) = element.get_attribute(Some(TTS), "textOutline") {
+            // "<color>? <thickness> <blur>?", a blur radius is excluded.
+            let lengths = attribute
+                .value
+                .split_whitespace()
+                .filter(|token| Length::parse(token).is_ok())
+                .count();
+            if lengths > 1 {
+                diagnostics.push(
+                    Severity::Error,
+                    Some(element),
+                    format!(
+                        "Blurred outlines are not allowed by IMSC1: {}",
+                        attribute.value
+                    ),
+                );
+            }
+        }
+    }
+}
+
+struct Cue<'a> {
+    element: Option<&'a Element>,
+    id: String,
+    region: Option<String>,
+    begin: i64,
+    end: i64,
+}
+
+impl Subtitles {
+    fn get_profiles(&self, elements: &[Element]) -> Profiles {
+        let mut profiles = Profiles::default();
+        for metadata in self.tt.iter().filter_map(|tt| tt.head.metadata.as_ref()) {
+            for designator in metadata
+                .document_metadata
+                .iter()
+                .flat_map(|document_metadata| document_metadata.conforms_to_standard.iter())
+            {
+                profiles.add(designator);
+            }
+        }
+        if let Some(root) = elements.first() {
+            for name in ["profile", "contentProfiles"] {
+                if let Some(attribute) = root.get_attribute(Some(TTP), name) {
+                    attribute
+                        .value
+                        .split_whitespace()
+                        .for_each(|designator| profiles.add(designator));
+                }
+            }
+        }
+        profiles
+    }
+
+    /// Style and region references.
+    fn check_references(&self, diagnostics: &mut Diagnostics, element: &Element) {
+        if let Some(style) = element.get_attribute(None, "style") {
+            for style_id in style.value.split_whitespace() {
+                if !self.styles_index.contains_key(style_id) {
+                    diagnostics.push(
+                        Severity::Error,
+                        Some(element),
+                        format!("Unknown style: {}", style_id),
+                    );
+                }
+            }
+        }
+        if let Some(region) = element.get_attribute(None, "region") {
+            if !self.region_index.contains_key(region.value.as_str()) {
+                diagnostics.push(
+                    Severity::Error,
+                    Some(element),
+                    format!("Unknown region: {}", region.value),
+                );
+            }
+        }
+    }
+
+    /// Position of a region as fractions of the root container (left, top,
+    /// right, bottom), independent of the size of the display element.
+    fn get_region_rect(&self, region: &Region) -> Option<[f32; 4]> {
+        let to_fraction = |length: &Length, direction: &str| match length.unit {
+            Unit::Percentage => Some(length.value / 100.0),
+            Unit::Cell => {
+                let cell_resolution = &self.tt_root_config.cell_resolution;
+                let cells = if direction == "x" {
+                    cell_resolution.columns
+                } else {
+                    cell_resolution.rows
+                };
+                Some(length.value / cells as f32)
+            }
+            Unit::Pixel => {
+                let extent = self.tt_root_config.extent.as_ref()?;
+                let size = if direction == "x" {
+                    extent.width
+                } else {
+                    extent.height
+                };
+                Some(length.value / size)
+            }
+            Unit::Em => None,
+        };
+        let get_pair = |value: Option<&String>, default: f32| match value {
+            Some(value) if value != "auto" => match Length::parse_list(value).ok()?[..] {
+                [x, y] => Some((to_fraction(&x, "x")?, to_fraction(&y, "y")?)),
+                _ => None,
+            },
+            _ => Some((default, default)),
+        };
+        let (left, top) = get_pair(region.origin.as_ref(), 0.0)?;
+        let (width, height) = get_pair(region.extent.as_ref(), 1.0)?;
+        Some([left, top, left + width, top + height])
+    }
+
+    fn are_regions_overlapping(&self, first: &str, second: &str) -> bool {
+        let rects = (
+            self.get_region(first)
+                .and_then(|region| self.get_region_rect(region)),
+            self.get_region(second)
+                .and_then(|region| self.get_region_rect(region)),
+        );
+        match rects {
+            (Some(first), Some(second)) => {
+                // Tolerance for the rounding of percentages of adjacent regions.
+                let epsilon = 0.0001;
+                first[0] + epsilon < second[2]
+                    && second[0] + epsilon < first[2]
+                    && first[1] + epsilon < second[3]
+                    && second[1] + epsilon < first[3]
+            }
+            _ => false,
+        }
+    }
+
+    /// Timed paragraphs and image divisions with their source element.
+    fn get_cues<'a>(
+        &self,
+        diagnostics: &mut Diagnostics,
+        profiles: &Profiles,
+        elements: &'a [Element],
+    ) -> Vec<Cue<'a>> {
+        let ids: HashMap<&str, &Element> = elements
+            .iter()
+            .filter_map(|element| Some((element.get_id()?, element)))
+            .collect();
+        let divs: Vec<&Element> = elements
+            .iter()
+            .filter(|element| element.name == "div")
+            .collect();
+        let has_regions = !self.region_index.is_empty();
+        let mut cues: Vec<Cue> = Vec::new();
+        for index in 0..self.subtitle_refs.len() {
+            let subtitle = match self.get_subtitle_element(index) {
+                Some(subtitle) => subtitle,
+                None => continue,
+            };
+            let (element, begin, end) = match &subtitle {
+                SubtitleElement::Paragraph(_, p) => (
+                    ids.get(p.id.as_str()).copied(),
+                    Some(&p.begin),
+                    Some(&p.end),
+                ),
+                SubtitleElement::Image(div_index, div) => (
+                    div.id
+                        .as_deref()
+                        .and_then(|id| ids.get(id).copied())
+                        .or_else(|| divs.get(*div_index).copied()),
+                    div.begin.as_ref(),
+                    div.end.as_ref(),
+                ),
+            };
+            let id = subtitle.get_id();
+            let region = subtitle.get_region_id().cloned();
+            if region.is_none() && (has_regions || profiles.ebu_tt_d) {
+                let severity = if profiles.ebu_tt_d {
+                    Severity::Error
+                } else {
+                    Severity::Warning
+                };
+                diagnostics.push(
+                    severity,
+                    element,
+                    format!("Subtitle {} is not in a region and is not shown", id),
+                );
+            }
+            let mut times: Vec<i64> = Vec::new();
+            for (name, time) in [("begin", begin), ("end", end)] {
+                match time.map(|time| time_to_ms(time)) {
+                    Some(Ok(ms)) => times.push(ms),
+                    Some(Err(error)) => diagnostics.push(
+                        Severity::Error,
+                        element,
+                        format!("Invalid {} of subtitle {}: {}", name, id, error),
+                    ),
+                    None => diagnostics.push(
+                        Severity::Error,
+                        element,
+                        format!("Subtitle {} has no {}", id, name),
+                    ),
+                }
+            }
+            if let [begin, end] = times[..] {
+                if end <= begin {
+                    diagnostics.push(
+                        Severity::Error,
+                        element,
+                        format!("Subtitle {} ends before it begins", id),
+                    );
+                    continue;
+                }
+                if end - begin < MINIMUM_DURATION_MS {
+                    diagnostics.push(
+                        Severity::Warning,
+                        element,
+                        format!(
+                            "Subtitle {} is shown for {}ms, less than {}ms",
+                            id,
+                            end - begin,
+                            MINIMUM_DURATION_MS
+                        ),
+                    );
+                }
+                cues.push(Cue {
+                    element,
+                    id,
+                    region,
+                    begin,
+                    end,
+                });
+            }
+        }
+        cues.sort_by_key(|cue| cue.begin);
+        cues
+    }
+
+    /// Subtitles shown at the same time in the same region or in overlapping
+    /// regions, and short gaps between the subtitles of a region.
+    fn check_cues(&self, diagnostics: &mut Diagnostics, cues: &[Cue]) {
+        let mut overlapping_regions: Vec<(&str, &str)> = Vec::new();
+        let mut last_cues: HashMap<&str, &Cue> = HashMap::new();
+        for (index, cue) in cues.iter().enumerate() {
+            let region = match cue.region.as_deref() {
+                Some(region) => region,
+                None => continue,
+            };
+            // Cues are sorted by begin, so only the following ones that
+            // begin before this one ends can overlap it.
+            for other in cues[index + 1..]
+                .iter()
+                .take_while(|other| other.begin < cue.end)
+            {
+                let other_region = match other.region.as_deref() {
+                    Some(other_region) => other_region,
+                    None => continue,
+                };
+                if other_region == region {
+                    diagnostics.push(
+                        Severity::Warning,
+                        other.element,
+                        format!(
+                            "Subtitles {} and {} are shown at the same time in region {}",
+                            cue.id, other.id, region
+                        ),
+                    );
+                } else if !overlapping_regions.contains(&(region, other_region))
+                    && self.are_regions_overlapping(region, other_region)
+                {
+                    overlapping_regions.push((region, other_region));
+                    overlapping_regions.push((other_region, region));
+                    diagnostics.push(
+                        Severity::Error,
+                        other.element,
+                        format!(
+                            "Regions {} and {} overlap and are shown at the same time by subtitles {} and {}",
+                            region, other_region, cue.id, other.id
+                        ),
+                    );
+                }
+            }
+            if let Some(last) = last_cues.get(region) {
+                let gap = cue.begin - last.end;
+                if gap > 0 && gap < MINIMUM_GAP_MS {
+                    diagnostics.push(
+                        Severity::Warning,
+                        cue.element,
+                        format!(
+                            "Gap of {}ms between subtitles {} and {}, less than {}ms",
+                            gap, last.id, cue.id, MINIMUM_GAP_MS
+                        ),
+                    );
+                }
+            }
+            if last_cues.get(region).is_none_or(|last| last.end < cue.end) {
+                last_cues.insert(region, cue);
+            }
+        }
+    }
+
+    /// Conformance issues of the loaded document for the profiles it declares
+    /// (`ebuttm:conformsToStandard`, `ttp:profile`), sorted by line. Timing,
+    /// references and layout are checked for every document.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Diagnostics {
+            diagnostics: Vec::new(),
+        };
+        if self.tt.is_none() {
+            diagnostics.push(Severity::Error, None, "No document loaded".to_string());
+            return diagnostics.diagnostics;
+        }
+        let elements = match scan_elements(&self.source) {
+            Ok(elements) => elements,
+            Err(diagnostic) => return vec![diagnostic],
+        };
+        let profiles = self.get_profiles(&elements);
+        let has_root_extent = elements
+            .first()
+            .and_then(|root| root.get_attribute(Some(TTS), "extent"))
+            .is_some();
+        for element in elements.iter() {
+            check_lengths(&mut diagnostics, &profiles, element, has_root_extent);
+            check_features(&mut diagnostics, &profiles, element);
+            self.check_references(&mut diagnostics, element);
+        }
+        let cues = self.get_cues(&mut diagnostics, &profiles, &elements);
+        self.check_cues(&mut diagnostics, &cues);
+        let mut diagnostics = diagnostics.diagnostics;
+        diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(body: &str, layout: &str) -> Vec<Diagnostic> {
+        let xml = format!(
+            r#"<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling" xmlns:ebuttm="urn:ebu:tt:metadata">
+<tt:head>
+<tt:metadata><ebuttm:documentMetadata><ebuttm:conformsToStandard>urn:ebu:tt:distribution:2018-04</ebuttm:conformsToStandard></ebuttm:documentMetadata></tt:metadata>
+<tt:styling><tt:style xml:id="s1" tts:fontSize="100%"/></tt:styling>
+<tt:layout>{}</tt:layout>
+</tt:head>
+<tt:body><tt:div>
+{}
+</tt:div></tt:body>
+</tt:tt>"#,
+            layout, body
+        );
+        let mut subtitles = Subtitles::new();
+        subtitles.load(&xml).unwrap();
+        subtitles.validate()
+    }
+
+    const REGION: &str = r#"<tt:region xml:id="r1" tts:origin="10% 80%" tts:extent="80% 15%"/>"#;
+
+    #[test]
+    fn accepts_a_conforming_document() {
+        let body = r#"<tt:p xml:id="p1" region="r1" style="s1" begin="00:00:01.000" end="00:00:03.000"><tt:span>Hi</tt:span></tt:p>"#;
+        assert_eq!(validate(body, REGION), Vec::new());
+    }
+
+    #[test]
+    fn reports_disallowed_attributes_with_their_line() {
+        let body = r#"<tt:p xml:id="p1" region="r1" begin="00:00:01.000" end="00:00:03.000" tts:color="red"><tt:span>Hi</tt:span></tt:p>"#;
+        let diagnostics = validate(body, REGION);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].line, Some(8));
+        assert_eq!(
+            diagnostics[0].path,
+            "/tt:tt[1]/tt:body[1]/tt:div[1]/tt:p[1]"
+        );
+    }
+
+    #[test]
+    fn reports_missing_regions_and_timing() {
+        let body = r#"<tt:p xml:id="p1" begin="00:00:01.000" end="00:00:01.200"><tt:span>Hi</tt:span></tt:p>
+<tt:p xml:id="p2" region="r2" begin="00:00:03.000" end="00:00:02.000"><tt:span>Hi</tt:span></tt:p>"#;
+        let messages: Vec<String> = validate(body, REGION)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Subtitle p1 is not in a region and is not shown",
+                "Subtitle p1 is shown for 200ms, less than 500ms",
+                "Unknown region: r2",
+                "Subtitle p2 ends before it begins",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_overlaps_and_short_gaps() {
+        let layout = r#"<tt:region xml:id="r1" tts:origin="0% 50%" tts:extent="100% 50%"/>
+<tt:region xml:id="r2" tts:origin="0% 60%" tts:extent="100% 20%"/>"#;
+        let body = r#"<tt:p xml:id="p1" region="r1" begin="00:00:01.000" end="00:00:03.000"><tt:span>A</tt:span></tt:p>
+<tt:p xml:id="p2" region="r2" begin="00:00:02.000" end="00:00:04.000"><tt:span>B</tt:span></tt:p>
+<tt:p xml:id="p3" region="r1" begin="00:00:03.040" end="00:00:05.000"><tt:span>C</tt:span></tt:p>
+<tt:p xml:id="p4" region="r1" begin="00:00:04.000" end="00:00:06.000"><tt:span>D</tt:span></tt:p>"#;
+        let messages: Vec<String> = validate(body, layout)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Regions r1 and r2 overlap and are shown at the same time by subtitles p1 and p2",
+                "Gap of 40ms between subtitles p1 and p3, less than 80ms",
+                "Subtitles p3 and p4 are shown at the same time in region r1",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_units() {
+        let layout = r#"<tt:region xml:id="r1" tts:origin="1c 80%" tts:extent="80% 15px"/>"#;
+        let body = r#"<tt:p xml:id="p1" region="r1" begin="00:00:01.000" end="00:00:03.000"><tt:span>Hi</tt:span></tt:p>"#;
+        let messages: Vec<String> = validate(body, layout)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Unit c not allowed in tts:origin by EBU-TT-D: 1c 80%",
+                "Pixel lengths require a tts:extent on the root element: tts:extent",
+            ]
+        );
+    }
+}
@@ -0,0 +1,15 @@
+// Where the subtitles are drawn. The engine decides when a subtitle is shown
+// or hidden and renders its HTML, the view puts it on screen: the page in the
+// browser (see the wasm bindings), nothing in native builds.
+
+pub trait SubtitleView {
+    fn show_subtitle(&self, id: &str, html: &str);
+
+    fn hide_subtitle(&self, id: &str);
+
+    /// The subtitle is already on screen.
+    fn exist_subtitle(&self, id: &str) -> bool;
+
+    /// Warnings about the document, like overflowing regions.
+    fn log(&self, _message: &str) {}
+}
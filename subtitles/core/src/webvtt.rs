@@ -0,0 +1,386 @@
+// WebVTT files and cues, converted to a TTML document for the engine: the
+// samples of `wvtt` MP4 tracks and the segments of HLS subtitle playlists.
+// The payload markup keeps italics, bold and underline,
+// other tags (classes, voices, ruby, timestamps) keep only their text. Cue
+// settings are not mapped: the cues are shown centred at the bottom.
+use super::convert::{escape_attribute, escape_text, format_clock_time};
+
+/// A WebVTT cue with its times in milliseconds.
+pub struct VttCue {
+    pub id: Option<String>,
+    pub begin: i64,
+    pub end: i64,
+    pub payload: String,
+}
+
+/// `X-TIMESTAMP-MAP` of an HLS segment: the cue time `local` is the MPEG-2
+/// TS presentation time `mpegts` (90 kHz) of the media.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimestampMap {
+    pub mpegts: u64,
+    pub local: i64,
+}
+
+pub struct VttFile {
+    pub cues: Vec<VttCue>,
+    pub timestamp_map: Option<TimestampMap>,
+}
+
+/// "hh:mm:ss.ttt" or "mm:ss.ttt".
+fn parse_timestamp(timestamp: &str) -> Result<i64, String> {
+    let error = || format!("Invalid WebVTT timestamp: {}", timestamp);
+    let (clock, milliseconds) = timestamp.trim().split_once('.').ok_or_else(error)?;
+    if milliseconds.len() != 3 {
+        return Err(error());
+    }
+    let mut ms = milliseconds.parse::<i64>().map_err(|_| error())?;
+    let blocks: Vec<&str> = clock.split(':').collect();
+    if !(2..=3).contains(&blocks.len()) {
+        return Err(error());
+    }
+    let mut seconds: i64 = 0;
+    for block in blocks {
+        let value = block.parse::<i64>().map_err(|_| error())?;
+        seconds = seconds.checked_mul(60).ok_or_else(error)? + value;
+    }
+    ms += seconds.checked_mul(1000).ok_or_else(error)?;
+    Ok(ms)
+}
+
+fn parse_timestamp_map(value: &str) -> Result<TimestampMap, String> {
+    let error = || format!("Invalid X-TIMESTAMP-MAP: {}", value);
+    let mut mpegts = None;
+    let mut local = None;
+    for item in value.split(',') {
+        match item.trim().split_once(':') {
+            Some(("MPEGTS", ticks)) => mpegts = Some(ticks.parse::<u64>().map_err(|_| error())?),
+            Some(("LOCAL", timestamp)) => local = Some(parse_timestamp(timestamp)?),
+            _ => return Err(error()),
+        }
+    }
+    Ok(TimestampMap {
+        mpegts: mpegts.ok_or_else(error)?,
+        local: local.ok_or_else(error)?,
+    })
+}
+
+/// Cues of a WebVTT file. Comments, styles and regions are skipped.
+pub fn parse_webvtt(text: &str) -> Result<VttFile, String> {
+    let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut blocks = text.split("\n\n").map(|block| block.trim_matches('\n'));
+    let header = blocks.next().unwrap_or("");
+    if !header.starts_with("WEBVTT") {
+        return Err("Invalid WebVTT: missing WEBVTT header".to_string());
+    }
+    let timestamp_map = header
+        .lines()
+        .find_map(|line| line.strip_prefix("X-TIMESTAMP-MAP="))
+        .map(parse_timestamp_map)
+        .transpose()?;
+    let mut cues = Vec::new();
+    for block in blocks.filter(|block| !block.is_empty()) {
+        let mut lines = block.lines();
+        let mut line = lines.next().unwrap_or("");
+        let mut id = None;
+        if !line.contains("-->") {
+            if line.starts_with("NOTE") || line.starts_with("STYLE") || line.starts_with("REGION") {
+                continue;
+            }
+            id = Some(line.to_string());
+            line = match lines.next() {
+                Some(line) => line,
+                None => continue,
+            };
+        }
+        let (begin, rest) = line
+            .split_once("-->")
+            .ok_or_else(|| format!("Invalid WebVTT cue timing: {}", line))?;
+        // The settings follow the end time.
+        let end = rest.split_whitespace().next().unwrap_or("");
+        cues.push(VttCue {
+            id,
+            begin: parse_timestamp(begin)?,
+            end: parse_timestamp(end)?,
+            payload: lines.collect::<Vec<&str>>().join("\n"),
+        });
+    }
+    Ok(VttFile {
+        cues,
+        timestamp_map,
+    })
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+struct RunStyle {
+    italic: bool,
+    bold: bool,
+    underline: bool,
+}
+
+struct Run {
+    text: String,
+    style: RunStyle,
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&lrm;", "\u{200e}")
+        .replace("&rlm;", "\u{200f}")
+        .replace("&amp;", "&")
+}
+
+/// Lines of styled runs of a cue payload.
+fn parse_payload(payload: &str) -> Vec<Vec<Run>> {
+    let mut lines: Vec<Vec<Run>> = vec![Vec::new()];
+    let mut depths = [0usize; 3];
+    let mut text = String::new();
+    let mut chars = payload.chars();
+    let flush = |lines: &mut Vec<Vec<Run>>, text: &mut String, depths: &[usize; 3]| {
+        if !text.is_empty() {
+            let style = RunStyle {
+                italic: depths[0] > 0,
+                bold: depths[1] > 0,
+                underline: depths[2] > 0,
+            };
+            lines.last_mut().unwrap().push(Run {
+                text: decode_entities(text),
+                style,
+            });
+            text.clear();
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '<' => {
+                flush(&mut lines, &mut text, &depths);
+                let tag: String = chars.by_ref().take_while(|c| *c != '>').collect();
+                let (is_closing, tag) = match tag.strip_prefix('/') {
+                    Some(tag) => (true, tag),
+                    None => (false, tag.as_str()),
+                };
+                let name = tag.split(['.', ' ']).next().unwrap_or("");
+                let depth = match name {
+                    "i" => &mut depths[0],
+                    "b" => &mut depths[1],
+                    "u" => &mut depths[2],
+                    _ => continue,
+                };
+                *depth = if is_closing {
+                    depth.saturating_sub(1)
+                } else {
+                    *depth + 1
+                };
+            }
+            '\n' => {
+                flush(&mut lines, &mut text, &depths);
+                lines.push(Vec::new());
+            }
+            '\r' => {}
+            _ => text.push(c),
+        }
+    }
+    flush(&mut lines, &mut text, &depths);
+    lines
+}
+
+/// Spaces at the ends of a run are written as references, the document
+/// parser trims the text of elements and they separate the runs.
+pub fn escape_run_text(text: &str) -> String {
+    let trimmed = text.trim_matches(' ');
+    let start = text.len() - text.trim_start_matches(' ').len();
+    let end = if trimmed.is_empty() {
+        0
+    } else {
+        text.len() - text.trim_end_matches(' ').len()
+    };
+    format!(
+        "{}{}{}",
+        "&#32;".repeat(start),
+        escape_text(trimmed),
+        "&#32;".repeat(end)
+    )
+}
+
+fn get_style_refs(style: RunStyle) -> String {
+    let mut style_refs: Vec<&str> = Vec::new();
+    if style.italic {
+        style_refs.push("vtt-italic");
+    }
+    if style.bold {
+        style_refs.push("vtt-bold");
+    }
+    if style.underline {
+        style_refs.push("vtt-underline");
+    }
+    style_refs.join(" ")
+}
+
+fn get_paragraph_content(cues: &[&VttCue]) -> String {
+    cues.iter()
+        .flat_map(|cue| parse_payload(&cue.payload))
+        .map(|line| {
+            line.iter()
+                .map(|run| {
+                    format!(
+                        "<tt:span style=\"{}\">{}</tt:span>",
+                        get_style_refs(run.style),
+                        escape_run_text(&run.text)
+                    )
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("<tt:br/>")
+}
+
+/// TTML document with the cues. Cues with the same times (e.g. the cues of
+/// a `wvtt` sample) are shown together in one paragraph.
+pub fn to_ttml(cues: &[VttCue]) -> String {
+    let mut groups: Vec<Vec<&VttCue>> = Vec::new();
+    for cue in cues.iter().filter(|cue| cue.end > cue.begin) {
+        match groups.last_mut() {
+            Some(group) if group[0].begin == cue.begin && group[0].end == cue.end => {
+                group.push(cue)
+            }
+            _ => groups.push(vec![cue]),
+        }
+    }
+    let mut paragraphs = String::new();
+    let mut previous_begin = None;
+    let mut index = 0;
+    for group in groups.iter() {
+        let begin = group[0].begin;
+        index = if previous_begin == Some(begin) {
+            index + 1
+        } else {
+            0
+        };
+        previous_begin = Some(begin);
+        // Cue ids can be numbers, which are not valid XML nor HTML ids, so they
+        // are only kept as metadata.
+        let cue_id = group[0]
+            .id
+            .as_deref()
+            .filter(|id| !id.is_empty())
+            .map(|id| format!(" vtt:cueId=\"{}\"", escape_attribute(id)))
+            .unwrap_or_default();
+        paragraphs.push_str(&format!(
+            "      <tt:p xml:id=\"vtt-{}-{}\"{} begin=\"{}\" end=\"{}\" style=\"vtt\">{}</tt:p>\n",
+            begin,
+            index,
+            cue_id,
+            format_clock_time(begin, '.'),
+            format_clock_time(group[0].end, '.'),
+            get_paragraph_content(group)
+        ));
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<tt:tt xmlns:tt="http://www.w3.org/ns/ttml" xmlns:ttp="http://www.w3.org/ns/ttml#parameter" xmlns:tts="http://www.w3.org/ns/ttml#styling" xmlns:vtt="urn:webvtt:cue" ttp:timeBase="media">
+  <tt:head>
+    <tt:styling>
+      <tt:style xml:id="vtt" tts:textAlign="center"/>
+      <tt:style xml:id="vtt-italic" tts:fontStyle="italic"/>
+      <tt:style xml:id="vtt-bold" tts:fontWeight="bold"/>
+      <tt:style xml:id="vtt-underline" tts:textDecoration="underline"/>
+    </tt:styling>
+    <tt:layout>
+      <tt:region xml:id="vtt" tts:origin="10% 10%" tts:extent="80% 80%" tts:displayAlign="after"/>
+    </tt:layout>
+  </tt:head>
+  <tt:body>
+    <tt:div region="vtt">
+{}    </tt:div>
+  </tt:body>
+</tt:tt>"#,
+        paragraphs
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Subtitles;
+
+    fn cue(begin: i64, end: i64, payload: &str) -> VttCue {
+        VttCue {
+            id: None,
+            begin,
+            end,
+            payload: payload.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_the_payload_markup() {
+        let lines = parse_payload("<v Anna>Hello <i>there</i></v>\n<c.yellow>&lt;b&gt; &amp;</c>");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0][0].text, "Hello ");
+        assert!(!lines[0][0].style.italic);
+        assert_eq!(lines[0][1].text, "there");
+        assert!(lines[0][1].style.italic);
+        assert_eq!(lines[1][0].text, "<b> &");
+    }
+
+    #[test]
+    fn parses_webvtt_files() {
+        let file = parse_webvtt(
+            "WEBVTT\r\nX-TIMESTAMP-MAP=MPEGTS:900000,LOCAL:00:00:00.000\r\n\r\nNOTE a comment\r\n\r\n1\r\n00:01.000 --> 00:02.500 line:90%\r\nHello\r\nworld\r\n\r\n01:00:00.000 --> 01:00:01.000\r\n<i>Bye</i>\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            file.timestamp_map,
+            Some(TimestampMap {
+                mpegts: 900000,
+                local: 0
+            })
+        );
+        assert_eq!(file.cues.len(), 2);
+        assert_eq!(file.cues[0].id.as_deref(), Some("1"));
+        assert_eq!((file.cues[0].begin, file.cues[0].end), (1000, 2500));
+        assert_eq!(file.cues[0].payload, "Hello\nworld");
+        assert_eq!(file.cues[1].begin, 3600000);
+        assert!(parse_webvtt("1\n00:01.000 --> 00:02.000\nText").is_err());
+        assert!(parse_webvtt("WEBVTT\n\n00:01 --> 00:02\nText").is_err());
+    }
+
+    #[test]
+    fn loads_the_cues_in_the_engine() {
+        let mut subtitles = Subtitles::new();
+        subtitles.set_element_size(1000, 600);
+        subtitles
+            .load(&to_ttml(&[
+                cue(1000, 2000, "<b>One</b>"),
+                cue(1000, 2000, "Two"),
+                cue(3000, 4000, "Three & <u>four</u>"),
+            ]))
+            .unwrap();
+        let timed_subtitles = subtitles.get_timed_subtitles();
+        assert_eq!(timed_subtitles.len(), 2);
+        let first = &timed_subtitles[0].subtitle;
+        assert_eq!(first.lines.len(), 2);
+        assert_eq!(first.lines[1][0].text, "Two");
+        let second = &timed_subtitles[1];
+        assert_eq!((second.begin, second.end), (3000, 4000));
+        assert_eq!(second.subtitle.lines[0][0].text, "Three & ");
+    }
+
+    #[test]
+    fn keeps_numeric_cue_ids_as_metadata() {
+        let mut first = cue(1000, 2000, "One");
+        first.id = Some("1".to_string());
+        let mut second = cue(3000, 4000, "Two");
+        second.id = Some("2".to_string());
+        let ttml = to_ttml(&[first, second]);
+        assert!(ttml.contains("xml:id=\"vtt-1000-0\" vtt:cueId=\"1\""));
+        assert!(ttml.contains("xml:id=\"vtt-3000-0\" vtt:cueId=\"2\""));
+        let mut subtitles = Subtitles::new();
+        subtitles.set_element_size(1000, 600);
+        subtitles.load(&ttml).unwrap();
+        let timed_subtitles = subtitles.get_timed_subtitles();
+        assert_eq!(timed_subtitles[0].subtitle.id, "vtt-1000-0");
+        assert_eq!(timed_subtitles[1].subtitle.id, "vtt-3000-0");
+    }
+}
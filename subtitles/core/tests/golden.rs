@@ -0,0 +1,105 @@
+// Golden tests: the subtitles of `subtitles.xml` (the document of the demo
+// page) rendered at a range of timecodes, compared with the expected output
+// in `tests/golden`. After an intended change of the output, run the tests
+// with UPDATE_GOLDEN=1 to rewrite the files and review their diff.
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use subtitles_core::{format_clock_time, SubtitleView, Subtitles};
+
+const ELEMENT_WIDTH: i32 = 1280;
+const ELEMENT_HEIGHT: i32 = 720;
+
+/// One subtitle per region of the document, two regions at the same time,
+/// the first and last subtitles and times without subtitles.
+const TIMECODES: [i64; 10] = [
+    1000, 6000, 8500, 78500, 89000, 2588800, 2807000, 2827600, 3320000, 3330000,
+];
+
+fn load() -> Subtitles {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../subtitles.xml");
+    let xml = fs::read_to_string(path).unwrap();
+    let mut subtitles = Subtitles::new();
+    subtitles.set_element_size(ELEMENT_WIDTH, ELEMENT_HEIGHT);
+    subtitles.load(&xml).unwrap();
+    subtitles
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected =
+        fs::read_to_string(&path).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+    assert!(
+        expected == actual,
+        "{} differs from the output, run with UPDATE_GOLDEN=1 to update it",
+        name
+    );
+}
+
+/// The page: subtitles on screen and their HTML, in the order shown.
+#[derive(Clone, Default)]
+struct PageView {
+    shown: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl SubtitleView for PageView {
+    fn show_subtitle(&self, id: &str, html: &str) {
+        self.shown
+            .lock()
+            .unwrap()
+            .push((id.to_string(), html.to_string()));
+    }
+
+    fn hide_subtitle(&self, id: &str) {
+        self.shown.lock().unwrap().retain(|(shown, _)| shown != id);
+    }
+
+    fn exist_subtitle(&self, id: &str) -> bool {
+        self.shown
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(shown, _)| shown == id)
+    }
+}
+
+#[test]
+fn active_subtitles_at_timecodes() {
+    let mut subtitles = load();
+    let mut output = String::new();
+    for ms in TIMECODES {
+        let active = subtitles.get_subtitles_at(ms);
+        output.push_str(&format!("== {}\n", format_clock_time(ms, '.')));
+        output.push_str(&serde_json::to_string_pretty(&active).unwrap());
+        output.push('\n');
+    }
+    assert_golden("active_subtitles.txt", &output);
+}
+
+/// Plays the document like the page does, updating every 100ms, and takes
+/// the HTML on screen at the timecodes.
+#[test]
+fn page_html_at_timecodes() {
+    let mut subtitles = load();
+    let view = PageView::default();
+    subtitles.set_view(Some(Box::new(view.clone())));
+    let mut output = String::new();
+    let mut ms = 0;
+    for timecode in TIMECODES {
+        while ms <= timecode {
+            subtitles.update_subtitles_for_ms(ms);
+            ms += 100;
+        }
+        output.push_str(&format!("== {}\n", format_clock_time(timecode, '.')));
+        for (id, html) in view.shown.lock().unwrap().iter() {
+            output.push_str(&format!("{}\n{}\n", id, html));
+        }
+    }
+    assert_golden("page_html.txt", &output);
+}
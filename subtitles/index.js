@@ -31,11 +31,25 @@ const runWasm = async () => {
   subtitles.parse(text);
   console.timeEnd("parse");
 
+  // Pixel sizes depend on the container, refresh them when it changes
+  // (window resize, fullscreen, picture-in-picture...)
+  const updateElementSize = function () {
+    subtitles.setElementHeight(
+      subtitleContainerEl.offsetWidth,
+      subtitleContainerEl.offsetHeight
+    );
+  };
+  window.addEventListener("resize", updateElementSize);
+  document.addEventListener("fullscreenchange", updateElementSize);
+  document.addEventListener("webkitfullscreenchange", updateElementSize);
+
   const video = document.querySelector("video");
   //https://api-media.ccma.cat/pvideo/media.jsp?media=video&versio=vast&idint=6266073&profile=apptv_tv3&format=dm
   video.addEventListener("timeupdate", function () {
-    const ms = video.currentTime * 1000;
-    subtitles.updateSubtitlesForTimecode(ms);
+    subtitles.updateSubtitlesForTime(video.currentTime);
+  });
+  video.addEventListener("ratechange", function () {
+    subtitles.setPlaybackRate(video.playbackRate);
   });
 };
 runWasm();
@@ -1,9 +1,13 @@
 // The wasm-pack uses wasm-bindgen to build and generate JavaScript binding file.
 // Import the wasm-bindgen crate.
 use lazy_static::lazy_static;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::sync::Mutex;
+use subtitles_core::{
+    parse_caption_track, parse_track_kind, SubtitleView, Subtitles, TrackPreferences,
+};
 use wasm_bindgen::prelude::*;
-mod subtitles;
 
 #[wasm_bindgen]
 extern "C" {
@@ -12,6 +16,22 @@ extern "C" {
     #[wasm_bindgen(js_namespace = console)]
     fn log(s: &str);
     fn alert(s: &str);
+
+    #[wasm_bindgen(js_namespace = console)]
+    fn time(s: &str);
+
+    #[wasm_bindgen(js_namespace = console)]
+    fn timeEnd(s: &str);
+
+    fn showSubtitle(s: &str, text: &str);
+    fn hideSubtitle(s: &str);
+    fn existSubtitle(s: &str) -> bool;
+
+    /// JavaScript function that receives the URL of an image and returns the
+    /// URL to load it from, or `undefined` when it can not be loaded.
+    pub type ImageResolver;
+    #[wasm_bindgen(method, catch, js_name = call)]
+    fn call(this: &ImageResolver, context: &JsValue, url: &str) -> Result<JsValue, JsValue>;
 }
 
 /*
@@ -21,27 +41,481 @@ fn run() {
 }
  */
 
+/// Draws the subtitles with the functions of the page.
+struct PageView;
+
+impl SubtitleView for PageView {
+    fn show_subtitle(&self, id: &str, html: &str) {
+        time("show subtitle");
+        showSubtitle(id, html);
+        timeEnd("show subtitle");
+    }
+
+    fn hide_subtitle(&self, id: &str) {
+        hideSubtitle(id);
+    }
+
+    fn exist_subtitle(&self, id: &str) -> bool {
+        existSubtitle(id)
+    }
+
+    fn log(&self, message: &str) {
+        log(message);
+    }
+}
+
 lazy_static! {
-    static ref SUBTITLES: Mutex<subtitles::Subtitles> = Mutex::new(subtitles::Subtitles::new());
+    static ref SUBTITLES: Mutex<Subtitles> = {
+        let mut subtitles = Subtitles::new();
+        subtitles.set_view(Some(Box::new(PageView)));
+        Mutex::new(subtitles)
+    };
+}
+
+thread_local! {
+    // JS values can not be shared between threads, so the resolver is kept
+    // outside the engine.
+    static IMAGE_RESOLVER: RefCell<Option<ImageResolver>> = const { RefCell::new(None) };
+    // URLs already resolved by the current resolver.
+    static RESOLVED_IMAGES: RefCell<HashMap<String, Option<String>>> = RefCell::new(HashMap::new());
+}
+
+fn resolve_image(url: &str) -> Option<String> {
+    if let Some(resolved) = RESOLVED_IMAGES.with(|resolved| resolved.borrow().get(url).cloned()) {
+        return resolved;
+    }
+    let resolved = IMAGE_RESOLVER.with(|image_resolver| {
+        image_resolver
+            .borrow()
+            .as_ref()?
+            .call(&JsValue::NULL, url)
+            .ok()?
+            .as_string()
+    });
+    RESOLVED_IMAGES.with(|resolved_images| {
+        resolved_images
+            .borrow_mut()
+            .insert(url.to_string(), resolved.clone())
+    });
+    resolved
 }
 
 #[wasm_bindgen]
-pub fn parse(xml: &str) {
+pub fn parse(xml: &str) -> Result<(), JsValue> {
     let mut subtitles = SUBTITLES.lock().unwrap();
-    subtitles.load(xml);
-    return;
+    subtitles
+        .load(xml)
+        .map_err(|error| JsValue::from_str(&error))
 }
 
-#[wasm_bindgen]
-pub fn setElementHeight(width: i32, height: i32) {
+/// Merges a segment of a live or segmented stream (e.g. DASH stpp) into the
+/// loaded document.
+#[wasm_bindgen(js_name = appendDocument)]
+pub fn append_document(xml: &str) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .append_document(xml)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Init segment of a fragmented MP4 subtitle track (`stpp` or `wvtt`).
+#[wasm_bindgen(js_name = loadInitSegment)]
+pub fn load_init_segment(data: &[u8]) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .load_init_segment(data)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Media segment of the track of the init segment, its samples are appended
+/// like `appendDocument`.
+#[wasm_bindgen(js_name = appendMediaSegment)]
+pub fn append_media_segment(data: &[u8]) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .append_media_segment(data)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Seconds of subtitles kept behind the current time when appending.
+#[wasm_bindgen(js_name = setBackBuffer)]
+pub fn set_back_buffer(seconds: f64) -> Result<(), JsValue> {
+    let ms = seconds_to_ms(seconds)?;
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_back_buffer(ms)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Text tracks of a DASH MPD as JSON, with the URLs of their segments and
+/// their times in milliseconds. `url` resolves relative URLs. Live MPDs list
+/// the segments available at `now`, the wall clock time in seconds since the
+/// Unix epoch (`Date.now() / 1000`).
+#[wasm_bindgen(js_name = parseMpd)]
+pub fn parse_mpd(xml: &str, url: &str, now: Option<f64>) -> Result<String, JsValue> {
+    let now = now.map(seconds_to_ms).transpose()?;
+    let tracks =
+        subtitles_core::parse_mpd(xml, url, now).map_err(|error| JsValue::from_str(&error))?;
+    serde_json::to_string(&tracks).map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+/// Subtitle renditions of an HLS master playlist as JSON.
+#[wasm_bindgen(js_name = parseHlsMasterPlaylist)]
+pub fn parse_hls_master_playlist(text: &str, url: &str) -> Result<String, JsValue> {
+    let renditions = subtitles_core::parse_master_playlist(text, url)
+        .map_err(|error| JsValue::from_str(&error))?;
+    serde_json::to_string(&renditions).map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+/// Segments of an HLS media playlist as JSON, with their times in
+/// milliseconds from the first segment.
+#[wasm_bindgen(js_name = parseHlsMediaPlaylist)]
+pub fn parse_hls_media_playlist(text: &str, url: &str) -> Result<String, JsValue> {
+    let playlist = subtitles_core::parse_media_playlist(text, url)
+        .map_err(|error| JsValue::from_str(&error))?;
+    serde_json::to_string(&playlist).map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+/// WebVTT segment of an HLS subtitle playlist. `initialPts` is the 90 kHz
+/// presentation time of the start of the video, 0 if omitted.
+#[wasm_bindgen(js_name = appendWebVttSegment)]
+pub fn append_webvtt_segment(text: &str, initial_pts: Option<f64>) -> Result<(), JsValue> {
+    let initial_pts = initial_pts.unwrap_or(0.0);
+    if !initial_pts.is_finite() || initial_pts < 0.0 {
+        return Err(JsValue::from_str(&format!(
+            "Invalid initial PTS: {}",
+            initial_pts
+        )));
+    }
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .append_webvtt_segment(text, initial_pts as u64)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Payload of a DVB subtitle PES packet presented at `seconds`. Its bitmaps
+/// are shown as images of `getActiveSubtitles`, drawn over their regions.
+#[wasm_bindgen(js_name = appendDvbPes)]
+pub fn append_dvb_pes(data: &[u8], seconds: f64) -> Result<(), JsValue> {
+    let ms = seconds_to_ms(seconds)?;
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .append_dvb_pes(data, ms)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Chunk of an MPEG-2 transport stream, whose DVB subtitles or Teletext are
+/// decoded. `initialPts` is the 90 kHz presentation time of the start of the
+/// video, the first one of the stream if omitted.
+#[wasm_bindgen(js_name = appendTs)]
+pub fn append_ts(data: &[u8], initial_pts: Option<f64>) -> Result<(), JsValue> {
+    if let Some(pts) = initial_pts.filter(|pts| !pts.is_finite() || *pts < 0.0) {
+        return Err(JsValue::from_str(&format!("Invalid initial PTS: {}", pts)));
+    }
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .append_ts(data, initial_pts.map(|pts| pts as u64))
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Subtitle services of the transport stream as JSON, with their PID, kind,
+/// language and pages.
+#[wasm_bindgen(js_name = getTsStreams)]
+pub fn get_ts_streams() -> Result<String, JsValue> {
+    let subtitles = SUBTITLES.lock().unwrap();
+    serde_json::to_string(&subtitles.get_ts_streams())
+        .map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+/// Shows the subtitle service `index` of `getTsStreams`.
+#[wasm_bindgen(js_name = selectTsStream)]
+pub fn select_ts_stream(index: usize) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .select_ts_stream(index)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Payload of a Teletext PES packet presented at `seconds`. Every update of
+/// the selected page replaces the subtitles on screen.
+#[wasm_bindgen(js_name = appendTeletextPes)]
+pub fn append_teletext_pes(data: &[u8], seconds: f64) -> Result<(), JsValue> {
+    let ms = seconds_to_ms(seconds)?;
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .append_teletext_pes(data, ms)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Teletext subtitle page, 888 by default.
+#[wasm_bindgen(js_name = setTeletextPage)]
+pub fn set_teletext_page(page: u16) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_teletext_page(page)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Teletext national option subset (0 to 7, 5 for Spanish and Catalan),
+/// instead of the one signalled by the page.
+#[wasm_bindgen(js_name = setTeletextNationalOption)]
+pub fn set_teletext_national_option(option: Option<u8>) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_teletext_national_option(option)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// cc_data triplets of the video frame presented at `seconds`, with
+/// CEA-608 or CEA-708 closed captions.
+#[wasm_bindgen(js_name = appendCcData)]
+pub fn append_cc_data(data: &[u8], seconds: f64) -> Result<(), JsValue> {
+    let ms = seconds_to_ms(seconds)?;
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .append_cc_data(data, ms)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// SEI NAL unit of the video frame presented at `seconds`, whose closed
+/// captions are decoded.
+#[wasm_bindgen(js_name = appendSei)]
+pub fn append_sei(data: &[u8], seconds: f64) -> Result<(), JsValue> {
+    let ms = seconds_to_ms(seconds)?;
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .append_sei(data, ms)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Closed caption track: "CC1" to "CC4" or "SERVICE1" to "SERVICE63", CC1 by
+/// default.
+#[wasm_bindgen(js_name = setCaptionTrack)]
+pub fn set_caption_track(name: &str) -> Result<(), JsValue> {
+    let track = parse_caption_track(name).map_err(|error| JsValue::from_str(&error))?;
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_caption_track(track)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Registers a subtitle track of the title, or replaces the one with the same
+/// id. `kind` is "subtitles", "captions" or "forced", `role` the one of the
+/// manifest (e.g. "main" or "commentary").
+#[wasm_bindgen(js_name = addTrack)]
+pub fn add_track(id: &str, xml: &str, kind: &str, role: Option<String>) -> Result<(), JsValue> {
+    let kind = parse_track_kind(kind).map_err(|error| JsValue::from_str(&error))?;
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .add_track(id, xml, kind, role.as_deref())
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+#[wasm_bindgen(js_name = removeTrack)]
+pub fn remove_track(id: &str) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .remove_track(id)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Registered tracks as JSON, with their id, language, kind and role.
+#[wasm_bindgen(js_name = getTracks)]
+pub fn get_tracks() -> Result<String, JsValue> {
+    let subtitles = SUBTITLES.lock().unwrap();
+    serde_json::to_string(subtitles.get_tracks())
+        .map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+/// Id of the track on screen.
+#[wasm_bindgen(js_name = getSelectedTrack)]
+pub fn get_selected_track() -> Option<String> {
+    let subtitles = SUBTITLES.lock().unwrap();
+    subtitles.get_selected_track().map(|track| track.id.clone())
+}
+
+/// Shows a track, `undefined` for none. It stops the automatic selection
+/// until `setTrackPreferences`.
+#[wasm_bindgen(js_name = selectTrack)]
+pub fn select_track(id: Option<String>) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .select_track(id.as_deref())
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Selects the track of the first language of `languages` with one, the
+/// captions for the hard of hearing if `captions`, else the forced
+/// narratives of `audioLanguage`. Kept when tracks are added or removed.
+#[wasm_bindgen(js_name = setTrackPreferences)]
+pub fn set_track_preferences(
+    languages: Vec<String>,
+    captions: bool,
+    audio_language: Option<String>,
+) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_track_preferences(TrackPreferences {
+            languages,
+            captions,
+            audio_language,
+        })
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// DVB subtitle service to decode: the composition and ancillary page ids of
+/// the subtitling descriptor.
+#[wasm_bindgen(js_name = setDvbPages)]
+pub fn set_dvb_pages(composition_page_id: u16, ancillary_page_id: Option<u16>) {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles.set_dvb_pages(composition_page_id, ancillary_page_id);
+}
+
+#[wasm_bindgen(js_name = setElementHeight)]
+pub fn set_element_height(width: i32, height: i32) {
     let mut subtitles = SUBTITLES.lock().unwrap();
     subtitles.set_element_size(width, height);
-    return;
 }
 
+/// Milliseconds of a time in seconds from JS, like `video.currentTime`.
+fn seconds_to_ms(seconds: f64) -> Result<i64, JsValue> {
+    if !seconds.is_finite() {
+        return Err(JsValue::from_str(&format!("Invalid time: {}", seconds)));
+    }
+    Ok((seconds * 1000.0).round() as i64)
+}
+
+/// Media time in seconds, `video.currentTime`.
+#[wasm_bindgen(js_name = updateSubtitlesForTime)]
+pub fn update_subtitles_for_time(seconds: f64) -> Result<(), JsValue> {
+    let ms = seconds_to_ms(seconds)?;
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles.update_subtitles_for_ms(ms);
+    Ok(())
+}
+
+/// Media time in milliseconds, kept for existing pages. Use
+/// `updateSubtitlesForTime`.
+#[wasm_bindgen(js_name = updateSubtitlesForTimecode)]
+pub fn update_subtitles_for_timecode(ms: f64) -> Result<(), JsValue> {
+    update_subtitles_for_time(ms / 1000.0)
+}
+
+/// Media time in seconds at which the document starts, e.g. the start of
+/// the DASH period or of the live window.
+#[wasm_bindgen(js_name = setTimeOffset)]
+pub fn set_time_offset(seconds: f64) -> Result<(), JsValue> {
+    let ms = seconds_to_ms(seconds)?;
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles.set_time_offset(ms);
+    Ok(())
+}
+
+/// Media milliseconds per document millisecond, e.g. 25 / 23.976.
+#[wasm_bindgen(js_name = setTimeScale)]
+pub fn set_time_scale(scale: f64) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_time_scale(scale)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// User delay in seconds, positive values show the subtitles later.
+#[wasm_bindgen(js_name = setSubtitleDelay)]
+pub fn set_subtitle_delay(seconds: f64) -> Result<(), JsValue> {
+    let ms = seconds_to_ms(seconds)?;
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles.set_subtitle_delay(ms);
+    Ok(())
+}
+
+/// `video.playbackRate`, so that faster playback does not skip subtitles.
+#[wasm_bindgen(js_name = setPlaybackRate)]
+pub fn set_playback_rate(rate: f64) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_playback_rate(rate)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+#[wasm_bindgen(js_name = setPersonalizationEnabled)]
+pub fn set_personalization_enabled(enabled: bool) {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles.set_personalization_enabled(enabled);
+}
+
+#[wasm_bindgen(js_name = setPersonalizationFontScale)]
+pub fn set_personalization_font_scale(font_scale: f32) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_personalization_font_scale(font_scale)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Accepts any TTML colour, `undefined` restores the colour of the document.
+#[wasm_bindgen(js_name = setPersonalizationTextColor)]
+pub fn set_personalization_text_color(color: Option<String>) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_personalization_text_color(color.as_deref())
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Opacity from 0 to 1, `undefined` restores the background of the document.
+#[wasm_bindgen(js_name = setPersonalizationBackgroundOpacity)]
+pub fn set_personalization_background_opacity(opacity: Option<f32>) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_personalization_background_opacity(opacity)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Percentage of the element height, positive values move the subtitles up.
+#[wasm_bindgen(js_name = setPersonalizationVerticalOffset)]
+pub fn set_personalization_vertical_offset(percentage: f32) -> Result<(), JsValue> {
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    subtitles
+        .set_personalization_vertical_offset(percentage)
+        .map_err(|error| JsValue::from_str(&error))
+}
+
+/// Subtitles on screen as JSON: region geometry and computed styles of every
+/// run of text in display pixels, for apps that draw the subtitles themselves.
+#[wasm_bindgen(js_name = getActiveSubtitles)]
+pub fn get_active_subtitles() -> Result<String, JsValue> {
+    let subtitles = SUBTITLES.lock().unwrap();
+    serde_json::to_string(&subtitles.get_active_subtitles())
+        .map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+/// Conformance issues of the loaded document as JSON: severity, message, path
+/// and line of every EBU-TT-D or IMSC1 violation.
 #[wasm_bindgen]
-pub fn updateSubtitlesForTimecode(ms: i32) {
+pub fn validate() -> Result<String, JsValue> {
     let subtitles = SUBTITLES.lock().unwrap();
-    subtitles.update_subtitles_for_ms(ms);
-    return;
+    serde_json::to_string(&subtitles.validate())
+        .map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+/// Function that resolves the URLs of the external images of the IMSC image
+/// profile, `undefined` loads them relative to the page.
+#[wasm_bindgen(js_name = setImageResolver)]
+pub fn set_image_resolver(resolver: Option<ImageResolver>) {
+    let has_resolver = resolver.is_some();
+    IMAGE_RESOLVER.with(|image_resolver| *image_resolver.borrow_mut() = resolver);
+    RESOLVED_IMAGES.with(|resolved_images| resolved_images.borrow_mut().clear());
+    // The images on screen are resolved without the lock, the resolver may
+    // call the other exports. Showing them again uses the resolved URLs.
+    if has_resolver {
+        let urls = SUBTITLES.lock().unwrap().get_visible_image_urls();
+        for url in urls.iter() {
+            resolve_image(url);
+        }
+    }
+    let mut subtitles = SUBTITLES.lock().unwrap();
+    if has_resolver {
+        subtitles.set_image_resolver(Some(Box::new(resolve_image)));
+    } else {
+        subtitles.set_image_resolver(None);
+    }
 }
@@ -16,7 +16,7 @@ function execute() {
 execute "$@"
 
 inotifywait --recursive --monitor --format "%e %w%f" \
---event modify,move,create,delete ./src \
+--event modify,move,create,delete ./src $(test -d ./core && echo ./core/src) \
 | while read changed; do
     counter=$((counter+1))
     echo "Detected change n. $counter" 